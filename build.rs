@@ -0,0 +1,49 @@
+//! Converts the teapot arrays into the binary mesh format, so the crate
+//! includes a small file instead of compiling thousands of lines of `const`
+//! arrays on every build.
+
+use std::{env, fs, path::Path};
+
+#[allow(dead_code)]
+#[path = "src/meshfile/format.rs"]
+mod format;
+
+#[derive(Copy, Clone)]
+pub struct Vertex {
+    position: (f32, f32, f32),
+}
+
+#[derive(Copy, Clone)]
+pub struct Normal {
+    normal: (f32, f32, f32),
+}
+
+include!("data/teapot.rs");
+
+fn main() {
+    println!("cargo:rerun-if-changed=data/teapot.rs");
+    println!("cargo:rerun-if-changed=src/meshfile/format.rs");
+
+    let positions: Vec<[f32; 3]> = VERTICES
+        .iter()
+        .map(|v| [v.position.0, v.position.1, v.position.2])
+        .collect();
+    let normals: Vec<[f32; 3]> = NORMALS
+        .iter()
+        .map(|n| [n.normal.0, n.normal.1, n.normal.2])
+        .collect();
+    let indices: Vec<u32> = INDICES.iter().map(|&i| i as u32).collect();
+    let bytes = format::write(
+        &format::Streams {
+            positions: &positions,
+            normals: &normals,
+            uvs: &[],
+            indices: &indices,
+        },
+        format::Encoding::default(),
+    );
+
+    let out_dir = env::var("OUT_DIR").expect("failed to read OUT_DIR!");
+    fs::write(Path::new(&out_dir).join("teapot.mesh"), bytes)
+        .expect("failed to write teapot.mesh!");
+}
@@ -1,10 +1,3 @@
-#[derive(Copy, Clone)]
-pub struct Vertex {
-    position: (f32, f32, f32),
-}
-
-implement_vertex!(Vertex, position);
-
 pub const VERTICES: [Vertex; 531] = [
     Vertex {
         position: (0.0, 0.0, 0.0),
@@ -1602,13 +1595,6 @@ pub const VERTICES: [Vertex; 531] = [
     },
 ];
 
-#[derive(Copy, Clone)]
-pub struct Normal {
-    normal: (f32, f32, f32),
-}
-
-implement_vertex!(Normal, normal);
-
 pub const NORMALS: [Normal; 531] = [
     Normal {
         normal: (0.0, 0.0, 0.0),
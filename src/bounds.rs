@@ -0,0 +1,305 @@
+use crate::math::{self, Mat4, Vec3};
+
+/// The twelve edges of a box as pairs of indices into `Aabb::corners`, or
+/// into any eight corners numbered the same way.
+pub const BOX_EDGES: [[usize; 2]; 12] = [
+    [0, 1],
+    [2, 3],
+    [4, 5],
+    [6, 7],
+    [0, 2],
+    [1, 3],
+    [4, 6],
+    [5, 7],
+    [0, 4],
+    [1, 5],
+    [2, 6],
+    [3, 7],
+];
+
+/// Axis-aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// An inverted box that any `extend` call will replace.
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: [f32::INFINITY; 3],
+            max: [f32::NEG_INFINITY; 3],
+        }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Aabb {
+        let mut aabb = Aabb::empty();
+        for point in points {
+            aabb.extend(point);
+        }
+        aabb
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|i| self.min[i] > self.max[i])
+    }
+
+    pub fn extend(&mut self, point: Vec3) {
+        self.min = [0, 1, 2].map(|i| self.min[i].min(point[i]));
+        self.max = [0, 1, 2].map(|i| self.max[i].max(point[i]));
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        let mut out = *self;
+        out.extend(other.min);
+        out.extend(other.max);
+        out
+    }
+
+    pub fn center(&self) -> Vec3 {
+        math::scale(math::add(self.min, self.max), 0.5)
+    }
+
+    pub fn extents(&self) -> Vec3 {
+        math::sub(self.max, self.min)
+    }
+
+    /// Corner `i` takes its x, y and z from `max` where bits 0, 1 and 2 of `i`
+    /// are set and from `min` elsewhere.
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            [a[0], a[1], a[2]],
+            [b[0], a[1], a[2]],
+            [a[0], b[1], a[2]],
+            [b[0], b[1], a[2]],
+            [a[0], a[1], b[2]],
+            [b[0], a[1], b[2]],
+            [a[0], b[1], b[2]],
+            [b[0], b[1], b[2]],
+        ]
+    }
+
+    /// The box enclosing this one after an affine transform (Arvo's method).
+    pub fn transform(&self, m: &Mat4) -> Aabb {
+        let translation = [m[3][0], m[3][1], m[3][2]];
+        let mut out = Aabb {
+            min: translation,
+            max: translation,
+        };
+
+        for (col, axis) in m.iter().take(3).enumerate() {
+            for (row, &factor) in axis.iter().take(3).enumerate() {
+                let a = factor * self.min[col];
+                let b = factor * self.max[col];
+                out.min[row] += a.min(b);
+                out.max[row] += a.max(b);
+            }
+        }
+        out
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl Sphere {
+    /// A sphere centred on the bounding box of `points`, just large enough to
+    /// hold all of them.
+    pub fn from_points(points: &[Vec3]) -> Sphere {
+        let center = Aabb::from_points(points.iter().copied()).center();
+        let radius = points
+            .iter()
+            .map(|&p| math::length(math::sub(p, center)))
+            .fold(0.0, f32::max);
+        Sphere { center, radius }
+    }
+
+    pub fn transform(&self, m: &Mat4) -> Sphere {
+        Sphere {
+            center: math::transform_point(m, self.center),
+            radius: self.radius * max_scale(m),
+        }
+    }
+}
+
+/// The largest factor by which `m` stretches any direction, bounded by its
+/// longest basis vector.
+pub fn max_scale(m: &Mat4) -> f32 {
+    (0..3)
+        .map(|col| math::length([m[col][0], m[col][1], m[col][2]]))
+        .fold(0.0, f32::max)
+}
+
+/// The six clip planes of a view volume, each `[a, b, c, d]` with the normal
+/// pointing inwards so that `a*x + b*y + c*z + d >= 0` inside.
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum {
+    pub planes: [[f32; 4]; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from a combined `perspective * view` (Gribb–Hartmann).
+    pub fn from_matrix(m: &Mat4) -> Frustum {
+        let row = |i: usize| [m[0][i], m[1][i], m[2][i], m[3][i]];
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let combine = |a: [f32; 4], b: [f32; 4], sign: f32| {
+            let plane = [
+                a[0] + sign * b[0],
+                a[1] + sign * b[1],
+                a[2] + sign * b[2],
+                a[3] + sign * b[3],
+            ];
+            let len = math::length([plane[0], plane[1], plane[2]]);
+            plane.map(|v| v / len)
+        };
+
+        Frustum {
+            planes: [
+                combine(r3, r0, 1.0),
+                combine(r3, r0, -1.0),
+                combine(r3, r1, 1.0),
+                combine(r3, r1, -1.0),
+                combine(r3, r2, 1.0),
+                combine(r3, r2, -1.0),
+            ],
+        }
+    }
+
+    fn distance(plane: &[f32; 4], point: Vec3) -> f32 {
+        plane[0] * point[0] + plane[1] * point[1] + plane[2] * point[2] + plane[3]
+    }
+
+    pub fn intersects_sphere(&self, sphere: &Sphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| Frustum::distance(plane, sphere.center) >= -sphere.radius)
+    }
+
+    /// Conservative box test: only rejects boxes entirely behind one plane.
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            let farthest = [0, 1, 2].map(|i| {
+                if plane[i] >= 0.0 {
+                    aabb.max[i]
+                } else {
+                    aabb.min[i]
+                }
+            });
+            Frustum::distance(plane, farthest) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 90° square frustum from a camera at z = -10 looking along +z, so it
+    /// spans z = -9 to 90 and widens by one unit sideways per unit forwards.
+    fn frustum() -> Frustum {
+        let perspective = math::perspective_matrix(90f32.to_radians(), 100, 100, 1.0, 100.0);
+        let view = math::view_matrix(&[0.0, 0.0, -10.0], &[0.0, 0.0, 1.0], &[0.0, 1.0, 0.0]);
+        Frustum::from_matrix(&math::mul(&perspective, &view))
+    }
+
+    fn sphere(center: Vec3, radius: f32) -> Sphere {
+        Sphere { center, radius }
+    }
+
+    fn aabb(center: Vec3, half: f32) -> Aabb {
+        Aabb {
+            min: center.map(|c| c - half),
+            max: center.map(|c| c + half),
+        }
+    }
+
+    #[test]
+    fn frustum_planes_face_inwards() {
+        let frustum = frustum();
+        for plane in &frustum.planes {
+            assert!((math::length([plane[0], plane[1], plane[2]]) - 1.0).abs() < 1e-5);
+            assert!(Frustum::distance(plane, [0.0, 0.0, 0.0]) > 0.0);
+        }
+        // Ten units in front of the camera the sides are ten units out.
+        let on_sides = [
+            [10.0, 0.0, 0.0],
+            [-10.0, 0.0, 0.0],
+            [0.0, 10.0, 0.0],
+            [0.0, -10.0, 0.0],
+        ];
+        for point in on_sides {
+            let nearest = frustum
+                .planes
+                .iter()
+                .map(|plane| Frustum::distance(plane, point).abs())
+                .fold(f32::INFINITY, f32::min);
+            assert!(nearest < 1e-4, "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn spheres_inside_outside_and_straddling() {
+        let frustum = frustum();
+        let cases = [
+            (sphere([0.0, 0.0, 0.0], 1.0), true),
+            (sphere([5.0, -5.0, 20.0], 2.0), true),
+            // Straddling the right, near and far planes.
+            (sphere([10.0, 0.0, 0.0], 1.0), true),
+            (sphere([0.0, 0.0, -9.5], 1.0), true),
+            (sphere([0.0, 0.0, 90.5], 1.0), true),
+            // Beyond the right, bottom, near and far planes.
+            (sphere([20.0, 0.0, 0.0], 1.0), false),
+            (sphere([0.0, -20.0, 0.0], 1.0), false),
+            (sphere([0.0, 0.0, -12.0], 1.0), false),
+            (sphere([0.0, 0.0, 92.0], 1.0), false),
+        ];
+        for (sphere, inside) in cases {
+            assert_eq!(frustum.intersects_sphere(&sphere), inside, "{:?}", sphere);
+        }
+    }
+
+    #[test]
+    fn boxes_inside_outside_and_straddling() {
+        let frustum = frustum();
+        let cases = [
+            (aabb([0.0, 0.0, 0.0], 1.0), true),
+            (aabb([-5.0, 5.0, 20.0], 2.0), true),
+            // Straddling the left, top and near planes.
+            (aabb([-10.0, 0.0, 0.0], 1.0), true),
+            (aabb([0.0, 10.0, 0.0], 1.0), true),
+            (aabb([0.0, 0.0, -9.5], 1.0), true),
+            // Beyond the left, top, near and far planes.
+            (aabb([-20.0, 0.0, 0.0], 1.0), false),
+            (aabb([0.0, 20.0, 0.0], 1.0), false),
+            (aabb([0.0, 0.0, -12.0], 1.0), false),
+            (aabb([0.0, 0.0, 92.0], 1.0), false),
+        ];
+        for (aabb, inside) in cases {
+            assert_eq!(frustum.intersects_aabb(&aabb), inside, "{:?}", aabb);
+        }
+    }
+
+    #[test]
+    fn transformed_boxes_enclose_transformed_corners() {
+        let aabb = Aabb {
+            min: [-1.0, -2.0, 0.5],
+            max: [2.0, 1.0, 3.0],
+        };
+        let m = math::mul(
+            &math::translation([4.0, -3.0, 2.0]),
+            &math::rotation([30.0, 45.0, 60.0]),
+        );
+        let transformed = aabb.transform(&m);
+        let expected = Aabb::from_points(aabb.corners().map(|c| math::transform_point(&m, c)));
+        for i in 0..3 {
+            assert!((transformed.min[i] - expected.min[i]).abs() < 1e-5);
+            assert!((transformed.max[i] - expected.max[i]).abs() < 1e-5);
+        }
+        assert_eq!(aabb.transform(&math::identity()), aabb);
+    }
+}
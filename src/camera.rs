@@ -0,0 +1,274 @@
+use crate::bounds::{Aabb, Sphere};
+use crate::math::{self, Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// How far `orbit` and `snap` let the camera tilt above or below its target,
+/// short of straight up or down where `up` would no longer define a view.
+const PITCH_LIMIT: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Camera {
+    pub position: Vec3,
+    pub target: Vec3,
+    #[serde(default = "default_up")]
+    pub up: Vec3,
+    /// Vertical field of view in degrees.
+    #[serde(default = "default_fov")]
+    pub fov: f32,
+    #[serde(default = "default_znear")]
+    pub znear: f32,
+    #[serde(default = "default_zfar")]
+    pub zfar: f32,
+    /// Fit the camera to the scene bounds when the scene is loaded. Scenes
+    /// that leave out `camera` entirely get this; explicit cameras keep their
+    /// placement unless they opt in.
+    #[serde(default)]
+    pub frame_on_load: bool,
+}
+
+fn default_up() -> Vec3 {
+    [0.0, 1.0, 0.0]
+}
+
+fn default_fov() -> f32 {
+    60.0
+}
+
+fn default_znear() -> f32 {
+    0.1
+}
+
+fn default_zfar() -> f32 {
+    1024.0
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera {
+            position: [2.0, -1.0, 1.0],
+            target: [0.0, 0.0, 2.0],
+            up: default_up(),
+            fov: default_fov(),
+            znear: default_znear(),
+            zfar: default_zfar(),
+            frame_on_load: true,
+        }
+    }
+}
+
+impl Camera {
+    pub fn direction(&self) -> Vec3 {
+        math::sub(self.target, self.position)
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        math::view_matrix(&self.position, &self.direction(), &self.up)
+    }
+
+    pub fn perspective(&self, width: u32, height: u32) -> Mat4 {
+        math::perspective_matrix(self.fov.to_radians(), width, height, self.znear, self.zfar)
+    }
+
+    /// Rotates the camera around its target by the given yaw and pitch (radians).
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        let offset = math::sub(self.position, self.target);
+        let radius = math::length(offset);
+        if radius == 0.0 {
+            return;
+        }
+
+        let up = math::normalize(self.up);
+        let height = math::dot(offset, up).clamp(-radius, radius);
+        let planar = math::sub(offset, math::scale(up, height));
+        let right = math::normalize(math::cross(up, offset));
+        let forward = math::normalize(planar);
+
+        let current_pitch = (height / radius).asin();
+        let new_pitch = (current_pitch + pitch).clamp(-PITCH_LIMIT, PITCH_LIMIT);
+
+        let forward = math::normalize(math::add(
+            math::scale(forward, yaw.cos()),
+            math::scale(right, yaw.sin()),
+        ));
+
+        let offset = math::add(
+            math::scale(forward, radius * new_pitch.cos()),
+            math::scale(up, radius * new_pitch.sin()),
+        );
+        self.position = math::add(self.target, offset);
+    }
+
+    /// Moves the camera to look at its target from `side`, keeping its
+    /// distance: from the +X side along -X, and so on. Sides along `up` stop
+    /// just short of it, at the steepest pitch `orbit` reaches.
+    pub fn snap(&mut self, side: Vec3) {
+        let radius = math::length(math::sub(self.position, self.target));
+        let up = math::normalize(self.up);
+        let side = math::normalize(side);
+        let height = math::dot(side, up);
+
+        let offset = if height.abs() < PITCH_LIMIT.sin() {
+            side
+        } else {
+            // Keep facing the way the camera faced before, tilted as far as
+            // it goes.
+            let mut forward = math::sub(self.position, self.target);
+            forward = math::sub(forward, math::scale(up, math::dot(forward, up)));
+            if math::length(forward) == 0.0 {
+                forward = math::cross(up, [1.0, 0.0, 0.0]);
+            }
+            math::add(
+                math::scale(math::normalize(forward), PITCH_LIMIT.cos()),
+                math::scale(up, PITCH_LIMIT.sin() * height.signum()),
+            )
+        };
+        self.position = math::add(self.target, math::scale(offset, radius));
+    }
+
+    /// Moves the camera towards (factor < 1) or away from (factor > 1) its
+    /// target, scaling the clip planes along so the scene stays inside them.
+    pub fn zoom(&mut self, factor: f32) {
+        let offset = math::sub(self.position, self.target);
+        self.position = math::add(self.target, math::scale(offset, factor));
+        self.znear *= factor;
+        self.zfar *= factor;
+    }
+
+    /// Aims at the centre of `bounds` from the current viewing direction, backs
+    /// off until its bounding sphere fits both the vertical and horizontal
+    /// field of view, and tightens the near/far planes around it.
+    pub fn frame(&mut self, bounds: &Aabb, width: u32, height: u32) {
+        if bounds.is_empty() {
+            return;
+        }
+
+        let center = bounds.center();
+        let radius = (math::length(bounds.extents()) / 2.0).max(f32::EPSILON);
+
+        let vertical = self.fov.to_radians();
+        let horizontal = 2.0 * ((vertical / 2.0).tan() * width as f32 / height as f32).atan();
+        let distance = radius / (vertical.min(horizontal) / 2.0).sin();
+
+        let mut direction = math::normalize(self.direction());
+        if math::length(direction) == 0.0 {
+            direction = [0.0, 0.0, 1.0];
+        }
+
+        self.target = center;
+        self.position = math::sub(center, math::scale(direction, distance));
+        self.znear = ((distance - radius) * 0.9).max(distance * 0.001);
+        self.zfar = (distance + radius) * 1.1;
+    }
+
+    /// Approximate on-screen diameter of `sphere` in pixels, for a viewport
+    /// `height` pixels tall. Spheres around the camera count as infinite.
+    pub fn projected_size(&self, sphere: &Sphere, height: u32) -> f32 {
+        let distance = math::length(math::sub(sphere.center, self.position));
+        if distance <= sphere.radius {
+            return f32::INFINITY;
+        }
+        let angle = (sphere.radius / distance).asin();
+        angle.tan() / (self.fov.to_radians() / 2.0).tan() * height as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bounds::Frustum;
+
+    fn distance(a: Vec3, b: Vec3) -> f32 {
+        math::length(math::sub(a, b))
+    }
+
+    #[test]
+    fn framed_spheres_fit_the_view() {
+        let bounds = Aabb {
+            min: [4.0, 1.0, -5.0],
+            max: [6.0, 3.0, -1.0],
+        };
+        let sphere = Sphere {
+            center: bounds.center(),
+            radius: math::length(bounds.extents()) / 2.0,
+        };
+        // Wide and tall viewports, so each field of view is the tighter one once.
+        for (width, height) in [(1600, 900), (400, 800)] {
+            let mut camera = Camera::default();
+            camera.frame(&bounds, width, height);
+            assert_eq!(camera.target, sphere.center);
+
+            let view = math::mul(&camera.perspective(width, height), &camera.view_matrix());
+            let frustum = Frustum::from_matrix(&view);
+            let gaps: Vec<f32> = frustum
+                .planes
+                .iter()
+                .map(|[a, b, c, d]| {
+                    let [x, y, z] = sphere.center;
+                    a * x + b * y + c * z + d - sphere.radius
+                })
+                .collect();
+            // Inside every plane, and touching the tighter pair of sides.
+            assert!(gaps.iter().all(|&gap| gap > -1e-3), "{:?}", gaps);
+            let tightest = gaps[..4].iter().copied().fold(f32::INFINITY, f32::min);
+            assert!(tightest < 1e-3, "{:?}", gaps);
+        }
+    }
+
+    #[test]
+    fn orbits_keep_their_distance() {
+        let mut camera = Camera {
+            position: [0.0, 0.0, -5.0],
+            target: [0.0, 0.0, 0.0],
+            ..Camera::default()
+        };
+        camera.orbit(std::f32::consts::FRAC_PI_2, 0.0);
+        // A quarter turn one way swings the camera from -Z round to -X.
+        assert!(
+            distance(camera.position, [-5.0, 0.0, 0.0]) < 1e-4,
+            "{:?}",
+            camera.position
+        );
+        // Pitching past straight up stops just short of it.
+        camera.orbit(0.0, 10.0);
+        assert!((distance(camera.position, camera.target) - 5.0).abs() < 1e-4);
+        let pitch = (camera.position[1] / 5.0).asin();
+        assert!((pitch - PITCH_LIMIT).abs() < 1e-3, "{}", pitch);
+    }
+
+    #[test]
+    fn zooms_scale_distance_and_clip_planes() {
+        let mut camera = Camera::default();
+        let (before, znear, zfar) = (
+            distance(camera.position, camera.target),
+            camera.znear,
+            camera.zfar,
+        );
+        camera.zoom(0.5);
+        assert!((distance(camera.position, camera.target) - before / 2.0).abs() < 1e-5);
+        assert_eq!((camera.znear, camera.zfar), (znear / 2.0, zfar / 2.0));
+        assert_eq!(camera.target, Camera::default().target);
+    }
+
+    #[test]
+    fn projected_sizes_shrink_with_distance() {
+        let camera = Camera {
+            position: [0.0, 0.0, 0.0],
+            target: [0.0, 0.0, 1.0],
+            ..Camera::default()
+        };
+        let at = |z: f32| Sphere {
+            center: [0.0, 0.0, z],
+            radius: 1.0,
+        };
+        let (near, far) = (
+            camera.projected_size(&at(50.0), 600),
+            camera.projected_size(&at(100.0), 600),
+        );
+        assert!((near / far - 2.0).abs() < 1e-3, "{} {}", near, far);
+
+        // A sphere just filling the 60° field of view covers the viewport.
+        let filling = camera.projected_size(&at(2.0), 600);
+        assert!((filling - 600.0).abs() < 0.5, "{}", filling);
+        assert_eq!(camera.projected_size(&at(0.5), 600), f32::INFINITY);
+    }
+}
@@ -0,0 +1,126 @@
+//! Frame pacing. The clock measures the time between frames, divides it into
+//! fixed simulation steps, and says when the next frame is due under an
+//! optional frame-rate cap.
+
+use std::time::{Duration, Instant};
+
+/// Frames longer than this, after a stall or while the window was dragged,
+/// count as this long, so the simulation does not try to catch up on all of
+/// it at once.
+const MAX_DELTA: Duration = Duration::from_millis(250);
+
+/// What a frame is to show.
+#[derive(Copy, Clone, Debug)]
+pub struct FrameTime {
+    /// Time since the previous frame; zero for the first.
+    pub delta: Duration,
+    /// Number of fixed steps to simulate before drawing.
+    pub steps: u32,
+}
+
+pub struct FrameClock {
+    /// Length of one simulation step.
+    step: Duration,
+    /// Shortest time between the starts of two frames, or `None` to draw as
+    /// often as the event loop, or vsync, allows.
+    min_frame: Option<Duration>,
+    last_frame: Option<Instant>,
+    /// Time not yet simulated.
+    accumulator: Duration,
+}
+
+impl FrameClock {
+    pub fn new(step: Duration, fps_cap: Option<f32>) -> Result<FrameClock, String> {
+        let mut clock = FrameClock {
+            step,
+            min_frame: None,
+            last_frame: None,
+            accumulator: Duration::ZERO,
+        };
+        clock.set_fps_cap(fps_cap)?;
+        Ok(clock)
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// The frame-rate cap in frames per second, if there is one.
+    pub fn fps_cap(&self) -> Option<f32> {
+        self.min_frame.map(|min| 1.0 / min.as_secs_f32())
+    }
+
+    /// Caps the frame rate at `fps`, or lifts the cap for `None`. Caps of
+    /// zero or less count as no cap; caps so small that a frame would not fit
+    /// in a `Duration`, or NaN, are refused and leave the cap as it was.
+    pub fn set_fps_cap(&mut self, fps: Option<f32>) -> Result<(), String> {
+        self.min_frame = match fps {
+            Some(fps) if fps > 0.0 || fps.is_nan() => Some(
+                Duration::try_from_secs_f32(1.0 / fps)
+                    .map_err(|_| format!("a frame-rate cap of {} fps is out of range", fps))?,
+            ),
+            _ => None,
+        };
+        Ok(())
+    }
+
+    /// When the next frame should start: `None` if it is due already, or if
+    /// the cap puts it further off than an `Instant` can reach, which counts
+    /// as no deadline.
+    pub fn next_frame(&self) -> Option<Instant> {
+        let due = self.last_frame?.checked_add(self.min_frame?)?;
+        (due > Instant::now()).then_some(due)
+    }
+
+    /// Starts a frame, measuring the time since the last one.
+    pub fn tick(&mut self) -> FrameTime {
+        let now = Instant::now();
+        let delta = self.last_frame.map_or(Duration::ZERO, |last| now - last);
+        self.last_frame = Some(now);
+
+        self.accumulator += delta.min(MAX_DELTA);
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        FrameTime { delta, steps }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: Duration = Duration::from_millis(10);
+
+    #[test]
+    fn fps_caps() {
+        let mut clock = FrameClock::new(STEP, Some(50.0)).unwrap();
+        assert!((clock.fps_cap().unwrap() - 50.0).abs() < 1e-3);
+        clock.set_fps_cap(Some(0.0)).unwrap();
+        assert_eq!(clock.fps_cap(), None);
+        clock.set_fps_cap(Some(-5.0)).unwrap();
+        assert_eq!(clock.fps_cap(), None);
+    }
+
+    #[test]
+    fn out_of_range_fps_caps_are_refused() {
+        for fps in [1e-39, f32::MIN_POSITIVE, f32::NAN] {
+            assert!(FrameClock::new(STEP, Some(fps)).is_err(), "{}", fps);
+        }
+        let mut clock = FrameClock::new(STEP, Some(30.0)).unwrap();
+        assert!(clock.set_fps_cap(Some(1e-39)).is_err());
+        assert!((clock.fps_cap().unwrap() - 30.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn tiny_fps_caps_set_no_deadline() {
+        let mut clock = FrameClock::new(STEP, Some(1e-19)).unwrap();
+        clock.tick();
+        assert_eq!(clock.next_frame(), None);
+
+        clock.set_fps_cap(Some(1.0)).unwrap();
+        assert!(clock.next_frame().is_some());
+    }
+}
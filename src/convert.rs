@@ -0,0 +1,411 @@
+use crate::gltf;
+use crate::math;
+use crate::mesh::{Mesh, Normal};
+use crate::meshfile::{self, Encoding};
+use crate::obj;
+use crate::repair;
+use crate::scene::{MeshSource, Scene};
+use crate::simplify;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+const USAGE: &str = "usage: rusty_glad convert <teapot|in.obj|in.mesh|in.gltf|in.glb> \
+    <out.obj|out.mesh|out.gltf|out.glb> [--weld [tolerance]] [--normals] \
+    [--simplify <triangles>] [--scale <factor>] [--recenter] [--flip] [--compress]
+       rusty_glad convert <in.ron|in.json|in.gltf|in.glb> <out.ron|out.json|out.gltf|out.glb>";
+
+/// The window size the viewer opens with, which exported cameras frame for.
+const FRAME_SIZE: (u32, u32) = (720, 480);
+
+/// A change made to the mesh on its way through `convert`, in the order
+/// given on the command line.
+enum Operation {
+    /// Merges vertices that agree to within the tolerance.
+    Weld(f32),
+    /// Replaces the normals with smooth ones averaged across seams.
+    Normals,
+    Simplify(usize),
+    Scale(f32),
+    /// Moves the bounding box center to the origin.
+    Recenter,
+    /// Reverses the winding and the normals.
+    Flip,
+}
+
+/// `rusty_glad convert <in> <out> [operations…]`: loads a mesh, applies the
+/// operations in order and saves it in the format of `out`'s extension.
+/// When either side is a scene file, converts the whole scene instead.
+pub fn run(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let (Some(input), Some(output)) = (args.next(), args.next()) else {
+        return Err(USAGE.to_owned());
+    };
+    let mut args = args.peekable();
+    let mut operations = Vec::new();
+    let mut encoding = Encoding::default();
+    while let Some(arg) = args.next() {
+        operations.push(match arg.as_str() {
+            "--weld" => {
+                let tolerance = match args.peek().and_then(|value| value.parse().ok()) {
+                    Some(tolerance) => {
+                        args.next();
+                        tolerance
+                    }
+                    None => repair::Repair::default().tolerance,
+                };
+                Operation::Weld(tolerance)
+            }
+            "--normals" => Operation::Normals,
+            "--simplify" => Operation::Simplify(number(&mut args, &arg)?),
+            "--scale" => match number(&mut args, &arg)? {
+                factor if factor > 0.0 => Operation::Scale(factor),
+                _ => return Err("--scale needs a positive factor".to_owned()),
+            },
+            "--recenter" => Operation::Recenter,
+            "--flip" => Operation::Flip,
+            "--compress" => {
+                encoding = Encoding {
+                    quantize_positions: true,
+                    octahedral_normals: true,
+                };
+                continue;
+            }
+            _ => return Err(format!("unknown option `{}`\n{}", arg, USAGE)),
+        });
+    }
+
+    let (input_path, output_path) = (Path::new(&input), Path::new(&output));
+    if is_scene(input_path) || is_scene(output_path) {
+        if !operations.is_empty() {
+            return Err("operations only apply to single meshes, not scenes".to_owned());
+        }
+        return convert_scene(input_path, output_path);
+    }
+
+    let mut mesh = load(&input)?;
+    println!(
+        "{}: {} vertices, {} triangles",
+        input,
+        mesh.vertex_count(),
+        mesh.triangle_count()
+    );
+    for operation in &operations {
+        apply(operation, &mut mesh);
+    }
+    save(&mesh, output_path, encoding)?;
+    println!(
+        "{}: {} vertices, {} triangles",
+        output,
+        mesh.vertex_count(),
+        mesh.triangle_count()
+    );
+    Ok(())
+}
+
+/// Parses the argument after option `name` as a number.
+fn number<T: FromStr>(args: &mut impl Iterator<Item = String>, name: &str) -> Result<T, String> {
+    args.next()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("{} needs a number\n{}", name, USAGE))
+}
+
+fn apply(operation: &Operation, mesh: &mut Mesh) {
+    match *operation {
+        Operation::Weld(tolerance) => {
+            let merged = repair::weld(mesh, tolerance);
+            println!("  weld: merged {} vertices", merged);
+        }
+        Operation::Normals => {
+            smooth_normals(mesh);
+            println!("  normals: recomputed");
+        }
+        Operation::Simplify(triangles) => {
+            *mesh = simplify::simplify(mesh, triangles);
+            println!("  simplify: {} triangles", mesh.triangle_count());
+        }
+        Operation::Scale(factor) => {
+            for vertex in &mut mesh.positions {
+                let (x, y, z) = vertex.position;
+                vertex.position = (x * factor, y * factor, z * factor);
+            }
+            println!("  scale: by {}", factor);
+        }
+        Operation::Recenter => {
+            let bounds = mesh.bounds();
+            let center = math::scale(math::add(bounds.min, bounds.max), 0.5);
+            for vertex in &mut mesh.positions {
+                let (x, y, z) = vertex.position;
+                vertex.position = (x - center[0], y - center[1], z - center[2]);
+            }
+            println!(
+                "  recenter: moved by [{:.3}, {:.3}, {:.3}]",
+                -center[0], -center[1], -center[2]
+            );
+        }
+        Operation::Flip => {
+            for triangle in mesh.indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+            for normal in &mut mesh.normals {
+                let (x, y, z) = normal.normal;
+                *normal = Normal {
+                    normal: (-x, -y, -z),
+                };
+            }
+            println!("  flip: reversed the winding");
+        }
+    }
+}
+
+/// Area-weighted vertex normals shared by every vertex at the same position,
+/// so that seams split only for UVs or old normals shade smoothly.
+fn smooth_normals(mesh: &mut Mesh) {
+    let (welded, positions) = mesh.weld();
+    let mut sums = vec![[0.0f32; 3]; positions.len()];
+    for t in mesh.triangles() {
+        let face = mesh.face_normal(t);
+        for i in t {
+            let p = welded[i as usize] as usize;
+            sums[p] = math::add(sums[p], face);
+        }
+    }
+    mesh.normals = welded
+        .iter()
+        .map(|&p| {
+            let [x, y, z] = math::normalize(sums[p as usize]);
+            Normal { normal: (x, y, z) }
+        })
+        .collect();
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|e| e.to_str())
+}
+
+fn is_scene(path: &Path) -> bool {
+    matches!(extension(path), Some("ron" | "json"))
+}
+
+/// Converts between `.ron` and `.json` scenes and glTF files. Scenes read
+/// from glTF load their meshes from the glTF file; mesh files a scene refers
+/// to stay where they are, so their paths are rebased onto the output.
+fn convert_scene(input: &Path, output: &Path) -> Result<(), String> {
+    let (scene, meshes) = match extension(input) {
+        Some("ron" | "json") => {
+            let mut scene = Scene::load(input)?;
+            let input_dir = input.parent().unwrap_or(Path::new(""));
+            // Meshes load from where the input scene says, before their paths
+            // are rebased for a scene file that will sit elsewhere.
+            let meshes = match extension(output) {
+                Some("gltf" | "glb") => scene.load_meshes(input_dir)?.0,
+                _ => Vec::new(),
+            };
+            if is_scene(output) {
+                for desc in &mut scene.meshes {
+                    if let MeshSource::Obj(path)
+                    | MeshSource::Binary(path)
+                    | MeshSource::Gltf { path, .. } = &mut desc.source
+                    {
+                        *path = relative_to(&input_dir.join(&*path), output);
+                    }
+                }
+            }
+            (scene, meshes)
+        }
+        Some("gltf" | "glb") => {
+            let (mut scene, meshes) = gltf::import(input)?;
+            let source = relative_to(input, output);
+            for desc in &mut scene.meshes {
+                if let MeshSource::Gltf { path, .. } = &mut desc.source {
+                    path.clone_from(&source);
+                }
+            }
+            (scene, meshes)
+        }
+        _ => {
+            return Err(format!(
+                "{}: unknown scene format, expected .ron, .json, .gltf or .glb",
+                input.display()
+            ))
+        }
+    };
+    match extension(output) {
+        Some("ron" | "json") => scene.save(output)?,
+        Some("gltf" | "glb") => gltf::save(&scene, &meshes, output)?,
+        _ => {
+            return Err(format!(
+                "{}: unknown scene format, expected .ron, .json, .gltf or .glb",
+                output.display()
+            ))
+        }
+    }
+    println!(
+        "{}: {} meshes, {} nodes, {} lights",
+        output.display(),
+        scene.meshes.len(),
+        scene.nodes.len(),
+        scene.lights.len()
+    );
+    Ok(())
+}
+
+/// `path` as a scene file at `scene` should refer to it: relative to the
+/// scene's directory, climbing out of it with `..` where needed, or absolute
+/// when the two share no directory below the root.
+fn relative_to(path: &Path, scene: &Path) -> PathBuf {
+    let dir = match scene.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let absolute = |path: &Path| {
+        fs::canonicalize(path)
+            .or_else(|_| std::path::absolute(path))
+            .unwrap_or_else(|_| path.to_owned())
+    };
+    let (path, dir) = (absolute(path), absolute(dir));
+    let common = path
+        .components()
+        .zip(dir.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common <= 1 {
+        return path;
+    }
+    let mut relative: PathBuf = dir.components().skip(common).map(|_| "..").collect();
+    relative.extend(path.components().skip(common));
+    relative
+}
+
+/// Loads a mesh by name: `teapot` for the built-in teapot, otherwise a file
+/// in a format known by its extension. Of glTF files, the first mesh.
+pub fn load(target: &str) -> Result<Mesh, String> {
+    if target == "teapot" {
+        return Ok(Mesh::teapot());
+    }
+    let path = Path::new(target);
+    match extension(path) {
+        Some("obj") => obj::load(path),
+        Some("mesh") => meshfile::load(path),
+        Some("gltf" | "glb") => gltf::load_mesh(path, 0),
+        _ => Err(format!(
+            "{}: unknown mesh format, expected teapot, .obj, .mesh, .gltf or .glb",
+            target
+        )),
+    }
+}
+
+/// Saves `mesh` in the format of `path`'s extension; `encoding` only
+/// applies to binary meshes. glTF files get the mesh in the default scene,
+/// with the camera framing it.
+pub fn save(mesh: &Mesh, path: &Path, encoding: Encoding) -> Result<(), String> {
+    match extension(path) {
+        Some("obj") => obj::save(mesh, path),
+        Some("mesh") => meshfile::save(mesh, path, encoding),
+        Some("gltf" | "glb") => {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .unwrap_or("mesh");
+            let mut scene = Scene::default();
+            scene.meshes[0].name = name.to_owned();
+            scene.nodes[0].name = name.to_owned();
+            scene.nodes[0].mesh = name.to_owned();
+            let (width, height) = FRAME_SIZE;
+            scene.camera.frame(&mesh.bounds(), width, height);
+            gltf::save(&scene, std::slice::from_ref(mesh), path)
+        }
+        _ => Err(format!(
+            "{}: unknown mesh format, expected .obj, .mesh, .gltf or .glb",
+            path.display()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own for test `name`.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "rusty_glad_convert_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn scenes_convert_into_other_directories() {
+        let root = temp_dir("scene");
+        fs::create_dir_all(root.join("in/meshes")).unwrap();
+        fs::create_dir_all(root.join("out/nested")).unwrap();
+        obj::save(
+            &crate::primitives::cube(1.0, 1),
+            &root.join("in/meshes/cube.obj"),
+        )
+        .unwrap();
+        let scene = root.join("in/scene.ron");
+        fs::write(
+            &scene,
+            r#"(
+                meshes: [(name: "cube", source: Obj("meshes/cube.obj"), optimize: false)],
+                nodes: [(name: "box", mesh: "cube")],
+            )"#,
+        )
+        .unwrap();
+
+        // Converts `input` to `output` and loads the result as the viewer would.
+        let convert = |input: &Path, output: &Path| -> Result<(Scene, Vec<Mesh>), String> {
+            convert_scene(input, output)?;
+            let converted = match extension(output) {
+                Some("glb") => gltf::import(output)?.0,
+                _ => Scene::load(output)?,
+            };
+            let (meshes, _) = converted.load_meshes(output.parent().unwrap())?;
+            Ok((converted, meshes))
+        };
+        let json = convert(&scene, &root.join("out/nested/scene.json"));
+        let glb = convert(&scene, &root.join("out/scene.glb"));
+        let back = convert(
+            &root.join("out/scene.glb"),
+            &root.join("out/nested/back.ron"),
+        );
+        fs::remove_dir_all(&root).unwrap();
+
+        let (json, glb, back) = (json.unwrap(), glb.unwrap(), back.unwrap());
+        assert!(matches!(
+            &json.0.meshes[0].source,
+            MeshSource::Obj(path) if path == Path::new("../../in/meshes/cube.obj")
+        ));
+        assert!(matches!(
+            &back.0.meshes[0].source,
+            MeshSource::Gltf { path, .. } if path == Path::new("../scene.glb")
+        ));
+        for (scene, meshes) in [json, glb, back] {
+            assert_eq!(scene.nodes.len(), 1);
+            assert_eq!(meshes[0].triangle_count(), 12);
+        }
+    }
+
+    #[test]
+    fn relative_to_climbs_out_of_the_scene_directory() {
+        let root = temp_dir("relative");
+        fs::create_dir_all(root.join("a/meshes")).unwrap();
+        fs::create_dir_all(root.join("b/c")).unwrap();
+        let mesh = root.join("a/meshes/mesh.obj");
+
+        let same = relative_to(&mesh, &root.join("a/meshes/scene.ron"));
+        let below = relative_to(&mesh, &root.join("a/scene.ron"));
+        let beside = relative_to(&mesh, &root.join("b/c/scene.ron"));
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(same, Path::new("mesh.obj"));
+        assert_eq!(below, Path::new("meshes/mesh.obj"));
+        assert_eq!(beside, Path::new("../../a/meshes/mesh.obj"));
+    }
+}
@@ -0,0 +1,427 @@
+//! Immediate-mode debug drawing. Any code running on the main thread can
+//! add shapes during a frame with the free functions here; the viewer draws
+//! them all after the scene, batched into one vertex buffer, and drops each
+//! once its lifetime in frames runs out.
+
+use crate::bounds::{self, Aabb};
+use crate::camera::Camera;
+use crate::math::{self, Mat4, Vec3};
+use crate::overlay::LineVertex;
+use std::cell::RefCell;
+
+/// Line segments per circle of `sphere`.
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Arrow heads are this fraction of the arrow's length, up to `arrow`'s
+/// `head` argument.
+const ARROW_HEAD_FRACTION: f32 = 0.2;
+
+/// Horizontal distance between the starts of consecutive `text3d`
+/// characters, in character heights.
+const CHARACTER_ADVANCE: f32 = 0.75;
+
+thread_local! {
+    static DEBUG_DRAW: RefCell<DebugDraw> = RefCell::new(DebugDraw::default());
+}
+
+/// How a debug shape is drawn, built from `Style::color`:
+/// `Style::color(RED).on_top().frames(60)`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Style {
+    pub color: Vec3,
+    /// Hide the shape behind scene surfaces; off draws it on top of everything.
+    pub depth_test: bool,
+    /// Number of frames to draw the shape for, 1 being the current frame only.
+    pub frames: u32,
+}
+
+impl Style {
+    /// Depth-tested and drawn for the current frame only.
+    pub fn color(color: Vec3) -> Style {
+        Style {
+            color,
+            depth_test: true,
+            frames: 1,
+        }
+    }
+
+    pub fn on_top(self) -> Style {
+        Style {
+            depth_test: false,
+            ..self
+        }
+    }
+
+    pub fn frames(self, frames: u32) -> Style {
+        Style { frames, ..self }
+    }
+}
+
+enum Geometry {
+    /// World-space segments, two points each.
+    Lines(Vec<Vec3>),
+    /// Text facing the camera, which is only known when drawing, so it is
+    /// laid out into segments then.
+    Text {
+        position: Vec3,
+        text: String,
+        height: f32,
+    },
+}
+
+struct Shape {
+    geometry: Geometry,
+    style: Style,
+}
+
+/// The shapes waiting to be drawn. Most code goes through the free functions,
+/// which add to the thread's shared instance.
+#[derive(Default)]
+pub struct DebugDraw {
+    shapes: Vec<Shape>,
+}
+
+impl DebugDraw {
+    pub fn line(&mut self, a: Vec3, b: Vec3, style: Style) {
+        self.lines(vec![a, b], style);
+    }
+
+    /// A line from `from` to `to` with a four-pronged head at `to`.
+    pub fn arrow(&mut self, from: Vec3, to: Vec3, head: f32, style: Style) {
+        let direction = math::sub(to, from);
+        let length = math::length(direction);
+        if length == 0.0 {
+            return;
+        }
+        let head = head.min(ARROW_HEAD_FRACTION * length);
+        let direction = math::scale(direction, 1.0 / length);
+        let (u, v) = perpendiculars(direction);
+        let base = math::sub(to, math::scale(direction, head));
+
+        let mut points = vec![from, to];
+        for side in [u, v, math::scale(u, -1.0), math::scale(v, -1.0)] {
+            points.push(to);
+            points.push(math::add(base, math::scale(side, head / 2.0)));
+        }
+        self.lines(points, style);
+    }
+
+    pub fn aabb(&mut self, aabb: &Aabb, style: Style) {
+        self.corners(&aabb.corners(), style);
+    }
+
+    /// Three great circles around `center`, in the XY, YZ and ZX planes.
+    pub fn sphere(&mut self, center: Vec3, radius: f32, style: Style) {
+        let mut points = Vec::with_capacity(3 * 2 * CIRCLE_SEGMENTS);
+        for axis in 0..3 {
+            let point = |i: usize| {
+                let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+                let mut offset = [0.0; 3];
+                offset[axis] = radius * angle.cos();
+                offset[(axis + 1) % 3] = radius * angle.sin();
+                math::add(center, offset)
+            };
+            for i in 0..CIRCLE_SEGMENTS {
+                points.push(point(i));
+                points.push(point(i + 1));
+            }
+        }
+        self.lines(points, style);
+    }
+
+    /// The view volume of a `perspective * view` matrix, such as one camera's
+    /// seen from another.
+    pub fn frustum(&mut self, view_projection: &Mat4, style: Style) {
+        let Some(inverse) = math::invert(view_projection) else {
+            return;
+        };
+        let ndc = Aabb {
+            min: [-1.0; 3],
+            max: [1.0; 3],
+        };
+        let corners = ndc
+            .corners()
+            .map(|corner| math::project_point(&inverse, corner));
+        self.corners(&corners, style);
+    }
+
+    /// A square grid of `cells × cells` cells, each `spacing` wide, centred
+    /// on `center` in the plane through it parallel to XZ.
+    pub fn grid(&mut self, center: Vec3, cells: u32, spacing: f32, style: Style) {
+        let half = cells as f32 * spacing / 2.0;
+        let mut points = Vec::with_capacity(4 * (cells as usize + 1));
+        for i in 0..=cells {
+            let offset = i as f32 * spacing - half;
+            for (a, b) in [
+                ([offset, -half], [offset, half]),
+                ([-half, offset], [half, offset]),
+            ] {
+                points.push(math::add(center, [a[0], 0.0, a[1]]));
+                points.push(math::add(center, [b[0], 0.0, b[1]]));
+            }
+        }
+        self.lines(points, style);
+    }
+
+    /// `text` centred on `position`, facing the camera, with characters
+    /// `height` tall in world units. Draws upper case letters, digits and
+    /// common punctuation; anything else leaves a gap.
+    pub fn text3d(&mut self, position: Vec3, text: &str, height: f32, style: Style) {
+        self.shapes.push(Shape {
+            geometry: Geometry::Text {
+                position,
+                text: text.to_owned(),
+                height,
+            },
+            style,
+        });
+    }
+
+    fn lines(&mut self, points: Vec<Vec3>, style: Style) {
+        self.shapes.push(Shape {
+            geometry: Geometry::Lines(points),
+            style,
+        });
+    }
+
+    /// The edges of a box through eight corners numbered as by `Aabb::corners`.
+    fn corners(&mut self, corners: &[Vec3; 8], style: Style) {
+        let points = bounds::BOX_EDGES
+            .iter()
+            .flat_map(|&[a, b]| [corners[a], corners[b]])
+            .collect();
+        self.lines(points, style);
+    }
+
+    /// Lays out this frame's shapes as lines seen from `camera`, the
+    /// depth-tested ones first, and counts down their lifetimes. Returns the
+    /// vertices and how many of them are depth-tested.
+    pub fn end_frame(&mut self, camera: &Camera) -> (Vec<LineVertex>, usize) {
+        let view = camera.view_matrix();
+        // The rows of the view matrix are the camera's right and up axes.
+        let right = [view[0][0], view[1][0], view[2][0]];
+        let up = [view[0][1], view[1][1], view[2][1]];
+
+        let mut vertices = Vec::new();
+        let mut depth_tested = 0;
+        for depth_test in [true, false] {
+            for shape in self
+                .shapes
+                .iter()
+                .filter(|s| s.style.depth_test == depth_test)
+            {
+                let [r, g, b] = shape.style.color;
+                let mut push = |[x, y, z]: Vec3| {
+                    vertices.push(LineVertex {
+                        position: (x, y, z),
+                        color: (r, g, b),
+                    })
+                };
+                match &shape.geometry {
+                    Geometry::Lines(points) => points.iter().copied().for_each(&mut push),
+                    Geometry::Text {
+                        position,
+                        text,
+                        height,
+                    } => text_lines(*position, text, *height, right, up, &mut push),
+                }
+            }
+            if depth_test {
+                depth_tested = vertices.len();
+            }
+        }
+
+        self.shapes.retain_mut(|shape| {
+            shape.style.frames = shape.style.frames.saturating_sub(1);
+            shape.style.frames > 0
+        });
+        (vertices, depth_tested)
+    }
+}
+
+/// Runs `f` on the thread's shared `DebugDraw`.
+pub fn with<T>(f: impl FnOnce(&mut DebugDraw) -> T) -> T {
+    DEBUG_DRAW.with(|debug_draw| f(&mut debug_draw.borrow_mut()))
+}
+
+pub fn line(a: Vec3, b: Vec3, style: Style) {
+    with(|d| d.line(a, b, style));
+}
+
+pub fn arrow(from: Vec3, to: Vec3, head: f32, style: Style) {
+    with(|d| d.arrow(from, to, head, style));
+}
+
+pub fn aabb(aabb: &Aabb, style: Style) {
+    with(|d| d.aabb(aabb, style));
+}
+
+pub fn sphere(center: Vec3, radius: f32, style: Style) {
+    with(|d| d.sphere(center, radius, style));
+}
+
+pub fn frustum(view_projection: &Mat4, style: Style) {
+    with(|d| d.frustum(view_projection, style));
+}
+
+pub fn grid(center: Vec3, cells: u32, spacing: f32, style: Style) {
+    with(|d| d.grid(center, cells, spacing, style));
+}
+
+pub fn text3d(position: Vec3, text: &str, height: f32, style: Style) {
+    with(|d| d.text3d(position, text, height, style));
+}
+
+/// Two unit vectors perpendicular to `direction` and to each other.
+fn perpendiculars(direction: Vec3) -> (Vec3, Vec3) {
+    let helper = if direction[1].abs() < 0.9 {
+        [0.0, 1.0, 0.0]
+    } else {
+        [1.0, 0.0, 0.0]
+    };
+    let u = math::normalize(math::cross(direction, helper));
+    (u, math::cross(direction, u))
+}
+
+/// Lays `text` out centred on `position` in the plane spanned by `right` and
+/// `up`, as the segments of a sixteen-segment display, passing both ends of
+/// each segment to `push`.
+pub fn text_lines(
+    position: Vec3,
+    text: &str,
+    height: f32,
+    right: Vec3,
+    up: Vec3,
+    mut push: impl FnMut(Vec3),
+) {
+    // Glyphs are one unit wide and two tall; `scale` turns units into world
+    // distances.
+    let scale = height / 2.0;
+    let width = (text.chars().count() as f32 * CHARACTER_ADVANCE - 0.25) * height;
+    let origin = math::sub(
+        position,
+        math::add(
+            math::scale(right, width / 2.0),
+            math::scale(up, height / 2.0),
+        ),
+    );
+    let point = |x: f32, y: f32| {
+        math::add(
+            origin,
+            math::add(math::scale(right, x * scale), math::scale(up, y * scale)),
+        )
+    };
+
+    for (i, c) in text.chars().enumerate() {
+        let x = i as f32 * CHARACTER_ADVANCE * 2.0;
+        let segments = glyph(c);
+        for (bit, &[(x0, y0), (x1, y1)]) in SEGMENTS.iter().enumerate() {
+            if segments & (1 << bit) != 0 {
+                push(point(x + x0, y0));
+                push(point(x + x1, y1));
+            }
+        }
+    }
+}
+
+/// The ends of each segment in a glyph one unit wide and two tall, indexed
+/// by the bit that turns it on.
+const SEGMENTS: [[(f32, f32); 2]; 16] = [
+    [(0.0, 2.0), (0.5, 2.0)], // A1: top left
+    [(0.5, 2.0), (1.0, 2.0)], // A2: top right
+    [(1.0, 2.0), (1.0, 1.0)], // B: upper right
+    [(1.0, 1.0), (1.0, 0.0)], // C: lower right
+    [(1.0, 0.0), (0.5, 0.0)], // D2: bottom right
+    [(0.5, 0.0), (0.0, 0.0)], // D1: bottom left
+    [(0.0, 0.0), (0.0, 1.0)], // E: lower left
+    [(0.0, 1.0), (0.0, 2.0)], // F: upper left
+    [(0.0, 1.0), (0.5, 1.0)], // G1: middle left
+    [(0.5, 1.0), (1.0, 1.0)], // G2: middle right
+    [(0.0, 2.0), (0.5, 1.0)], // H: upper left diagonal
+    [(0.5, 2.0), (0.5, 1.0)], // I: upper middle
+    [(1.0, 2.0), (0.5, 1.0)], // J: upper right diagonal
+    [(0.5, 1.0), (1.0, 0.0)], // K: lower right diagonal
+    [(0.5, 1.0), (0.5, 0.0)], // L: lower middle
+    [(0.5, 1.0), (0.0, 0.0)], // M: lower left diagonal
+];
+
+const A1: u16 = 1 << 0;
+const A2: u16 = 1 << 1;
+const B: u16 = 1 << 2;
+const C: u16 = 1 << 3;
+const D2: u16 = 1 << 4;
+const D1: u16 = 1 << 5;
+const E: u16 = 1 << 6;
+const F: u16 = 1 << 7;
+const G1: u16 = 1 << 8;
+const G2: u16 = 1 << 9;
+const H: u16 = 1 << 10;
+const I: u16 = 1 << 11;
+const J: u16 = 1 << 12;
+const K: u16 = 1 << 13;
+const L: u16 = 1 << 14;
+const M: u16 = 1 << 15;
+
+const A: u16 = A1 | A2;
+const D: u16 = D1 | D2;
+const G: u16 = G1 | G2;
+const O: u16 = A | B | C | D | E | F;
+
+/// The segments lit for `c`; lower case letters look like upper case ones.
+fn glyph(c: char) -> u16 {
+    match c.to_ascii_uppercase() {
+        '0' => O | J | M,
+        '1' => B | C | J,
+        '2' => A | B | G | E | D,
+        '3' => A | B | C | D | G2,
+        '4' => F | G | B | C,
+        '5' => A | F | G | C | D,
+        '6' => A | F | E | D | C | G,
+        '7' => A | B | C,
+        '8' => O | G,
+        '9' => A | B | C | D | F | G,
+        'A' => A | B | C | E | F | G,
+        'B' => A | B | C | D | I | L | G2,
+        'C' => A | F | E | D,
+        'D' => A | B | C | D | I | L,
+        'E' => A | F | E | D | G1,
+        'F' => A | F | E | G1,
+        'G' => A | F | E | D | C | G2,
+        'H' => F | E | B | C | G,
+        'I' => A | I | L | D,
+        'J' => B | C | D | E,
+        'K' => F | E | G1 | J | K,
+        'L' => F | E | D,
+        'M' => F | E | B | C | H | J,
+        'N' => F | E | B | C | H | K,
+        'O' => O,
+        'P' => A | B | F | E | G,
+        'Q' => O | K,
+        'R' => A | B | F | E | G | K,
+        'S' => A | F | G | C | D,
+        'T' => A | I | L,
+        'U' => F | E | D | C | B,
+        'V' => F | E | M | J,
+        'W' => F | E | B | C | M | K,
+        'X' => H | J | K | M,
+        'Y' => H | J | L,
+        'Z' => A | J | M | D,
+        '-' => G,
+        '+' => G | I | L,
+        '=' => G | D,
+        '_' => D,
+        '.' => D1,
+        ',' => M,
+        '/' => J | M,
+        '\\' => H | K,
+        '|' => I | L,
+        '(' | '<' => J | K,
+        ')' | '>' => H | M,
+        '[' => A2 | I | L | D2,
+        ']' => A1 | I | L | D1,
+        '*' => G | H | I | J | K | L | M,
+        '\'' => I,
+        '"' => I | B,
+        _ => 0,
+    }
+}
@@ -0,0 +1,136 @@
+//! The world axes as the camera sees them, drawn in a corner of the window.
+//! Clicking the end of an axis snaps the camera to look along it.
+
+use crate::camera::Camera;
+use crate::debug_draw;
+use crate::math::{self, Vec3};
+use crate::overlay::LineVertex;
+use glium::Rect;
+
+/// Width and height of the gizmo, in pixels.
+pub const SIZE: u32 = 96;
+/// Gap between the gizmo and the top right corner of the window, in pixels.
+const MARGIN: u32 = 8;
+
+/// Lengths of the positive and negative axes in the gizmo's [-1, 1] square.
+const POSITIVE_LENGTH: f32 = 0.6;
+const NEGATIVE_LENGTH: f32 = 0.4;
+/// Height of the X, Y and Z labels past the ends of the positive axes.
+const LABEL_HEIGHT: f32 = 0.2;
+/// Radius of the rings on the negative axes' ends.
+const RING_RADIUS: f32 = 0.06;
+const RING_SEGMENTS: usize = 12;
+/// How close to an axis end a click has to land to select it.
+const PICK_RADIUS: f32 = 0.2;
+
+const AXIS_COLORS: [Vec3; 3] = [[1.0, 0.2, 0.2], [0.2, 1.0, 0.2], [0.3, 0.5, 1.0]];
+const AXIS_NAMES: [&str; 3] = ["X", "Y", "Z"];
+/// Negative axes are drawn in their axis colour scaled by this.
+const NEGATIVE_DIM: f32 = 0.5;
+
+/// Where the gizmo goes in a window `width` by `height` pixels, as a viewport
+/// measured from the bottom left.
+pub fn viewport((width, height): (u32, u32)) -> Rect {
+    Rect {
+        left: width.saturating_sub(MARGIN + SIZE),
+        bottom: height.saturating_sub(MARGIN + SIZE),
+        width: SIZE,
+        height: SIZE,
+    }
+}
+
+/// The six axis ends, `+X, +Y, +Z, -X, -Y, -Z`, each with the world
+/// direction it stands for and its position in the gizmo's square, plus its
+/// depth as seen from `camera`.
+fn ends(camera: &Camera) -> [(Vec3, [f32; 2], f32); 6] {
+    let view = camera.view_matrix();
+    std::array::from_fn(|i| {
+        let axis = i % 3;
+        let (sign, length) = if i < 3 {
+            (1.0, POSITIVE_LENGTH)
+        } else {
+            (-1.0, NEGATIVE_LENGTH)
+        };
+        let mut direction = [0.0; 3];
+        direction[axis] = sign;
+        // Only the view rotation matters, so the gizmo looks the same from
+        // anywhere.
+        let [x, y, z] = math::transform_vector(&view, direction);
+        (direction, [x * length, y * length], z)
+    })
+}
+
+/// The gizmo's lines in its own [-1, 1] square, the farthest axes first so
+/// the nearer ones cover them.
+pub fn lines(camera: &Camera) -> Vec<LineVertex> {
+    let mut ends: Vec<(usize, [f32; 2], f32)> = ends(camera)
+        .into_iter()
+        .enumerate()
+        .map(|(i, (_, end, depth))| (i, end, depth))
+        .collect();
+    ends.sort_by(|a, b| b.2.total_cmp(&a.2));
+
+    let mut vertices = Vec::new();
+    for (i, [x, y], _) in ends {
+        let axis = i % 3;
+        let positive = i < 3;
+        let [r, g, b] = if positive {
+            AXIS_COLORS[axis]
+        } else {
+            math::scale(AXIS_COLORS[axis], NEGATIVE_DIM)
+        };
+        let mut push = |[x, y, _]: Vec3| {
+            vertices.push(LineVertex {
+                position: (x, y, 0.0),
+                color: (r, g, b),
+            })
+        };
+
+        push([0.0; 3]);
+        push([x, y, 0.0]);
+        if positive {
+            let offset = math::scale(math::normalize([x, y, 0.0]), LABEL_HEIGHT);
+            debug_draw::text_lines(
+                math::add([x, y, 0.0], offset),
+                AXIS_NAMES[axis],
+                LABEL_HEIGHT,
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                push,
+            );
+        } else {
+            let ring = |k: usize| {
+                let angle = k as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                [
+                    x + RING_RADIUS * angle.cos(),
+                    y + RING_RADIUS * angle.sin(),
+                    0.0,
+                ]
+            };
+            for k in 0..RING_SEGMENTS {
+                push(ring(k));
+                push(ring(k + 1));
+            }
+        }
+    }
+    vertices
+}
+
+/// The world direction whose axis end lies under `cursor`, given in window
+/// pixels from the top left, or `None` when the cursor misses them all.
+/// Ends nearer the camera win where they overlap.
+pub fn pick(camera: &Camera, window: (u32, u32), cursor: (f64, f64)) -> Option<Vec3> {
+    let rect = viewport(window);
+    let top = window.1 as f64 - (rect.bottom + rect.height) as f64;
+    let x = ((cursor.0 - rect.left as f64) / rect.width as f64 * 2.0 - 1.0) as f32;
+    let y = (1.0 - (cursor.1 - top) / rect.height as f64 * 2.0) as f32;
+    if x.abs() > 1.0 || y.abs() > 1.0 {
+        return None;
+    }
+
+    ends(camera)
+        .into_iter()
+        .filter(|(_, [ex, ey], _)| (ex - x).hypot(ey - y) <= PICK_RADIUS)
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+        .map(|(direction, _, _)| direction)
+}
@@ -0,0 +1,1164 @@
+//! glTF 2.0 export and import of scenes, as `.gltf` with a `.bin` beside it
+//! or as a single `.glb`.
+//!
+//! glTF is right-handed with counter-clockwise front faces, while the viewer's
+//! world is left-handed with clockwise ones (see `Mesh`). Mirroring Z converts
+//! between the two without changing how anything looks, and turns the winding
+//! around on its own, so indices are written as they are.
+//!
+//! Scenes have no textures, so materials carry colour factors only: textures
+//! are neither embedded on export nor read on import. What glTF has no place
+//! for, like the dark material colour or instance colours, goes into `extras`.
+
+use crate::camera::Camera;
+use crate::math::{self, Mat4, Vec3};
+use crate::mesh::{Mesh, Normal, TexCoord, Vertex};
+use crate::scene::{
+    Instance, Instances, Light, LightKind, Material, MeshDesc, MeshSource, Node, Scene, Transform,
+};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_JSON: u32 = 0x4E4F534A;
+const GLB_BIN: u32 = 0x004E4942;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const UNSIGNED_BYTE: u64 = 5121;
+const UNSIGNED_SHORT: u64 = 5123;
+const UNSIGNED_INT: u64 = 5125;
+const FLOAT: u64 = 5126;
+const TRIANGLES: u64 = 4;
+
+const LIGHTS_EXTENSION: &str = "KHR_lights_punctual";
+
+/// A rotation as rows.
+type Mat3 = [[f32; 3]; 3];
+
+/// Writes `scene` with its loaded `meshes` to `path`, as GLB when it ends in
+/// `.glb` and otherwise as glTF with the binary data in a `.bin` beside it.
+pub fn save(scene: &Scene, meshes: &[Mesh], path: &Path) -> Result<(), String> {
+    let (mut document, buffer) = export(scene, meshes);
+    let write_error = |path: &Path, err| format!("failed to write {}: {}", path.display(), err);
+    if path.extension().and_then(|e| e.to_str()) == Some("glb") {
+        return fs::write(path, glb(&document, &buffer)).map_err(|err| write_error(path, err));
+    }
+    let bin = path.with_extension("bin");
+    let uri = bin
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("{}: not a file name", path.display()))?;
+    document["buffers"][0]["uri"] = json!(uri);
+    fs::write(&bin, &buffer).map_err(|err| write_error(&bin, err))?;
+    let text = serde_json::to_string_pretty(&document).map_err(|err| err.to_string())?;
+    fs::write(path, text).map_err(|err| write_error(path, err))
+}
+
+/// Builds the glTF document for `scene` and the binary buffer it refers to.
+pub fn export(scene: &Scene, meshes: &[Mesh]) -> (Value, Vec<u8>) {
+    let mut writer = BufferWriter::default();
+    // Each scene mesh's accessors, shared by every glTF mesh made from it.
+    let primitives: Vec<Option<Value>> = meshes.iter().map(|mesh| writer.mesh(mesh)).collect();
+
+    let materials: Vec<Value> = scene
+        .materials
+        .iter()
+        .map(|material| {
+            let [r, g, b] = material.regular_color;
+            json!({
+                "name": material.name,
+                "pbrMetallicRoughness": {
+                    "baseColorFactor": [r, g, b, 1.0],
+                    "metallicFactor": 0.0,
+                    "roughnessFactor": 1.0,
+                },
+                "extras": { "dark_color": material.dark_color },
+            })
+        })
+        .collect();
+
+    // glTF binds materials to meshes rather than nodes, so each pairing of a
+    // mesh and a material the nodes use becomes its own glTF mesh.
+    let mut gltf_meshes = Vec::new();
+    let mut mesh_lookup = HashMap::new();
+    let mut mesh_for = |mesh: usize, material: Option<usize>| -> Option<usize> {
+        let primitive = primitives.get(mesh)?.as_ref()?;
+        Some(*mesh_lookup.entry((mesh, material)).or_insert_with(|| {
+            let mut primitive = primitive.clone();
+            if let Some(material) = material {
+                primitive["material"] = json!(material);
+            }
+            gltf_meshes.push(json!({
+                "name": scene.meshes[mesh].name,
+                "primitives": [primitive],
+            }));
+            gltf_meshes.len() - 1
+        }))
+    };
+
+    let mut nodes = Vec::new();
+    let mut roots = Vec::new();
+    for node in &scene.nodes {
+        let mesh = scene.meshes.iter().position(|m| m.name == node.mesh);
+        let material = match &node.material {
+            Some(name) => scene.materials.iter().position(|m| &m.name == name),
+            None => (!scene.materials.is_empty()).then_some(0),
+        };
+        let gltf_mesh = mesh.and_then(|mesh| mesh_for(mesh, material));
+
+        let mut value = trs(&node.transform.matrix());
+        value["name"] = json!(node.name);
+        match &node.instances {
+            None => {
+                if let Some(gltf_mesh) = gltf_mesh {
+                    value["mesh"] = json!(gltf_mesh);
+                }
+            }
+            Some(instances) => {
+                let mut children = Vec::new();
+                for (i, instance) in instances.expand().iter().enumerate() {
+                    let mut child = trs(&instance.transform.matrix());
+                    child["name"] = json!(format!("{} {}", node.name, i));
+                    child["extras"] = json!({ "color": instance.color });
+                    if let Some(gltf_mesh) = gltf_mesh {
+                        child["mesh"] = json!(gltf_mesh);
+                    }
+                    nodes.push(child);
+                    children.push(nodes.len() - 1);
+                }
+                value["children"] = json!(children);
+                value["extras"] = json!({ "instances": true });
+            }
+        }
+        nodes.push(value);
+        roots.push(nodes.len() - 1);
+    }
+
+    let camera = &scene.camera;
+    let camera_node = json!({
+        "name": "camera",
+        "camera": 0,
+        "translation": mirror(camera.position),
+        "rotation": quaternion(camera_basis(camera)),
+        "extras": { "target": camera.target, "up": camera.up },
+    });
+    nodes.push(camera_node);
+    roots.push(nodes.len() - 1);
+
+    let mut lights = Vec::new();
+    for (i, light) in scene.lights.iter().enumerate() {
+        let mut node = json!({
+            "name": format!("light {}", i),
+            "extensions": { LIGHTS_EXTENSION: { "light": i } },
+        });
+        let kind = match light.kind {
+            LightKind::Directional { direction } => {
+                // Directional lights shine down their node's -Z.
+                node["rotation"] = json!(quaternion(basis_towards(mirror(direction))));
+                "directional"
+            }
+            LightKind::Point { position } => {
+                node["translation"] = json!(mirror(position));
+                "point"
+            }
+        };
+        lights.push(json!({
+            "type": kind,
+            "color": light.color,
+            "intensity": light.intensity,
+        }));
+        nodes.push(node);
+        roots.push(nodes.len() - 1);
+    }
+
+    let mut document = json!({
+        "asset": { "version": "2.0", "generator": "rusty_glad" },
+        "scene": 0,
+        "scenes": [{
+            "nodes": roots,
+            "extras": { "clear_color": scene.clear_color },
+        }],
+        "nodes": nodes,
+        "cameras": [{
+            "type": "perspective",
+            "perspective": {
+                "yfov": camera.fov.to_radians(),
+                "znear": camera.znear,
+                "zfar": camera.zfar,
+            },
+        }],
+        "buffers": [{ "byteLength": writer.data.len() }],
+        "bufferViews": writer.views,
+        "accessors": writer.accessors,
+    });
+    if !materials.is_empty() {
+        document["materials"] = json!(materials);
+    }
+    if !gltf_meshes.is_empty() {
+        document["meshes"] = json!(gltf_meshes);
+    }
+    if !lights.is_empty() {
+        document["extensionsUsed"] = json!([LIGHTS_EXTENSION]);
+        document["extensions"] = json!({ LIGHTS_EXTENSION: { "lights": lights } });
+    }
+    (document, writer.data)
+}
+
+/// Packs the document and buffer into a GLB container.
+fn glb(document: &Value, buffer: &[u8]) -> Vec<u8> {
+    let mut json = document.to_string().into_bytes();
+    json.resize(json.len().next_multiple_of(4), b' ');
+    let mut bin = buffer.to_vec();
+    bin.resize(bin.len().next_multiple_of(4), 0);
+
+    let length = 12 + 8 + json.len() + 8 + bin.len();
+    let mut out = Vec::with_capacity(length);
+    out.extend_from_slice(GLB_MAGIC);
+    out.extend_from_slice(&2u32.to_le_bytes());
+    out.extend_from_slice(&(length as u32).to_le_bytes());
+    for (kind, chunk) in [(GLB_JSON, &json), (GLB_BIN, &bin)] {
+        out.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        out.extend_from_slice(&kind.to_le_bytes());
+        out.extend_from_slice(chunk);
+    }
+    out
+}
+
+/// Collects the vertex streams of every mesh into one buffer.
+#[derive(Default)]
+struct BufferWriter {
+    data: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl BufferWriter {
+    /// Writes the streams of `mesh` and returns the primitive drawing them,
+    /// or `None` for a mesh without triangles, which glTF cannot hold.
+    fn mesh(&mut self, mesh: &Mesh) -> Option<Value> {
+        if mesh.indices.is_empty() {
+            return None;
+        }
+        let count = mesh.vertex_count();
+        let positions: Vec<Vec3> = (0..count as u32)
+            .map(|i| mirror(mesh.position(i)))
+            .collect();
+        let bounds = crate::bounds::Aabb::from_points(positions.iter().copied());
+        let mut attributes = json!({
+            "POSITION": self.accessor(
+                bytes(positions.iter().flatten()),
+                ARRAY_BUFFER,
+                json!({ "componentType": FLOAT, "count": count, "type": "VEC3",
+                        "min": bounds.min, "max": bounds.max }),
+            ),
+        });
+        if mesh.normals.len() == count {
+            let normals = (0..count as u32).flat_map(|i| mirror(mesh.normal(i)));
+            attributes["NORMAL"] = json!(self.accessor(
+                bytes(normals.collect::<Vec<_>>().iter()),
+                ARRAY_BUFFER,
+                json!({ "componentType": FLOAT, "count": count, "type": "VEC3" }),
+            ));
+        }
+        if mesh.uvs.len() == count {
+            // glTF puts the UV origin at the top left.
+            let uvs: Vec<f32> = mesh
+                .uvs
+                .iter()
+                .flat_map(|uv| [uv.tex_coords.0, 1.0 - uv.tex_coords.1])
+                .collect();
+            attributes["TEXCOORD_0"] = json!(self.accessor(
+                bytes(uvs.iter()),
+                ARRAY_BUFFER,
+                json!({ "componentType": FLOAT, "count": count, "type": "VEC2" }),
+            ));
+        }
+        let indices = mesh.indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+        let indices = self.accessor(
+            indices,
+            ELEMENT_ARRAY_BUFFER,
+            json!({ "componentType": UNSIGNED_INT, "count": mesh.indices.len(), "type": "SCALAR" }),
+        );
+        Some(json!({ "attributes": attributes, "indices": indices, "mode": TRIANGLES }))
+    }
+
+    /// Appends `data` as a buffer view and returns the index of an accessor
+    /// over it, described by `accessor`.
+    fn accessor(&mut self, data: Vec<u8>, target: u32, mut accessor: Value) -> usize {
+        self.views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.data.len(),
+            "byteLength": data.len(),
+            "target": target,
+        }));
+        self.data.extend_from_slice(&data);
+        self.data.resize(self.data.len().next_multiple_of(4), 0);
+        accessor["bufferView"] = json!(self.views.len() - 1);
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+}
+
+fn bytes<'a>(values: impl Iterator<Item = &'a f32>) -> Vec<u8> {
+    values.flat_map(|v| v.to_le_bytes()).collect()
+}
+
+/// Reads the scene in a `.gltf` or `.glb` file along with its meshes. Each
+/// distinct glTF mesh becomes a scene mesh loading from `path` itself, and
+/// each node a scene node; nodes the exporter wrote for instances turn back
+/// into instances.
+pub fn import(path: &Path) -> Result<(Scene, Vec<Mesh>), String> {
+    let file = File::read(path)?;
+    let doc = &file.document;
+    let source_path: PathBuf = path.file_name().map(Into::into).unwrap_or_default();
+
+    // glTF meshes drawing the same accessors, as the exporter writes one per
+    // material, are one scene mesh.
+    let gltf_meshes = array(doc, "meshes");
+    let mut meshes = Vec::new();
+    let mut descs: Vec<MeshDesc> = Vec::new();
+    let mut mesh_of = Vec::new();
+    let mut by_data = HashMap::new();
+    for (index, gltf_mesh) in gltf_meshes.iter().enumerate() {
+        let mut key = gltf_mesh["primitives"].clone();
+        for primitive in key.as_array_mut().into_iter().flatten() {
+            primitive["material"] = Value::Null;
+        }
+        let mesh = *by_data.entry(key.to_string()).or_insert_with(|| {
+            descs.push(MeshDesc {
+                name: gltf_mesh["name"]
+                    .as_str()
+                    .map_or_else(|| format!("mesh {}", index), str::to_owned),
+                source: MeshSource::Gltf {
+                    path: source_path.clone(),
+                    mesh: index,
+                },
+                repair: None,
+                winding: Default::default(),
+                subdivision: None,
+                optimize: true,
+                lod: None,
+            });
+            descs.len() - 1
+        });
+        if mesh == meshes.len() {
+            meshes.push(file.mesh(index)?);
+        }
+        mesh_of.push(mesh);
+    }
+    // Two glTF meshes can share a name; scene meshes are looked up by it.
+    let mut seen = HashMap::new();
+    for desc in &mut descs {
+        let count = seen.entry(desc.name.clone()).or_insert(0);
+        *count += 1;
+        if *count > 1 {
+            desc.name = format!("{} {}", desc.name, count);
+        }
+    }
+
+    let materials: Vec<Material> = array(doc, "materials")
+        .iter()
+        .enumerate()
+        .map(|(i, material)| {
+            let base = &material["pbrMetallicRoughness"]["baseColorFactor"];
+            let regular_color = [0, 1, 2].map(|k| base[k].as_f64().unwrap_or(1.0) as f32);
+            Material {
+                name: material["name"]
+                    .as_str()
+                    .map_or_else(|| format!("material {}", i), str::to_owned),
+                dark_color: vec3(&material["extras"]["dark_color"])
+                    .unwrap_or(math::scale(regular_color, 0.5)),
+                regular_color,
+            }
+        })
+        .collect();
+
+    let mut scene = Scene {
+        meshes: Vec::new(),
+        materials,
+        lights: Vec::new(),
+        nodes: Vec::new(),
+        ..Scene::default()
+    };
+    let gltf_scene = &doc["scenes"][doc["scene"].as_u64().unwrap_or(0) as usize];
+    if let Some(color) = gltf_scene["extras"]["clear_color"].as_array() {
+        if let [r, g, b, a] = &color[..] {
+            scene.clear_color = [r, g, b, a].map(|c| c.as_f64().unwrap_or(0.0) as f32);
+        }
+    }
+
+    let gltf_lights = &doc["extensions"][LIGHTS_EXTENSION]["lights"];
+    let mut stack: Vec<(usize, Mat4)> = array(gltf_scene, "nodes")
+        .iter()
+        .filter_map(Value::as_u64)
+        .map(|node| (node as usize, math::identity()))
+        .rev()
+        .collect();
+    // A node reached twice is in a cycle, or the child of two parents, and
+    // the file is not a tree as glTF requires.
+    let mut visited = HashSet::new();
+    while let Some((index, parent)) = stack.pop() {
+        let node = &doc["nodes"][index];
+        if node.is_null() {
+            return Err(format!("{}: node {} does not exist", path.display(), index));
+        }
+        let mut visit = |index: usize| {
+            if visited.insert(index) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "{}: node {} is reached more than once; the node hierarchy is not a tree",
+                    path.display(),
+                    index
+                ))
+            }
+        };
+        visit(index)?;
+        let world = math::mul(&parent, &local_matrix(node));
+        let name = node["name"]
+            .as_str()
+            .map_or_else(|| format!("node {}", index), str::to_owned);
+        let children: Vec<usize> = array(node, "children")
+            .iter()
+            .filter_map(|c| c.as_u64().map(|c| c as usize))
+            .collect();
+
+        // Where the mesh and material of `node` go in the scene.
+        let mesh_and_material = |node: &Value| {
+            let gltf_mesh = node["mesh"].as_u64()? as usize;
+            let mesh = *mesh_of.get(gltf_mesh)?;
+            let material = doc["meshes"][gltf_mesh]["primitives"][0]["material"]
+                .as_u64()
+                .and_then(|m| scene.materials.get(m as usize))
+                .map(|m| m.name.clone());
+            Some((descs[mesh].name.clone(), material))
+        };
+
+        if node["extras"]["instances"].as_bool() == Some(true) {
+            let mut instances = Vec::new();
+            let mut target = None;
+            for &child in &children {
+                visit(child)?;
+                let child = &doc["nodes"][child];
+                target = target.or_else(|| mesh_and_material(child));
+                instances.push(Instance {
+                    transform: transform(&local_matrix(child)),
+                    color: vec3(&child["extras"]["color"]).unwrap_or([1.0; 3]),
+                });
+            }
+            if let Some((mesh, material)) = target {
+                scene.nodes.push(Node {
+                    name,
+                    mesh,
+                    material,
+                    transform: transform(&world),
+                    instances: Some(Instances::List(instances)),
+                });
+            }
+            continue;
+        }
+
+        if let Some((mesh, material)) = mesh_and_material(node) {
+            scene.nodes.push(Node {
+                name,
+                mesh,
+                material,
+                transform: transform(&world),
+                instances: None,
+            });
+        }
+        if node["camera"].is_u64() {
+            scene.camera = import_camera(doc, node, &world);
+        }
+        if let Some(light) = node["extensions"][LIGHTS_EXTENSION]["light"].as_u64() {
+            let light = &gltf_lights[light as usize];
+            let kind = match light["type"].as_str() {
+                Some("directional") => LightKind::Directional {
+                    direction: math::normalize(mirror(math::transform_vector(
+                        &world,
+                        [0.0, 0.0, 1.0],
+                    ))),
+                },
+                _ => LightKind::Point {
+                    position: mirror(math::transform_point(&world, [0.0; 3])),
+                },
+            };
+            scene.lights.push(Light {
+                kind,
+                color: vec3(&light["color"]).unwrap_or([1.0; 3]),
+                intensity: light["intensity"].as_f64().unwrap_or(1.0) as f32,
+            });
+        }
+        stack.extend(children.iter().rev().map(|&child| (child, world)));
+    }
+    scene.meshes = descs;
+    Ok((scene, meshes))
+}
+
+/// Loads glTF mesh `index` of the file at `path`.
+pub fn load_mesh(path: &Path, index: usize) -> Result<Mesh, String> {
+    File::read(path)?.mesh(index)
+}
+
+/// A parsed `.gltf` or `.glb` file with its buffers.
+struct File {
+    document: Value,
+    buffers: Vec<Vec<u8>>,
+}
+
+impl File {
+    fn read(path: &Path) -> Result<File, String> {
+        let data =
+            fs::read(path).map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        File::parse(&data, path.parent().unwrap_or(Path::new("")))
+            .map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// Parses GLB or glTF JSON, loading external buffers relative to `base_dir`.
+    fn parse(data: &[u8], base_dir: &Path) -> Result<File, String> {
+        let (json, mut bin) = if data.starts_with(GLB_MAGIC) {
+            let (json, bin) = glb_chunks(data)?;
+            (json, bin.map(<[u8]>::to_vec))
+        } else {
+            (data, None)
+        };
+        let document: Value = serde_json::from_slice(json).map_err(|err| err.to_string())?;
+        if !document["asset"]["version"]
+            .as_str()
+            .is_some_and(|v| v.starts_with("2."))
+        {
+            return Err("not a glTF 2.0 file".to_owned());
+        }
+        let buffers = array(&document, "buffers")
+            .iter()
+            .map(|buffer| match buffer["uri"].as_str() {
+                None => bin.take().ok_or_else(|| "buffer without data".to_owned()),
+                Some(uri) if uri.starts_with("data:") => {
+                    Err("embedded data URIs are not supported".to_owned())
+                }
+                Some(uri) => fs::read(base_dir.join(uri))
+                    .map_err(|err| format!("failed to read {}: {}", uri, err)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(File { document, buffers })
+    }
+
+    /// Merges the triangle primitives of glTF mesh `index` into one mesh.
+    fn mesh(&self, index: usize) -> Result<Mesh, String> {
+        let gltf_mesh = &self.document["meshes"][index];
+        if gltf_mesh.is_null() {
+            return Err(format!("mesh {} does not exist", index));
+        }
+        let mut mesh = Mesh::default();
+        let mut has_normals = true;
+        let mut has_uvs = true;
+        for primitive in array(gltf_mesh, "primitives") {
+            if primitive["mode"].as_u64().unwrap_or(TRIANGLES) != TRIANGLES {
+                continue;
+            }
+            let attributes = &primitive["attributes"];
+            let positions = self.floats(&attributes["POSITION"], 3)?;
+            let start = mesh.positions.len() as u32;
+            let count = positions.len() / 3;
+            mesh.positions.extend(positions.chunks_exact(3).map(|p| {
+                let [x, y, z] = mirror([p[0], p[1], p[2]]);
+                Vertex {
+                    position: (x, y, z),
+                }
+            }));
+            if attributes["NORMAL"].is_null() {
+                has_normals = false;
+            } else {
+                let normals = self.floats(&attributes["NORMAL"], 3)?;
+                mesh.normals.extend(normals.chunks_exact(3).map(|n| {
+                    let [x, y, z] = mirror([n[0], n[1], n[2]]);
+                    Normal { normal: (x, y, z) }
+                }));
+            }
+            if attributes["TEXCOORD_0"].is_null() {
+                has_uvs = false;
+            } else {
+                let uvs = self.floats(&attributes["TEXCOORD_0"], 2)?;
+                mesh.uvs.extend(uvs.chunks_exact(2).map(|uv| TexCoord {
+                    tex_coords: (uv[0], 1.0 - uv[1]),
+                }));
+            }
+            let indices = if primitive["indices"].is_null() {
+                (0..count as u32).collect()
+            } else {
+                self.indices(&primitive["indices"])?
+            };
+            if let Some(&bad) = indices.iter().find(|&&i| i as usize >= count) {
+                return Err(format!("mesh {}: index {} out of range", index, bad));
+            }
+            mesh.indices.extend(
+                indices
+                    .iter()
+                    .take(indices.len() / 3 * 3)
+                    .map(|i| i + start),
+            );
+        }
+        if !has_uvs || mesh.uvs.len() != mesh.positions.len() {
+            mesh.uvs.clear();
+        }
+        if !has_normals || mesh.normals.len() != mesh.positions.len() {
+            mesh.compute_normals();
+        }
+        Ok(mesh)
+    }
+
+    /// The bytes of each element of accessor `accessor`, `size` bytes long.
+    fn elements(&self, accessor: &Value, size: usize) -> Result<Vec<&[u8]>, String> {
+        let accessor = &self.document["accessors"][accessor.as_u64().unwrap_or(u64::MAX) as usize];
+        let view = &self.document["bufferViews"][accessor["bufferView"]
+            .as_u64()
+            .ok_or("accessor without a buffer view")?
+            as usize];
+        let buffer = self
+            .buffers
+            .get(view["buffer"].as_u64().unwrap_or(0) as usize)
+            .ok_or("buffer view without a buffer")?;
+        let start = view["byteOffset"].as_u64().unwrap_or(0) as usize
+            + accessor["byteOffset"].as_u64().unwrap_or(0) as usize;
+        let stride = view["byteStride"].as_u64().map_or(size, |s| s as usize);
+        let count = accessor["count"].as_u64().unwrap_or(0) as usize;
+        (0..count)
+            .map(|i| {
+                let offset = start + i * stride;
+                buffer
+                    .get(offset..offset + size)
+                    .ok_or_else(|| "accessor runs past its buffer".to_owned())
+            })
+            .collect()
+    }
+
+    /// Reads a float accessor of `components` per element.
+    fn floats(&self, accessor: &Value, components: usize) -> Result<Vec<f32>, String> {
+        let description =
+            &self.document["accessors"][accessor.as_u64().unwrap_or(u64::MAX) as usize];
+        if description["componentType"].as_u64() != Some(FLOAT) {
+            return Err("only float vertex attributes are supported".to_owned());
+        }
+        Ok(self
+            .elements(accessor, 4 * components)?
+            .into_iter()
+            .flat_map(|element| {
+                element
+                    .chunks_exact(4)
+                    .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            })
+            .collect())
+    }
+
+    fn indices(&self, accessor: &Value) -> Result<Vec<u32>, String> {
+        let description =
+            &self.document["accessors"][accessor.as_u64().unwrap_or(u64::MAX) as usize];
+        let (size, read): (usize, fn(&[u8]) -> u32) = match description["componentType"].as_u64() {
+            Some(UNSIGNED_BYTE) => (1, |b| b[0] as u32),
+            Some(UNSIGNED_SHORT) => (2, |b| u16::from_le_bytes([b[0], b[1]]) as u32),
+            Some(UNSIGNED_INT) => (4, |b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+            _ => return Err("indices must be unsigned integers".to_owned()),
+        };
+        Ok(self
+            .elements(accessor, size)?
+            .into_iter()
+            .map(read)
+            .collect())
+    }
+}
+
+/// Splits a GLB file into its JSON chunk and optional binary chunk.
+fn glb_chunks(data: &[u8]) -> Result<(&[u8], Option<&[u8]>), String> {
+    let u32_at = |offset: usize| {
+        data.get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+            .ok_or_else(|| "truncated GLB file".to_owned())
+    };
+    if u32_at(4)? != 2 {
+        return Err("only GLB version 2 is supported".to_owned());
+    }
+    let length = u32_at(8)?.min(data.len());
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+    while offset + 8 <= length {
+        let (size, kind) = (u32_at(offset)?, u32_at(offset + 4)?);
+        let chunk = data
+            .get(offset + 8..offset + 8 + size)
+            .ok_or_else(|| "truncated GLB chunk".to_owned())?;
+        chunks.push((kind as u32, chunk));
+        offset += 8 + size;
+    }
+    let find = |kind| chunks.iter().find(|(k, _)| *k == kind).map(|(_, c)| *c);
+    let json = find(GLB_JSON).ok_or_else(|| "GLB file without JSON".to_owned())?;
+    Ok((json, find(GLB_BIN)))
+}
+
+fn array<'a>(value: &'a Value, key: &str) -> &'a [Value] {
+    value[key].as_array().map_or(&[], Vec::as_slice)
+}
+
+fn vec3(value: &Value) -> Option<Vec3> {
+    match value.as_array()?.as_slice() {
+        [x, y, z] => Some([x.as_f64()? as f32, y.as_f64()? as f32, z.as_f64()? as f32]),
+        _ => None,
+    }
+}
+
+/// Mirrors a point or direction between the viewer's world and glTF's.
+fn mirror([x, y, z]: Vec3) -> Vec3 {
+    [x, y, -z]
+}
+
+/// Mirrors a transform between the viewer's world and glTF's: the same
+/// transform seen through `mirror` on both sides.
+fn mirror_matrix(m: &Mat4) -> Mat4 {
+    let sign = [1.0, 1.0, -1.0, 1.0];
+    let mut out = *m;
+    for (column, values) in out.iter_mut().enumerate() {
+        for (row, value) in values.iter_mut().enumerate() {
+            *value *= sign[column] * sign[row];
+        }
+    }
+    out
+}
+
+/// The translation, rotation and scale properties of a glTF node placed by
+/// the viewer's transform `m`.
+fn trs(m: &Mat4) -> Value {
+    let (translation, rotation, scale) = decompose(&mirror_matrix(m));
+    json!({
+        "translation": translation,
+        "rotation": quaternion(rotation),
+        "scale": scale,
+    })
+}
+
+/// The viewer's transform for a glTF node's matrix.
+fn transform(m: &Mat4) -> Transform {
+    let (translation, rotation, scale) = decompose(&mirror_matrix(m));
+    Transform {
+        translation,
+        rotation: euler(rotation),
+        scale,
+    }
+}
+
+/// The matrix of a glTF node relative to its parent, from `matrix` or its
+/// translation, rotation and scale.
+fn local_matrix(node: &Value) -> Mat4 {
+    if let Some(values) = node["matrix"].as_array() {
+        if values.len() == 16 {
+            let mut m = math::identity();
+            for (i, value) in values.iter().enumerate() {
+                m[i / 4][i % 4] = value.as_f64().unwrap_or(0.0) as f32;
+            }
+            return m;
+        }
+    }
+    let translation = vec3(&node["translation"]).unwrap_or([0.0; 3]);
+    let scale = vec3(&node["scale"]).unwrap_or([1.0; 3]);
+    let rotation = match node["rotation"].as_array().map(Vec::as_slice) {
+        Some([x, y, z, w]) => {
+            rotation_matrix([x, y, z, w].map(|c| c.as_f64().unwrap_or(0.0) as f32))
+        }
+        _ => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    };
+    let mut m = math::mul(&math::translation(translation), &math::scaling(scale));
+    for (column, values) in m.iter_mut().take(3).enumerate() {
+        for (row, value) in values.iter_mut().take(3).enumerate() {
+            *value = rotation[row][column] * scale[column];
+        }
+    }
+    m
+}
+
+/// Splits `m` into translation, rotation rows and scale, assuming it holds
+/// no shear. A mirroring `m` gets a negative X scale.
+fn decompose(m: &Mat4) -> (Vec3, Mat3, Vec3) {
+    let columns = [0, 1, 2].map(|c| [m[c][0], m[c][1], m[c][2]]);
+    let mut scale = columns.map(math::length);
+    if math::dot(math::cross(columns[0], columns[1]), columns[2]) < 0.0 {
+        scale[0] = -scale[0];
+    }
+    let rotation = [0, 1, 2].map(|row| {
+        [0, 1, 2].map(|column| {
+            if scale[column] != 0.0 {
+                columns[column][row] / scale[column]
+            } else {
+                (row == column) as u8 as f32
+            }
+        })
+    });
+    ([m[3][0], m[3][1], m[3][2]], rotation, scale)
+}
+
+/// Euler angles in degrees, as `Transform` and `math::rotation` take them,
+/// for rotation rows `r` = Rz·Ry·Rx.
+fn euler(r: Mat3) -> Vec3 {
+    let y = (-r[2][0]).clamp(-1.0, 1.0).asin();
+    let (x, z) = if y.cos().abs() > 1e-6 {
+        (r[2][1].atan2(r[2][2]), r[1][0].atan2(r[0][0]))
+    } else {
+        // Gimbal lock: only x ± z is known, so all of it goes to x.
+        ((-r[1][2]).atan2(r[1][1]), 0.0)
+    };
+    [x, y, z].map(f32::to_degrees)
+}
+
+/// The unit quaternion `[x, y, z, w]` of rotation rows `r`.
+fn quaternion(r: Mat3) -> [f32; 4] {
+    let trace = r[0][0] + r[1][1] + r[2][2];
+    if trace > 0.0 {
+        let s = (trace + 1.0).sqrt() * 2.0;
+        [
+            (r[2][1] - r[1][2]) / s,
+            (r[0][2] - r[2][0]) / s,
+            (r[1][0] - r[0][1]) / s,
+            s / 4.0,
+        ]
+    } else if r[0][0] > r[1][1] && r[0][0] > r[2][2] {
+        let s = (1.0 + r[0][0] - r[1][1] - r[2][2]).sqrt() * 2.0;
+        [
+            s / 4.0,
+            (r[0][1] + r[1][0]) / s,
+            (r[0][2] + r[2][0]) / s,
+            (r[2][1] - r[1][2]) / s,
+        ]
+    } else if r[1][1] > r[2][2] {
+        let s = (1.0 + r[1][1] - r[0][0] - r[2][2]).sqrt() * 2.0;
+        [
+            (r[0][1] + r[1][0]) / s,
+            s / 4.0,
+            (r[1][2] + r[2][1]) / s,
+            (r[0][2] - r[2][0]) / s,
+        ]
+    } else {
+        let s = (1.0 + r[2][2] - r[0][0] - r[1][1]).sqrt() * 2.0;
+        [
+            (r[0][2] + r[2][0]) / s,
+            (r[1][2] + r[2][1]) / s,
+            s / 4.0,
+            (r[1][0] - r[0][1]) / s,
+        ]
+    }
+}
+
+/// Rotation rows of the quaternion `[x, y, z, w]`, normalized first.
+fn rotation_matrix(q: [f32; 4]) -> Mat3 {
+    let length = q
+        .iter()
+        .map(|c| c * c)
+        .sum::<f32>()
+        .sqrt()
+        .max(f32::EPSILON);
+    let [x, y, z, w] = q.map(|c| c / length);
+    [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - z * w),
+            2.0 * (x * z + y * w),
+        ],
+        [
+            2.0 * (x * y + z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - x * w),
+        ],
+        [
+            2.0 * (x * z - y * w),
+            2.0 * (y * z + x * w),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ]
+}
+
+/// Rotation rows with the given axes as columns.
+fn from_axes(x: Vec3, y: Vec3, z: Vec3) -> Mat3 {
+    [0, 1, 2].map(|row| [x[row], y[row], z[row]])
+}
+
+/// The orientation of a glTF camera node for `camera`: glTF cameras look
+/// down their -Z with +Y up.
+fn camera_basis(camera: &Camera) -> Mat3 {
+    let forward = math::normalize(mirror(camera.direction()));
+    let right = math::normalize(math::cross(forward, mirror(camera.up)));
+    let up = math::cross(right, forward);
+    from_axes(right, up, math::scale(forward, -1.0))
+}
+
+/// An orientation whose +Z points along `z`.
+fn basis_towards(z: Vec3) -> Mat3 {
+    let z = math::normalize(z);
+    let helper = if z[1].abs() < 0.9 {
+        [0.0, 1.0, 0.0]
+    } else {
+        [1.0, 0.0, 0.0]
+    };
+    let x = math::normalize(math::cross(helper, z));
+    from_axes(x, math::cross(z, x), z)
+}
+
+/// The scene camera of a glTF camera node, aimed at the target the exporter
+/// stored, or one unit ahead, and with its up vector if stored.
+fn import_camera(doc: &Value, node: &Value, world: &Mat4) -> Camera {
+    let gltf_camera = &doc["cameras"][node["camera"].as_u64().unwrap_or(0) as usize];
+    let perspective = &gltf_camera["perspective"];
+    let position = mirror(math::transform_point(world, [0.0; 3]));
+    let forward = math::normalize(mirror(math::transform_vector(world, [0.0, 0.0, -1.0])));
+    let defaults = Camera::default();
+    Camera {
+        position,
+        target: vec3(&node["extras"]["target"]).unwrap_or(math::add(position, forward)),
+        up: vec3(&node["extras"]["up"]).unwrap_or_else(|| {
+            math::normalize(mirror(math::transform_vector(world, [0.0, 1.0, 0.0])))
+        }),
+        fov: perspective["yfov"]
+            .as_f64()
+            .map_or(defaults.fov, |fov| (fov as f32).to_degrees()),
+        znear: perspective["znear"]
+            .as_f64()
+            .map_or(defaults.znear, |z| z as f32),
+        zfar: perspective["zfar"]
+            .as_f64()
+            .map_or(defaults.zfar, |z| z as f32),
+        frame_on_load: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    const EPSILON: f32 = 1e-4;
+
+    fn assert_close(a: &[f32], b: &[f32], what: &str) {
+        assert_eq!(a.len(), b.len(), "{}", what);
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < EPSILON, "{}: {:?} != {:?}", what, a, b);
+        }
+    }
+
+    fn assert_same_matrix(a: &Transform, b: &Transform, what: &str) {
+        assert_close(a.matrix().as_flattened(), b.matrix().as_flattened(), what);
+    }
+
+    fn desc(name: &str) -> MeshDesc {
+        MeshDesc {
+            name: name.to_string(),
+            source: MeshSource::Teapot,
+            repair: None,
+            winding: Default::default(),
+            subdivision: None,
+            optimize: false,
+            lod: None,
+        }
+    }
+
+    fn transform(translation: Vec3, rotation: Vec3, scale: Vec3) -> Transform {
+        Transform {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    fn scene() -> Scene {
+        Scene {
+            clear_color: [0.1, 0.2, 0.3, 1.0],
+            camera: Camera {
+                position: [1.0, 2.0, -3.0],
+                target: [0.5, 0.0, 1.0],
+                up: [0.0, 1.0, 0.0],
+                fov: 45.0,
+                znear: 0.5,
+                zfar: 100.0,
+                frame_on_load: false,
+            },
+            lights: vec![
+                Light {
+                    kind: LightKind::Directional {
+                        direction: math::normalize([1.0, 2.0, -1.0]),
+                    },
+                    color: [1.0, 0.9, 0.8],
+                    intensity: 0.75,
+                },
+                Light {
+                    kind: LightKind::Point {
+                        position: [-2.0, 3.0, 4.0],
+                    },
+                    color: [0.2, 0.4, 1.0],
+                    intensity: 2.0,
+                },
+            ],
+            materials: vec![
+                Material {
+                    name: "red".to_string(),
+                    dark_color: [0.3, 0.0, 0.0],
+                    regular_color: [0.9, 0.1, 0.1],
+                },
+                Material {
+                    name: "green".to_string(),
+                    dark_color: [0.0, 0.25, 0.0],
+                    regular_color: [0.2, 0.8, 0.3],
+                },
+            ],
+            meshes: vec![desc("cube"), desc("sphere")],
+            nodes: vec![
+                Node {
+                    name: "box".to_string(),
+                    mesh: "cube".to_string(),
+                    material: Some("green".to_string()),
+                    transform: transform([1.0, -2.0, 3.0], [30.0, 45.0, 10.0], [1.0, 2.0, 0.5]),
+                    instances: None,
+                },
+                Node {
+                    name: "balls".to_string(),
+                    mesh: "sphere".to_string(),
+                    material: Some("red".to_string()),
+                    transform: transform([0.0, 1.0, 0.0], [0.0, 90.0, 0.0], [2.0; 3]),
+                    instances: Some(Instances::List(vec![
+                        Instance {
+                            transform: transform([-1.0, 0.0, 0.0], [0.0; 3], [1.0; 3]),
+                            color: [1.0, 0.5, 0.0],
+                        },
+                        Instance {
+                            transform: transform([1.0, 0.0, 2.0], [0.0, 0.0, 20.0], [0.5; 3]),
+                            color: [0.0, 0.5, 1.0],
+                        },
+                    ])),
+                },
+            ],
+        }
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("rusty_glad_{}_{}.glb", name, std::process::id()))
+    }
+
+    #[test]
+    fn glb_round_trip() {
+        let scene = scene();
+        let meshes = vec![primitives::cube(1.0, 2), primitives::icosphere(0.5, 1)];
+        let path = temp_path("round_trip");
+        save(&scene, &meshes, &path).unwrap();
+        let imported = import(&path);
+        fs::remove_file(&path).unwrap();
+        let (imported, imported_meshes) = imported.unwrap();
+
+        assert_eq!(imported_meshes.len(), meshes.len());
+        for (mesh, imported_mesh) in meshes.iter().zip(&imported_meshes) {
+            assert_eq!(mesh.indices, imported_mesh.indices);
+            assert_eq!(mesh.vertex_count(), imported_mesh.vertex_count());
+            for i in 0..mesh.vertex_count() as u32 {
+                assert_close(&mesh.position(i), &imported_mesh.position(i), "position");
+                assert_close(&mesh.normal(i), &imported_mesh.normal(i), "normal");
+            }
+            for (uv, imported_uv) in mesh.uvs.iter().zip(&imported_mesh.uvs) {
+                let (u, v) = uv.tex_coords;
+                let (iu, iv) = imported_uv.tex_coords;
+                assert_close(&[u, v], &[iu, iv], "uv");
+            }
+        }
+        let names: Vec<&str> = imported.meshes.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["cube", "sphere"]);
+
+        assert_close(&imported.clear_color, &scene.clear_color, "clear colour");
+        for (material, imported_material) in scene.materials.iter().zip(&imported.materials) {
+            assert_eq!(material.name, imported_material.name);
+            assert_close(&material.dark_color, &imported_material.dark_color, "dark");
+            assert_close(
+                &material.regular_color,
+                &imported_material.regular_color,
+                "regular",
+            );
+        }
+        assert_eq!(imported.materials.len(), scene.materials.len());
+
+        assert_eq!(imported.nodes.len(), scene.nodes.len());
+        for (node, imported_node) in scene.nodes.iter().zip(&imported.nodes) {
+            assert_eq!(node.name, imported_node.name);
+            assert_eq!(node.mesh, imported_node.mesh);
+            assert_eq!(node.material, imported_node.material);
+            assert_same_matrix(&node.transform, &imported_node.transform, &node.name);
+            let instances = node.instances.as_ref().map(Instances::expand);
+            let imported_instances = imported_node.instances.as_ref().map(Instances::expand);
+            assert_eq!(instances.is_some(), imported_instances.is_some());
+            for (instance, imported_instance) in instances
+                .iter()
+                .flatten()
+                .zip(imported_instances.iter().flatten())
+            {
+                assert_same_matrix(
+                    &instance.transform,
+                    &imported_instance.transform,
+                    "instance",
+                );
+                assert_close(&instance.color, &imported_instance.color, "instance colour");
+            }
+        }
+
+        assert_eq!(imported.lights.len(), scene.lights.len());
+        for (light, imported_light) in scene.lights.iter().zip(&imported.lights) {
+            match (&light.kind, &imported_light.kind) {
+                (
+                    LightKind::Directional { direction: a },
+                    LightKind::Directional { direction: b },
+                ) => assert_close(a, b, "direction"),
+                (LightKind::Point { position: a }, LightKind::Point { position: b }) => {
+                    assert_close(a, b, "light position")
+                }
+                _ => panic!("light kind changed"),
+            }
+            assert_close(&light.color, &imported_light.color, "light colour");
+            assert_close(&[light.intensity], &[imported_light.intensity], "intensity");
+        }
+
+        let (camera, imported_camera) = (&scene.camera, &imported.camera);
+        assert_close(
+            &camera.position,
+            &imported_camera.position,
+            "camera position",
+        );
+        assert_close(&camera.target, &imported_camera.target, "camera target");
+        assert_close(&camera.up, &imported_camera.up, "camera up");
+        assert_close(
+            &[camera.fov, camera.znear, camera.zfar],
+            &[
+                imported_camera.fov,
+                imported_camera.znear,
+                imported_camera.zfar,
+            ],
+            "camera projection",
+        );
+    }
+
+    #[test]
+    fn import_rejects_node_cycles() {
+        for nodes in [
+            json!([{ "children": [0] }]),
+            json!([{ "children": [1] }, { "children": [0] }]),
+            json!([{ "children": [1] }, {}, { "children": [1] }]),
+        ] {
+            let roots: Vec<usize> = (0..nodes.as_array().unwrap().len())
+                .filter(|&i| i != 1)
+                .collect();
+            let document = json!({
+                "asset": { "version": "2.0" },
+                "scene": 0,
+                "scenes": [{ "nodes": roots }],
+                "nodes": nodes,
+            });
+            let path = temp_path("cycle");
+            fs::write(&path, glb(&document, &[])).unwrap();
+            let result = import(&path);
+            fs::remove_file(&path).unwrap();
+            let err = result.err().expect("a node cycle should not import");
+            assert!(err.contains("more than once"), "{}", err);
+        }
+    }
+}
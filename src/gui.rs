@@ -0,0 +1,470 @@
+//! A small egui integration: window events go in as egui input and the
+//! tessellated output is drawn with glium.
+//!
+//! egui works in "points", which are `pixels_per_point` window pixels each,
+//! and hands over sRGB colours premultiplied by alpha. Those are blended as
+//! they are, without conversion to linear, which is what egui's own
+//! backends do and what its colours are tuned for.
+
+use egui::epaint::{textures::TexturesDelta, ClippedPrimitive, ImageData, Primitive};
+use egui::{
+    Context, CursorIcon, Event, Key, Modifiers, Pos2, RawInput, Rect, TextureFilter, TextureId,
+    TextureOptions, TextureWrapMode, Vec2,
+};
+use glium::{
+    glutin::event::{
+        ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode,
+        WindowEvent,
+    },
+    glutin::window::CursorIcon as WindowCursor,
+    index::PrimitiveType,
+    program::ProgramCreationInput,
+    texture::{MipmapsOption, RawImage2d, UncompressedFloatFormat},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction},
+    Blend, BlendingFunction, Display, DrawParameters, Frame, IndexBuffer, LinearBlendingFactor,
+    Program, Surface, Texture2d, VertexBuffer,
+};
+use std::collections::HashMap;
+use std::time::Instant;
+
+const VERTEX_SHADER_SRC: &str = r#"
+        #version 140
+
+        in vec2 position;
+        in vec2 tex_coords;
+        in vec4 color;
+
+        out vec2 v_tex_coords;
+        out vec4 v_color;
+
+        uniform vec2 u_screen_size;
+
+        void main() {
+            v_tex_coords = tex_coords;
+            v_color = color;
+            gl_Position = vec4(
+                2.0 * position.x / u_screen_size.x - 1.0,
+                1.0 - 2.0 * position.y / u_screen_size.y,
+                0.0,
+                1.0);
+        }
+    "#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+        #version 140
+
+        in vec2 v_tex_coords;
+        in vec4 v_color;
+        out vec4 color;
+
+        uniform sampler2D u_texture;
+
+        void main() {
+            color = v_color * texture(u_texture, v_tex_coords);
+        }
+    "#;
+
+/// An egui vertex, positioned in points from the top left of the window,
+/// with its colour as premultiplied sRGB in [0, 1].
+#[derive(Copy, Clone)]
+struct GuiVertex {
+    position: (f32, f32),
+    tex_coords: (f32, f32),
+    color: (f32, f32, f32, f32),
+}
+
+implement_vertex!(GuiVertex, position, tex_coords, color);
+
+/// The egui context together with the input gathered for its next frame and
+/// the output of its last one, ready to draw.
+pub struct Gui {
+    context: Context,
+    input: RawInput,
+    start: Instant,
+    pixels_per_point: f32,
+    /// Last cursor position, in points; winit reports buttons without one.
+    pointer: Pos2,
+    modifiers: Modifiers,
+    program: Program,
+    textures: HashMap<TextureId, (Texture2d, TextureOptions)>,
+    /// The last frame's meshes and the texture changes to make before
+    /// drawing them.
+    output: Option<(Vec<ClippedPrimitive>, TexturesDelta)>,
+}
+
+impl Gui {
+    pub fn new(display: &Display, pixels_per_point: f32) -> Gui {
+        let program = Program::new(
+            display,
+            ProgramCreationInput::SourceCode {
+                vertex_shader: VERTEX_SHADER_SRC,
+                tessellation_control_shader: None,
+                tessellation_evaluation_shader: None,
+                geometry_shader: None,
+                fragment_shader: FRAGMENT_SHADER_SRC,
+                transform_feedback_varyings: None,
+                // Keeps glium from converting the sRGB output a second time.
+                outputs_srgb: true,
+                uses_point_size: false,
+            },
+        )
+        .expect("failed to create gui program!");
+
+        Gui {
+            context: Context::default(),
+            input: RawInput::default(),
+            start: Instant::now(),
+            pixels_per_point,
+            pointer: Pos2::ZERO,
+            modifiers: Modifiers::default(),
+            program,
+            textures: HashMap::new(),
+            output: None,
+        }
+    }
+
+    /// Passes a window event on to egui. Returns whether egui takes it for
+    /// itself, as it does for clicks and scrolling over its windows and for
+    /// typing while one of its widgets has keyboard focus. Pointer motion and
+    /// button and key releases are never taken, so drags and held keys that
+    /// started outside egui end cleanly.
+    pub fn on_event(&mut self, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.pixels_per_point = *scale_factor as f32;
+                false
+            }
+            WindowEvent::Focused(focused) => {
+                self.input.focused = *focused;
+                self.input.events.push(Event::WindowFocused(*focused));
+                false
+            }
+            WindowEvent::ModifiersChanged(state) => {
+                self.modifiers = modifiers(*state);
+                self.input.modifiers = self.modifiers;
+                false
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.pointer = Pos2::new(
+                    position.x as f32 / self.pixels_per_point,
+                    position.y as f32 / self.pixels_per_point,
+                );
+                self.input.events.push(Event::PointerMoved(self.pointer));
+                false
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.input.events.push(Event::PointerGone);
+                false
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = match button {
+                    MouseButton::Left => egui::PointerButton::Primary,
+                    MouseButton::Right => egui::PointerButton::Secondary,
+                    MouseButton::Middle => egui::PointerButton::Middle,
+                    MouseButton::Other(_) => return false,
+                };
+                let pressed = *state == ElementState::Pressed;
+                self.input.events.push(Event::PointerButton {
+                    pos: self.pointer,
+                    button,
+                    pressed,
+                    modifiers: self.modifiers,
+                });
+                pressed && self.context.is_pointer_over_area()
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (unit, delta) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => {
+                        (egui::MouseWheelUnit::Line, Vec2::new(*x, *y))
+                    }
+                    MouseScrollDelta::PixelDelta(p) => (
+                        egui::MouseWheelUnit::Point,
+                        Vec2::new(p.x as f32, p.y as f32) / self.pixels_per_point,
+                    ),
+                };
+                self.input.events.push(Event::MouseWheel {
+                    unit,
+                    delta,
+                    modifiers: self.modifiers,
+                });
+                self.context.is_pointer_over_area()
+            }
+            WindowEvent::ReceivedCharacter(c) => {
+                if !c.is_control() && !self.modifiers.ctrl {
+                    self.input.events.push(Event::Text(c.to_string()));
+                }
+                self.context.wants_keyboard_input()
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(code),
+                        ..
+                    },
+                ..
+            } => {
+                let pressed = *state == ElementState::Pressed;
+                if let Some(key) = key(*code) {
+                    self.input.events.push(Event::Key {
+                        key,
+                        physical_key: None,
+                        pressed,
+                        repeat: false,
+                        modifiers: self.modifiers,
+                    });
+                }
+                pressed && self.context.wants_keyboard_input()
+            }
+            _ => false,
+        }
+    }
+
+    /// Runs one egui frame over a window `width` by `height` pixels, building
+    /// the interface with `ui`, and keeps the result for `draw`. Returns the
+    /// mouse cursor egui asks for.
+    pub fn run(
+        &mut self,
+        (width, height): (u32, u32),
+        ui: impl FnMut(&Context),
+    ) -> Option<WindowCursor> {
+        let next = RawInput {
+            focused: self.input.focused,
+            modifiers: self.modifiers,
+            ..Default::default()
+        };
+        let mut input = std::mem::replace(&mut self.input, next);
+        input.time = Some(self.start.elapsed().as_secs_f64());
+        input.screen_rect = Some(Rect::from_min_size(
+            Pos2::ZERO,
+            Vec2::new(width as f32, height as f32) / self.pixels_per_point,
+        ));
+        input
+            .viewports
+            .entry(input.viewport_id)
+            .or_default()
+            .native_pixels_per_point = Some(self.pixels_per_point);
+
+        let output = self.context.run(input, ui);
+        let primitives = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+        // Texture changes pile up when frames are built but never drawn.
+        let mut textures = self
+            .output
+            .take()
+            .map_or_else(TexturesDelta::default, |(_, textures)| textures);
+        textures.append(output.textures_delta);
+        self.output = Some((primitives, textures));
+        cursor(output.platform_output.cursor_icon)
+    }
+
+    /// Draws the last frame built by `run` over `target`.
+    pub fn draw(&mut self, display: &Display, target: &mut Frame) {
+        let Some((primitives, textures)) = self.output.take() else {
+            return;
+        };
+        for (id, delta) in textures.set {
+            let ImageData::Color(image) = &delta.image;
+            let pixels: Vec<u8> = image.pixels.iter().flat_map(|c| c.to_array()).collect();
+            let [width, height] = image.size;
+            let raw = RawImage2d::from_raw_rgba(pixels, (width as u32, height as u32));
+            match (delta.pos, self.textures.get_mut(&id)) {
+                (Some([x, y]), Some((texture, _))) => texture.write(
+                    glium::Rect {
+                        left: x as u32,
+                        bottom: y as u32,
+                        width: width as u32,
+                        height: height as u32,
+                    },
+                    raw,
+                ),
+                _ => {
+                    let texture = Texture2d::with_format(
+                        display,
+                        raw,
+                        UncompressedFloatFormat::U8U8U8U8,
+                        MipmapsOption::NoMipmap,
+                    )
+                    .expect("failed to create gui texture!");
+                    self.textures.insert(id, (texture, delta.options));
+                }
+            }
+        }
+
+        let (width, height) = target.get_dimensions();
+        let screen_size = [
+            width as f32 / self.pixels_per_point,
+            height as f32 / self.pixels_per_point,
+        ];
+        let blend = Blend {
+            color: BlendingFunction::Addition {
+                source: LinearBlendingFactor::One,
+                destination: LinearBlendingFactor::OneMinusSourceAlpha,
+            },
+            alpha: BlendingFunction::Addition {
+                source: LinearBlendingFactor::OneMinusDestinationAlpha,
+                destination: LinearBlendingFactor::One,
+            },
+            constant_value: (0.0, 0.0, 0.0, 0.0),
+        };
+
+        for ClippedPrimitive {
+            clip_rect,
+            primitive,
+        } in primitives
+        {
+            let Primitive::Mesh(mesh) = primitive else {
+                continue;
+            };
+            let Some((texture, options)) = self.textures.get(&mesh.texture_id) else {
+                continue;
+            };
+            let Some(scissor) = scissor(clip_rect, self.pixels_per_point, (width, height)) else {
+                continue;
+            };
+
+            let vertices: Vec<GuiVertex> = mesh
+                .vertices
+                .iter()
+                .map(|v| {
+                    let [r, g, b, a] = v.color.to_array().map(|c| c as f32 / 255.0);
+                    GuiVertex {
+                        position: (v.pos.x, v.pos.y),
+                        tex_coords: (v.uv.x, v.uv.y),
+                        color: (r, g, b, a),
+                    }
+                })
+                .collect();
+            let vertices =
+                VertexBuffer::new(display, &vertices).expect("failed to create gui VertexBuffer!");
+            let indices = IndexBuffer::new(display, PrimitiveType::TrianglesList, &mesh.indices)
+                .expect("failed to create gui IndexBuffer!");
+
+            let uniforms = uniform! {
+                u_screen_size: screen_size,
+                u_texture: texture
+                    .sampled()
+                    .magnify_filter(match options.magnification {
+                        TextureFilter::Nearest => MagnifySamplerFilter::Nearest,
+                        TextureFilter::Linear => MagnifySamplerFilter::Linear,
+                    })
+                    .minify_filter(match options.minification {
+                        TextureFilter::Nearest => MinifySamplerFilter::Nearest,
+                        TextureFilter::Linear => MinifySamplerFilter::Linear,
+                    })
+                    .wrap_function(match options.wrap_mode {
+                        TextureWrapMode::ClampToEdge => SamplerWrapFunction::Clamp,
+                        TextureWrapMode::Repeat => SamplerWrapFunction::Repeat,
+                        TextureWrapMode::MirroredRepeat => SamplerWrapFunction::Mirror,
+                    }),
+            };
+            let params = DrawParameters {
+                blend,
+                scissor: Some(scissor),
+                ..Default::default()
+            };
+
+            target
+                .draw(&vertices, &indices, &self.program, &uniforms, &params)
+                .expect("failed to draw gui!");
+        }
+
+        for id in textures.free {
+            self.textures.remove(&id);
+        }
+    }
+}
+
+/// `clip_rect`, in points, as a scissor rectangle in the pixels of a window
+/// `width` by `height`, or `None` when nothing of it is on screen.
+fn scissor(
+    clip_rect: Rect,
+    pixels_per_point: f32,
+    (width, height): (u32, u32),
+) -> Option<glium::Rect> {
+    let left = (clip_rect.min.x * pixels_per_point)
+        .round()
+        .clamp(0.0, width as f32) as u32;
+    let right = (clip_rect.max.x * pixels_per_point)
+        .round()
+        .clamp(0.0, width as f32) as u32;
+    let top = (clip_rect.min.y * pixels_per_point)
+        .round()
+        .clamp(0.0, height as f32) as u32;
+    let bottom = (clip_rect.max.y * pixels_per_point)
+        .round()
+        .clamp(0.0, height as f32) as u32;
+    if right <= left || bottom <= top {
+        return None;
+    }
+    Some(glium::Rect {
+        left,
+        bottom: height - bottom,
+        width: right - left,
+        height: bottom - top,
+    })
+}
+
+fn modifiers(state: ModifiersState) -> Modifiers {
+    let command = if cfg!(target_os = "macos") {
+        state.logo()
+    } else {
+        state.ctrl()
+    };
+    Modifiers {
+        alt: state.alt(),
+        ctrl: state.ctrl(),
+        shift: state.shift(),
+        mac_cmd: cfg!(target_os = "macos") && state.logo(),
+        command,
+    }
+}
+
+/// The egui key for the keys its widgets react to: editing, navigation and
+/// the letters of the usual shortcuts.
+fn key(code: VirtualKeyCode) -> Option<Key> {
+    Some(match code {
+        VirtualKeyCode::Down => Key::ArrowDown,
+        VirtualKeyCode::Left => Key::ArrowLeft,
+        VirtualKeyCode::Right => Key::ArrowRight,
+        VirtualKeyCode::Up => Key::ArrowUp,
+        VirtualKeyCode::Escape => Key::Escape,
+        VirtualKeyCode::Tab => Key::Tab,
+        VirtualKeyCode::Back => Key::Backspace,
+        VirtualKeyCode::Return | VirtualKeyCode::NumpadEnter => Key::Enter,
+        VirtualKeyCode::Space => Key::Space,
+        VirtualKeyCode::Insert => Key::Insert,
+        VirtualKeyCode::Delete => Key::Delete,
+        VirtualKeyCode::Home => Key::Home,
+        VirtualKeyCode::End => Key::End,
+        VirtualKeyCode::PageUp => Key::PageUp,
+        VirtualKeyCode::PageDown => Key::PageDown,
+        VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract => Key::Minus,
+        VirtualKeyCode::A => Key::A,
+        VirtualKeyCode::C => Key::C,
+        VirtualKeyCode::V => Key::V,
+        VirtualKeyCode::X => Key::X,
+        VirtualKeyCode::Y => Key::Y,
+        VirtualKeyCode::Z => Key::Z,
+        _ => return None,
+    })
+}
+
+/// The window cursor for `icon`, or `None` to hide it.
+fn cursor(icon: CursorIcon) -> Option<WindowCursor> {
+    Some(match icon {
+        CursorIcon::None => return None,
+        CursorIcon::PointingHand => WindowCursor::Hand,
+        CursorIcon::Text => WindowCursor::Text,
+        CursorIcon::Grab => WindowCursor::Grab,
+        CursorIcon::Grabbing => WindowCursor::Grabbing,
+        CursorIcon::Move | CursorIcon::AllScroll => WindowCursor::Move,
+        CursorIcon::NotAllowed | CursorIcon::NoDrop => WindowCursor::NotAllowed,
+        CursorIcon::ResizeHorizontal | CursorIcon::ResizeColumn => WindowCursor::EwResize,
+        CursorIcon::ResizeVertical | CursorIcon::ResizeRow => WindowCursor::NsResize,
+        CursorIcon::ResizeNeSw => WindowCursor::NeswResize,
+        CursorIcon::ResizeNwSe => WindowCursor::NwseResize,
+        _ => WindowCursor::Default,
+    })
+}
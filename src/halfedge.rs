@@ -0,0 +1,494 @@
+use crate::math::Vec3;
+use crate::mesh::{Mesh, Vertex};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// Polygon connectivity as half-edges: every face is a loop of directed
+/// half-edges and the two half-edges of an interior edge are each other's
+/// twin.
+///
+/// The half-edges of a face are numbered consecutively in corner order, so
+/// faces keep the winding they were built with. Edges used by more than two
+/// faces, or by two faces running the same way, are non-manifold: their
+/// half-edges get no twin and each counts as an edge of its own.
+pub struct HalfEdgeMesh {
+    pub positions: Vec<Vec3>,
+    origin: Vec<u32>,
+    twin: Vec<Option<u32>>,
+    face: Vec<u32>,
+    edge: Vec<u32>,
+    non_manifold: Vec<bool>,
+    /// Vertex of the source buffer each half-edge starts at; see `indices`.
+    corners: Vec<u32>,
+    /// `face_start[f]..face_start[f + 1]` are the half-edges of face `f`.
+    face_start: Vec<u32>,
+    /// One half-edge of each edge.
+    edges: Vec<u32>,
+    /// `outgoing[vertex_start[v]..vertex_start[v + 1]]` are the half-edges
+    /// leaving vertex `v`.
+    vertex_start: Vec<u32>,
+    outgoing: Vec<u32>,
+}
+
+impl HalfEdgeMesh {
+    /// Builds the connectivity of polygons given as indices into `positions`.
+    pub fn new(positions: Vec<Vec3>, faces: &[Vec<u32>]) -> HalfEdgeMesh {
+        let corners = faces.iter().flatten().copied().collect();
+        HalfEdgeMesh::with_corners(positions, faces, corners)
+    }
+
+    /// Builds the connectivity of a triangle mesh after welding its vertices
+    /// by position, so triangles split only for normals or UVs still share
+    /// edges. Triangles that collapse when welded are left out.
+    pub fn from_mesh(mesh: &Mesh) -> HalfEdgeMesh {
+        let (welded, positions) = mesh.weld();
+        let triangles: Vec<[u32; 3]> = mesh
+            .triangles()
+            .filter(|t| {
+                let [a, b, c] = t.map(|i| welded[i as usize]);
+                a != b && b != c && c != a
+            })
+            .collect();
+        let faces: Vec<Vec<u32>> = triangles
+            .iter()
+            .map(|t| t.iter().map(|&i| welded[i as usize]).collect())
+            .collect();
+        let corners = triangles.into_iter().flatten().collect();
+        HalfEdgeMesh::with_corners(positions, &faces, corners)
+    }
+
+    fn with_corners(positions: Vec<Vec3>, faces: &[Vec<u32>], corners: Vec<u32>) -> HalfEdgeMesh {
+        let count = corners.len();
+        let mut mesh = HalfEdgeMesh {
+            positions,
+            origin: Vec::with_capacity(count),
+            twin: vec![None; count],
+            face: Vec::with_capacity(count),
+            edge: vec![0; count],
+            non_manifold: vec![false; count],
+            corners,
+            face_start: Vec::with_capacity(faces.len() + 1),
+            edges: Vec::new(),
+            vertex_start: Vec::new(),
+            outgoing: Vec::new(),
+        };
+
+        for (f, face) in faces.iter().enumerate() {
+            mesh.face_start.push(mesh.origin.len() as u32);
+            mesh.origin.extend_from_slice(face);
+            mesh.face.extend(face.iter().map(|_| f as u32));
+        }
+        mesh.face_start.push(count as u32);
+
+        // Half-edges along each undirected edge; exactly two running
+        // opposite ways are twins.
+        let mut along: HashMap<(u32, u32), Vec<u32>> = HashMap::new();
+        for h in 0..count as u32 {
+            let (a, b) = (mesh.origin(h), mesh.destination(h));
+            along.entry((a.min(b), a.max(b))).or_default().push(h);
+        }
+        for halves in along.values() {
+            match halves[..] {
+                [_] => {}
+                [h, twin] if mesh.origin(h) == mesh.destination(twin) => {
+                    mesh.twin[h as usize] = Some(twin);
+                    mesh.twin[twin as usize] = Some(h);
+                }
+                _ => halves
+                    .iter()
+                    .for_each(|&h| mesh.non_manifold[h as usize] = true),
+            }
+        }
+
+        for h in 0..count as u32 {
+            match mesh.twin(h) {
+                Some(twin) if twin < h => mesh.edge[h as usize] = mesh.edge[twin as usize],
+                _ => {
+                    mesh.edge[h as usize] = mesh.edges.len() as u32;
+                    mesh.edges.push(h);
+                }
+            }
+        }
+
+        let vertex_count = mesh.positions.len();
+        let mut start = vec![0u32; vertex_count + 1];
+        for &v in &mesh.origin {
+            start[v as usize + 1] += 1;
+        }
+        for v in 0..vertex_count {
+            start[v + 1] += start[v];
+        }
+        let mut fill = start.clone();
+        mesh.outgoing = vec![0; count];
+        for h in 0..count as u32 {
+            let slot = &mut fill[mesh.origin(h) as usize];
+            mesh.outgoing[*slot as usize] = h;
+            *slot += 1;
+        }
+        mesh.vertex_start = start;
+        mesh
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.face_start.len() - 1
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn half_edge_count(&self) -> usize {
+        self.origin.len()
+    }
+
+    pub fn origin(&self, h: u32) -> u32 {
+        self.origin[h as usize]
+    }
+
+    pub fn destination(&self, h: u32) -> u32 {
+        self.origin(self.next(h))
+    }
+
+    /// The next half-edge around the same face.
+    pub fn next(&self, h: u32) -> u32 {
+        let f = self.face(h) as usize;
+        if h + 1 == self.face_start[f + 1] {
+            self.face_start[f]
+        } else {
+            h + 1
+        }
+    }
+
+    pub fn prev(&self, h: u32) -> u32 {
+        let f = self.face(h) as usize;
+        if h == self.face_start[f] {
+            self.face_start[f + 1] - 1
+        } else {
+            h - 1
+        }
+    }
+
+    /// The opposite half-edge, or `None` on borders and non-manifold edges.
+    pub fn twin(&self, h: u32) -> Option<u32> {
+        self.twin[h as usize]
+    }
+
+    pub fn face(&self, h: u32) -> u32 {
+        self.face[h as usize]
+    }
+
+    /// The edge `h` lies on; twins share an edge.
+    pub fn edge(&self, h: u32) -> u32 {
+        self.edge[h as usize]
+    }
+
+    /// One half-edge of every edge, in edge order.
+    pub fn edges(&self) -> impl Iterator<Item = u32> + '_ {
+        self.edges.iter().copied()
+    }
+
+    /// Whether `h` lies on an open border, with no face on its other side.
+    pub fn is_boundary(&self, h: u32) -> bool {
+        self.twin(h).is_none() && !self.non_manifold[h as usize]
+    }
+
+    pub fn is_non_manifold(&self, h: u32) -> bool {
+        self.non_manifold[h as usize]
+    }
+
+    pub fn face_half_edges(&self, f: u32) -> Range<u32> {
+        self.face_start[f as usize]..self.face_start[f as usize + 1]
+    }
+
+    pub fn face_vertices(&self, f: u32) -> impl Iterator<Item = u32> + '_ {
+        self.face_half_edges(f).map(|h| self.origin(h))
+    }
+
+    /// The faces across each edge of face `f`, in corner order.
+    pub fn face_neighbours(&self, f: u32) -> impl Iterator<Item = Option<u32>> + '_ {
+        self.face_half_edges(f)
+            .map(|h| self.twin(h).map(|twin| self.face(twin)))
+    }
+
+    pub fn outgoing(&self, v: u32) -> &[u32] {
+        let v = v as usize;
+        &self.outgoing[self.vertex_start[v] as usize..self.vertex_start[v + 1] as usize]
+    }
+
+    pub fn vertex_faces(&self, v: u32) -> impl Iterator<Item = u32> + '_ {
+        self.outgoing(v).iter().map(|&h| self.face(h))
+    }
+
+    /// The outgoing half-edges of `v` grouped into fans of faces joined by
+    /// shared edges, each in walking order. A manifold vertex has a single
+    /// fan; a fan is open when the vertex lies on a border.
+    pub fn fans(&self, v: u32) -> Vec<Vec<u32>> {
+        let outgoing = self.outgoing(v);
+        let mut seen = vec![false; outgoing.len()];
+        let mut fans = Vec::new();
+
+        for (i, &h) in outgoing.iter().enumerate() {
+            if seen[i] {
+                continue;
+            }
+            // Walk back to the start of an open fan, then forwards across
+            // each outgoing edge to the next face.
+            let mut start = h;
+            while let Some(twin) = self.twin(self.prev(start)) {
+                if twin == h {
+                    break;
+                }
+                start = twin;
+            }
+            let mut fan = vec![start];
+            let mut current = start;
+            while let Some(next) = self.twin(current).map(|twin| self.next(twin)) {
+                if next == start {
+                    break;
+                }
+                fan.push(next);
+                current = next;
+            }
+            for &h in &fan {
+                if let Some(k) = outgoing.iter().position(|&o| o == h) {
+                    seen[k] = true;
+                }
+            }
+            fans.push(fan);
+        }
+        fans
+    }
+
+    /// The neighbours of `v` in walking order around each of its fans.
+    pub fn one_ring(&self, v: u32) -> Vec<u32> {
+        let mut ring = Vec::new();
+        for fan in self.fans(v) {
+            // An open fan also reaches the far end of its first face's
+            // incoming border edge.
+            let first = fan[0];
+            if self.twin(self.prev(first)).is_none() {
+                ring.push(self.origin(self.prev(first)));
+            }
+            ring.extend(fan.iter().map(|&h| self.destination(h)));
+        }
+        ring
+    }
+
+    /// Vertices whose faces form more than one fan, like the shared tip of
+    /// two cones.
+    pub fn non_manifold_vertices(&self) -> Vec<u32> {
+        (0..self.vertex_count() as u32)
+            .filter(|&v| self.fans(v).len() > 1)
+            .collect()
+    }
+
+    /// End points of every non-manifold edge, each edge listed once.
+    pub fn non_manifold_edges(&self) -> Vec<[u32; 2]> {
+        let mut seen = HashSet::new();
+        self.edges()
+            .filter(|&h| self.is_non_manifold(h))
+            .map(|h| {
+                let (a, b) = (self.origin(h), self.destination(h));
+                [a.min(b), a.max(b)]
+            })
+            .filter(|edge| seen.insert(*edge))
+            .collect()
+    }
+
+    /// The vertices around each hole or open border, following the border
+    /// half-edges. Reversed, a loop is a polygon that closes its hole with
+    /// matching winding.
+    pub fn boundary_loops(&self) -> Vec<Vec<u32>> {
+        let mut visited = vec![false; self.half_edge_count()];
+        let mut loops = Vec::new();
+
+        for start in 0..self.half_edge_count() as u32 {
+            if visited[start as usize] || !self.is_boundary(start) {
+                continue;
+            }
+            let mut vertices = Vec::new();
+            let mut h = start;
+            loop {
+                visited[h as usize] = true;
+                vertices.push(self.origin(h));
+                let next = self
+                    .outgoing(self.destination(h))
+                    .iter()
+                    .copied()
+                    .find(|&o| self.is_boundary(o) && (o == start || !visited[o as usize]));
+                match next {
+                    Some(next) if next != start => h = next,
+                    _ => break,
+                }
+            }
+            loops.push(vertices);
+        }
+        loops
+    }
+
+    /// The faces fanned into triangles as an index buffer over the vertices
+    /// the mesh was built from: the source mesh's for `from_mesh`, otherwise
+    /// `positions`.
+    pub fn indices(&self) -> Vec<u32> {
+        let mut indices = Vec::with_capacity(self.half_edge_count() * 3);
+        for f in 0..self.face_count() as u32 {
+            let range = self.face_half_edges(f);
+            let first = self.corners[range.start as usize];
+            for h in range.start + 1..range.end - 1 {
+                indices.extend_from_slice(&[
+                    first,
+                    self.corners[h as usize],
+                    self.corners[h as usize + 1],
+                ]);
+            }
+        }
+        indices
+    }
+
+    /// A triangle mesh of `positions` with smooth normals.
+    pub fn to_mesh(&self) -> Mesh {
+        let mut indices = Vec::with_capacity(self.half_edge_count() * 3);
+        for f in 0..self.face_count() as u32 {
+            let range = self.face_half_edges(f);
+            for h in range.start + 1..range.end - 1 {
+                indices.extend_from_slice(&[
+                    self.origin(range.start),
+                    self.origin(h),
+                    self.origin(h + 1),
+                ]);
+            }
+        }
+        let mut mesh = Mesh {
+            positions: self
+                .positions
+                .iter()
+                .map(|&[x, y, z]| Vertex {
+                    position: (x, y, z),
+                })
+                .collect(),
+            indices,
+            ..Mesh::default()
+        };
+        mesh.compute_normals();
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    fn euler_characteristic(mesh: &HalfEdgeMesh) -> i64 {
+        mesh.vertex_count() as i64 - mesh.edge_count() as i64 + mesh.face_count() as i64
+    }
+
+    /// A unit cube of consistently wound quads, vertex `i` at the corner
+    /// whose x, y and z are bits 0, 1 and 2 of `i`.
+    fn quad_cube() -> HalfEdgeMesh {
+        let positions = (0..8)
+            .map(|i| [(i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32])
+            .collect();
+        let faces = [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ]
+        .map(Vec::from);
+        HalfEdgeMesh::new(positions, &faces)
+    }
+
+    #[test]
+    fn closed_cubes_have_euler_characteristic_two() {
+        let quads = quad_cube();
+        assert_eq!(
+            (quads.vertex_count(), quads.edge_count(), quads.face_count()),
+            (8, 12, 6)
+        );
+        assert_eq!(euler_characteristic(&quads), 2);
+
+        // The triangulated cube has split normals, which `from_mesh` welds.
+        let triangles = HalfEdgeMesh::from_mesh(&primitives::cube(1.0, 1));
+        assert_eq!(
+            (
+                triangles.vertex_count(),
+                triangles.edge_count(),
+                triangles.face_count()
+            ),
+            (8, 18, 12)
+        );
+        assert_eq!(euler_characteristic(&triangles), 2);
+
+        for mesh in [&quads, &triangles] {
+            assert_eq!(mesh.half_edge_count(), 2 * mesh.edge_count());
+            for h in 0..mesh.half_edge_count() as u32 {
+                let twin = mesh.twin(h).expect("a closed cube has no borders");
+                assert_eq!(mesh.twin(twin), Some(h));
+                assert_eq!(mesh.origin(twin), mesh.destination(h));
+                assert_eq!(mesh.edge(twin), mesh.edge(h));
+                assert_eq!(mesh.prev(mesh.next(h)), h);
+            }
+            assert!(mesh.boundary_loops().is_empty());
+            assert!(mesh.non_manifold_vertices().is_empty());
+        }
+    }
+
+    #[test]
+    fn cube_corners_have_three_neighbours() {
+        let cube = quad_cube();
+        for v in 0..8 {
+            let mut ring = cube.one_ring(v);
+            ring.sort();
+            // The neighbours differ from `v` in exactly one coordinate.
+            let mut expected = [v ^ 1, v ^ 2, v ^ 4];
+            expected.sort();
+            assert_eq!(ring, expected);
+            assert_eq!(cube.fans(v).len(), 1);
+        }
+    }
+
+    #[test]
+    fn open_square_has_one_boundary_loop() {
+        let positions = vec![[0.0; 3], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]];
+        let square = HalfEdgeMesh::new(positions, &[vec![0, 1, 2], vec![0, 2, 3]]);
+        assert_eq!(euler_characteristic(&square), 1);
+        let loops = square.boundary_loops();
+        assert_eq!(loops.len(), 1);
+        let mut border = loops[0].clone();
+        border.sort();
+        assert_eq!(border, [0, 1, 2, 3]);
+        assert_eq!(square.one_ring(0), [3, 2, 1]);
+    }
+
+    #[test]
+    fn bow_tie_vertex_is_non_manifold() {
+        let positions = vec![
+            [0.0; 3],
+            [1.0, 1.0, 0.0],
+            [-1.0, 1.0, 0.0],
+            [1.0, -1.0, 0.0],
+            [-1.0, -1.0, 0.0],
+        ];
+        let bow_tie = HalfEdgeMesh::new(positions, &[vec![0, 1, 2], vec![0, 4, 3]]);
+        assert_eq!(bow_tie.non_manifold_vertices(), [0]);
+        assert_eq!(bow_tie.fans(0).len(), 2);
+
+        // A third face on one edge makes the edge non-manifold.
+        let positions = vec![
+            [0.0; 3],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [0.0, 0.0, 1.0],
+        ];
+        let fin = HalfEdgeMesh::new(positions, &[vec![0, 1, 2], vec![1, 0, 3], vec![0, 1, 4]]);
+        assert_eq!(fin.non_manifold_edges(), [[0, 1]]);
+    }
+}
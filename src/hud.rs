@@ -0,0 +1,107 @@
+//! The on-screen statistics panel in the top left of the window.
+
+use crate::camera::Camera;
+use crate::math::Vec3;
+use crate::render::{DrawStats, RenderMode};
+use crate::text::TextRenderer;
+use std::time::Duration;
+
+/// Gap between the panel and the window corner, and between the panel's
+/// edge and its text, in pixels.
+const MARGIN: f32 = 8.0;
+const PADDING: f32 = 6.0;
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+/// Frame times drawn in this colour are slower than 60 fps.
+const SLOW_COLOR: [f32; 4] = [1.0, 0.6, 0.2, 1.0];
+const SLOW_FRAME: Duration = Duration::from_micros(16_667);
+
+/// Frame times over the last reporting interval.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FrameTimes {
+    pub average: Duration,
+    pub slowest: Duration,
+}
+
+/// What the panel shows.
+pub struct Hud<'a> {
+    /// `None` until the first interval has been measured.
+    pub frame_times: Option<FrameTimes>,
+    pub stats: DrawStats,
+    pub camera: &'a Camera,
+    pub render_mode: RenderMode,
+}
+
+impl Hud<'_> {
+    /// Queues the panel on `text`.
+    pub fn queue(&self, text: &mut TextRenderer) {
+        let mut lines = Vec::new();
+        match self.frame_times {
+            Some(times) => {
+                let average = times.average.as_secs_f64() * 1000.0;
+                let color = if times.slowest > SLOW_FRAME {
+                    SLOW_COLOR
+                } else {
+                    TEXT_COLOR
+                };
+                lines.push((
+                    format!(
+                        "{:.2} ms/frame  {:.0} fps  worst {:.2} ms",
+                        average,
+                        1000.0 / average,
+                        times.slowest.as_secs_f64() * 1000.0
+                    ),
+                    color,
+                ));
+            }
+            None => lines.push(("-- ms/frame".to_string(), TEXT_COLOR)),
+        }
+        lines.push((
+            format!(
+                "{} draw calls  {} drawn  {} culled",
+                self.stats.draw_calls, self.stats.instances, self.stats.culled
+            ),
+            TEXT_COLOR,
+        ));
+        lines.push((format!("{} triangles", self.stats.triangles), TEXT_COLOR));
+        lines.push((
+            format!("camera {}", vector(self.camera.position)),
+            TEXT_COLOR,
+        ));
+        lines.push((format!("target {}", vector(self.camera.target)), TEXT_COLOR));
+        lines.push((
+            format!(
+                "fov {:.0}°  near {:.2}  far {:.2}",
+                self.camera.fov, self.camera.znear, self.camera.zfar
+            ),
+            TEXT_COLOR,
+        ));
+        lines.push((format!("{} mode", self.render_mode.name()), TEXT_COLOR));
+
+        let line_height = text.line_height();
+        let width = lines
+            .iter()
+            .map(|(line, _)| text.width(line))
+            .fold(0.0, f32::max);
+        let height = lines.len() as f32 * line_height;
+        text.rect(
+            [MARGIN, MARGIN],
+            [
+                MARGIN + width + 2.0 * PADDING,
+                MARGIN + height + 2.0 * PADDING,
+            ],
+            BACKGROUND_COLOR,
+        );
+        for (i, (line, color)) in lines.iter().enumerate() {
+            text.text(
+                [MARGIN + PADDING, MARGIN + PADDING + i as f32 * line_height],
+                line,
+                *color,
+            );
+        }
+    }
+}
+
+fn vector([x, y, z]: Vec3) -> String {
+    format!("({:.2}, {:.2}, {:.2})", x, y, z)
+}
@@ -0,0 +1,255 @@
+use crate::convert;
+use crate::halfedge::HalfEdgeMesh;
+use crate::math::{self, Vec3};
+use crate::mesh::Mesh;
+use crate::optimize;
+use crate::repair;
+use crate::scene::Scene;
+use crate::winding;
+use std::path::Path;
+
+/// Width of the longest histogram bar, in characters.
+const BAR_WIDTH: usize = 40;
+
+const HISTOGRAM_BINS: usize = 10;
+
+/// Prints statistics for the meshes named by `target`: `teapot` for the
+/// built-in teapot, a mesh file `convert::load` reads, or a scene file,
+/// whose meshes are loaded as the viewer would draw them.
+pub fn run(target: &str) -> Result<(), String> {
+    let path = Path::new(target);
+    let meshes = match path.extension().and_then(|e| e.to_str()) {
+        Some("ron" | "json") => {
+            let scene = Scene::load(path)?;
+            let base_dir = path.parent().unwrap_or(Path::new(""));
+            let (meshes, logs) = scene.load_meshes(base_dir)?;
+            for (desc, log) in scene.meshes.iter().zip(&logs) {
+                log.print(&desc.name);
+            }
+            scene
+                .meshes
+                .iter()
+                .map(|desc| desc.name.clone())
+                .zip(meshes)
+                .collect()
+        }
+        _ => vec![(target.to_owned(), convert::load(target)?)],
+    };
+    for (i, (name, mesh)) in meshes.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        println!("{}", name);
+        print_stats(mesh);
+    }
+    Ok(())
+}
+
+fn print_stats(mesh: &Mesh) {
+    let attributes = |present: bool, name: &str| {
+        if present {
+            name.to_owned()
+        } else {
+            format!("no {}", name)
+        }
+    };
+    println!(
+        "  vertices      {} ({}, {})",
+        mesh.vertex_count(),
+        attributes(mesh.normals.len() == mesh.vertex_count(), "normals"),
+        attributes(mesh.uvs.len() == mesh.vertex_count(), "UVs")
+    );
+    println!("  triangles     {}", mesh.triangle_count());
+
+    let report = repair::validate(mesh);
+    if !report.out_of_range.is_empty() {
+        // Nothing below can index the vertices safely.
+        println!("  {}", report);
+        return;
+    }
+
+    let bounds = mesh.bounds();
+    if !bounds.is_empty() {
+        println!(
+            "  bounds        {} to {}, size {}",
+            vector(bounds.min),
+            vector(bounds.max),
+            vector(math::sub(bounds.max, bounds.min))
+        );
+    }
+    let area: f32 = mesh
+        .triangles()
+        .map(|t| math::length(mesh.face_normal(t)) / 2.0)
+        .sum();
+    println!("  surface area  {:.4}", area);
+
+    let half_edges = HalfEdgeMesh::from_mesh(mesh);
+    let topology = Topology::new(&half_edges);
+    let volume = enclosed_volume(mesh);
+    if topology.boundaries == 0 {
+        println!("  volume        {:.4}", volume);
+    } else {
+        println!("  volume        {:.4} (open, so only an estimate)", volume);
+    }
+
+    println!(
+        "  topology      V {} - E {} + F {} = χ {}, {} component{}, {} boundary loop{}",
+        topology.vertices,
+        topology.edges,
+        topology.faces,
+        topology.euler_characteristic(),
+        topology.components,
+        plural(topology.components),
+        topology.boundaries,
+        plural(topology.boundaries)
+    );
+    let non_manifold_edges = half_edges.non_manifold_edges().len();
+    let non_manifold_vertices = half_edges.non_manifold_vertices().len();
+    if non_manifold_edges == 0 && non_manifold_vertices == 0 {
+        println!("  manifold      yes, genus {}", topology.genus());
+    } else {
+        println!(
+            "  manifold      no: {} non-manifold edges, {} non-manifold vertices",
+            non_manifold_edges, non_manifold_vertices
+        );
+    }
+
+    let winding = winding::detect(mesh).map_or("undetermined", |w| w.name());
+    println!("  winding       {}", winding);
+    println!(
+        "  vertex cache  {}",
+        optimize::analyze(&mesh.indices, mesh.vertex_count())
+    );
+    // Lines the report's problem list up under its first line.
+    let report = report.to_string().replace('\n', "\n              ");
+    println!("  validation    {}", report);
+
+    let (qualities, angles): (Vec<f32>, Vec<f32>) =
+        mesh.triangles().map(|t| triangle_shape(mesh, t)).unzip();
+    println!("  triangle quality (1 for equilateral, 0 for degenerate)");
+    print_histogram(&qualities, 1.0, |low, high| {
+        format!("{:.1}-{:.1}", low, high)
+    });
+    println!("  smallest angle");
+    print_histogram(&angles, 60.0, |low, high| {
+        format!("{:>2}°-{:>2}°", low, high)
+    });
+}
+
+/// Counts for the Euler characteristic of the welded surface.
+struct Topology {
+    vertices: usize,
+    edges: usize,
+    faces: usize,
+    components: usize,
+    boundaries: usize,
+}
+
+impl Topology {
+    fn new(mesh: &HalfEdgeMesh) -> Topology {
+        // Union-find over the vertices, joined along every edge.
+        let mut parent: Vec<u32> = (0..mesh.vertex_count() as u32).collect();
+        fn root(parent: &mut [u32], mut v: u32) -> u32 {
+            while parent[v as usize] != v {
+                parent[v as usize] = parent[parent[v as usize] as usize];
+                v = parent[v as usize];
+            }
+            v
+        }
+        for h in mesh.edges() {
+            let a = root(&mut parent, mesh.origin(h));
+            let b = root(&mut parent, mesh.destination(h));
+            parent[a as usize] = b;
+        }
+        let used: Vec<u32> = (0..mesh.vertex_count() as u32)
+            .filter(|&v| !mesh.outgoing(v).is_empty())
+            .collect();
+        let components = used.iter().filter(|&&v| root(&mut parent, v) == v).count();
+        Topology {
+            vertices: used.len(),
+            edges: mesh.edge_count(),
+            faces: mesh.face_count(),
+            components,
+            boundaries: mesh.boundary_loops().len(),
+        }
+    }
+
+    fn euler_characteristic(&self) -> i64 {
+        self.vertices as i64 - self.edges as i64 + self.faces as i64
+    }
+
+    /// Total genus of the components, from χ = 2C - 2g - B for orientable
+    /// manifold surfaces.
+    fn genus(&self) -> i64 {
+        (2 * self.components as i64 - self.boundaries as i64 - self.euler_characteristic()) / 2
+    }
+}
+
+/// Volume inside the mesh, positive for the clockwise winding of `Mesh`.
+/// Measured about the bounding box center like `winding::detect`, which
+/// keeps the estimate for open meshes steady.
+fn enclosed_volume(mesh: &Mesh) -> f32 {
+    let bounds = mesh.bounds();
+    let center = math::scale(math::add(bounds.min, bounds.max), 0.5);
+    -mesh
+        .triangles()
+        .map(|t| {
+            let [a, b, c] = t.map(|i| math::sub(mesh.position(i), center));
+            math::dot(a, math::cross(b, c)) / 6.0
+        })
+        .sum::<f32>()
+}
+
+/// The quality 4√3·area / Σ edge² and the smallest angle in degrees.
+fn triangle_shape(mesh: &Mesh, [a, b, c]: [u32; 3]) -> (f32, f32) {
+    let p = [a, b, c].map(|i| mesh.position(i));
+    let edges = [0, 1, 2].map(|i| math::sub(p[(i + 1) % 3], p[i]));
+    let squares: f32 = edges.iter().map(|&e| math::dot(e, e)).sum();
+    let area = math::length(mesh.face_normal([a, b, c])) / 2.0;
+    if squares == 0.0 || area == 0.0 {
+        return (0.0, 0.0);
+    }
+    let angle = |u: Vec3, v: Vec3| {
+        let cos = math::dot(math::normalize(u), math::normalize(v));
+        cos.clamp(-1.0, 1.0).acos().to_degrees()
+    };
+    let smallest = (0..3)
+        .map(|i| angle(edges[i], math::scale(edges[(i + 2) % 3], -1.0)))
+        .fold(f32::INFINITY, f32::min);
+    (4.0 * 3f32.sqrt() * area / squares, smallest)
+}
+
+/// Prints `values` from 0 to `max` in `HISTOGRAM_BINS` equal bins, labelled
+/// by `label(low, high)`.
+fn print_histogram(values: &[f32], max: f32, label: impl Fn(f32, f32) -> String) {
+    let mut counts = [0usize; HISTOGRAM_BINS];
+    for &value in values {
+        let bin = (value / max * HISTOGRAM_BINS as f32) as usize;
+        counts[bin.min(HISTOGRAM_BINS - 1)] += 1;
+    }
+    let most = counts.iter().copied().max().unwrap_or(0).max(1);
+    for (bin, &count) in counts.iter().enumerate() {
+        let low = max * bin as f32 / HISTOGRAM_BINS as f32;
+        let high = max * (bin + 1) as f32 / HISTOGRAM_BINS as f32;
+        let bar = (count * BAR_WIDTH).div_ceil(most);
+        println!(
+            "    {} {:<width$} {}",
+            label(low, high),
+            "#".repeat(bar),
+            count,
+            width = BAR_WIDTH
+        );
+    }
+}
+
+fn vector([x, y, z]: Vec3) -> String {
+    format!("[{:.3}, {:.3}, {:.3}]", x, y, z)
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
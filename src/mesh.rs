@@ -0,0 +1,239 @@
+use crate::bounds::{Aabb, Sphere};
+use crate::math::{self, Vec3};
+use crate::meshfile;
+use std::collections::HashMap;
+
+#[derive(Copy, Clone)]
+pub struct Vertex {
+    pub position: (f32, f32, f32),
+}
+
+implement_vertex!(Vertex, position);
+
+#[derive(Copy, Clone)]
+pub struct Normal {
+    pub normal: (f32, f32, f32),
+}
+
+implement_vertex!(Normal, normal);
+
+#[derive(Copy, Clone)]
+pub struct TexCoord {
+    pub tex_coords: (f32, f32),
+}
+
+implement_vertex!(TexCoord, tex_coords);
+
+/// An indexed triangle mesh laid out the same way as the teapot arrays, with
+/// one position, normal and (optional) UV per vertex.
+///
+/// Triangles follow the teapot's winding: clockwise when seen from the front
+/// in world space. Through the left-handed `view_matrix` they appear
+/// counter-clockwise on screen, which is what `CullClockwise` keeps.
+#[derive(Clone, Default)]
+pub struct Mesh {
+    pub positions: Vec<Vertex>,
+    pub normals: Vec<Normal>,
+    pub uvs: Vec<TexCoord>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    /// The teapot of `data/teapot.rs`, which the build script stores in the
+    /// binary `meshfile` format.
+    pub fn teapot() -> Mesh {
+        meshfile::teapot().to_mesh()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn position(&self, index: u32) -> Vec3 {
+        let (x, y, z) = self.positions[index as usize].position;
+        [x, y, z]
+    }
+
+    pub fn normal(&self, index: u32) -> Vec3 {
+        let (x, y, z) = self.normals[index as usize].normal;
+        [x, y, z]
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Aabb::from_points((0..self.positions.len() as u32).map(|i| self.position(i)))
+    }
+
+    pub fn bounding_sphere(&self) -> Sphere {
+        let points: Vec<Vec3> = (0..self.positions.len() as u32)
+            .map(|i| self.position(i))
+            .collect();
+        Sphere::from_points(&points)
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = [u32; 3]> + '_ {
+        self.indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]])
+    }
+
+    /// Front-facing normal of a triangle, with a length of twice its area.
+    pub fn face_normal(&self, [a, b, c]: [u32; 3]) -> Vec3 {
+        let (pa, pb, pc) = (self.position(a), self.position(b), self.position(c));
+        math::cross(math::sub(pc, pa), math::sub(pb, pa))
+    }
+
+    /// Numbers the distinct vertex positions, returning the number of each
+    /// vertex's position and the positions themselves. Vertices split only
+    /// for their normals or UVs share a number.
+    pub fn weld(&self) -> (Vec<u32>, Vec<Vec3>) {
+        let mut lookup = HashMap::new();
+        let mut positions = Vec::new();
+        let welded = (0..self.positions.len() as u32)
+            .map(|i| {
+                let p = self.position(i);
+                // Adding zero folds -0.0 into 0.0 so both hash alike.
+                *lookup
+                    .entry(p.map(|c| (c + 0.0).to_bits()))
+                    .or_insert_with(|| {
+                        positions.push(p);
+                        positions.len() as u32 - 1
+                    })
+            })
+            .collect();
+        (welded, positions)
+    }
+
+    /// A mesh of the triangles in `indices` holding only the vertices they
+    /// use, renumbered in order of first use.
+    pub fn extract(&self, indices: &[u32]) -> Mesh {
+        let mut remap = HashMap::new();
+        let mut out = Mesh::default();
+        for &index in indices {
+            let next = remap.len() as u32;
+            let new = *remap.entry(index).or_insert_with(|| {
+                let i = index as usize;
+                out.positions.push(self.positions[i]);
+                if let Some(normal) = self.normals.get(i) {
+                    out.normals.push(*normal);
+                }
+                if let Some(uv) = self.uvs.get(i) {
+                    out.uvs.push(*uv);
+                }
+                next
+            });
+            out.indices.push(new);
+        }
+        out
+    }
+
+    /// Replaces the normals with area-weighted averages of the adjacent face normals.
+    pub fn compute_normals(&mut self) {
+        let mut normals = vec![[0.0f32; 3]; self.positions.len()];
+
+        for [a, b, c] in self.triangles() {
+            let face = self.face_normal([a, b, c]);
+            for i in [a, b, c] {
+                normals[i as usize] = math::add(normals[i as usize], face);
+            }
+        }
+
+        self.normals = normals
+            .into_iter()
+            .map(|n| {
+                let [x, y, z] = math::normalize(n);
+                Normal { normal: (x, y, z) }
+            })
+            .collect();
+    }
+
+    /// Per-vertex tangents pointing along increasing U, averaged over the
+    /// adjacent triangles and made perpendicular to the normals. `None` for
+    /// meshes without UVs or normals.
+    pub fn tangents(&self) -> Option<Vec<Vec3>> {
+        if self.uvs.len() != self.vertex_count() || self.normals.len() != self.vertex_count() {
+            return None;
+        }
+        let uv = |i: u32| {
+            let (u, v) = self.uvs[i as usize].tex_coords;
+            [u, v]
+        };
+        let mut tangents = vec![[0.0f32; 3]; self.positions.len()];
+
+        for [a, b, c] in self.triangles() {
+            let e1 = math::sub(self.position(b), self.position(a));
+            let e2 = math::sub(self.position(c), self.position(a));
+            let [du1, dv1] = [uv(b)[0] - uv(a)[0], uv(b)[1] - uv(a)[1]];
+            let [du2, dv2] = [uv(c)[0] - uv(a)[0], uv(c)[1] - uv(a)[1]];
+            let det = du1 * dv2 - du2 * dv1;
+            if det.abs() <= f32::EPSILON {
+                continue;
+            }
+            let tangent = math::scale(
+                math::sub(math::scale(e1, dv2), math::scale(e2, dv1)),
+                1.0 / det,
+            );
+            for i in [a, b, c] {
+                tangents[i as usize] = math::add(tangents[i as usize], tangent);
+            }
+        }
+
+        Some(
+            tangents
+                .into_iter()
+                .enumerate()
+                .map(|(i, t)| {
+                    let n = self.normal(i as u32);
+                    math::normalize(math::sub(t, math::scale(n, math::dot(n, t))))
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Incrementally builds a `Mesh` from triangles given counter-clockwise as
+/// seen from their front, the usual convention of geometry code and file
+/// formats, and stores them in the mesh's own winding.
+#[derive(Default)]
+pub struct MeshBuilder {
+    mesh: Mesh,
+}
+
+impl MeshBuilder {
+    pub fn new() -> MeshBuilder {
+        MeshBuilder::default()
+    }
+
+    pub fn vertex(&mut self, position: Vec3, normal: Vec3, uv: [f32; 2]) -> u32 {
+        let [x, y, z] = position;
+        let [nx, ny, nz] = normal;
+        self.mesh.positions.push(Vertex {
+            position: (x, y, z),
+        });
+        self.mesh.normals.push(Normal {
+            normal: (nx, ny, nz),
+        });
+        self.mesh.uvs.push(TexCoord {
+            tex_coords: (uv[0], uv[1]),
+        });
+        self.mesh.positions.len() as u32 - 1
+    }
+
+    pub fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.mesh.indices.extend_from_slice(&[a, c, b]);
+    }
+
+    pub fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.triangle(a, b, c);
+        self.triangle(a, c, d);
+    }
+
+    pub fn vertex_count(&self) -> u32 {
+        self.mesh.positions.len() as u32
+    }
+
+    pub fn build(self) -> Mesh {
+        self.mesh
+    }
+}
@@ -0,0 +1,452 @@
+use crate::bounds::Aabb;
+use crate::mesh::{Mesh, Normal, TexCoord, Vertex};
+use memmap2::Mmap;
+use std::{
+    fs::{self, File},
+    path::{Path, PathBuf},
+};
+
+mod format;
+
+pub use format::Encoding;
+
+/// Keeps `include_bytes!` data aligned for the casts in `MeshFile`.
+#[repr(C, align(16))]
+struct Aligned<T: ?Sized>(T);
+
+static TEAPOT: &Aligned<[u8]> = &Aligned(*include_bytes!(concat!(env!("OUT_DIR"), "/teapot.mesh")));
+
+/// The teapot the build script converts from `data/teapot.rs`.
+pub fn teapot() -> MeshFile<'static> {
+    MeshFile::parse(&TEAPOT.0).expect("failed to parse the built-in teapot!")
+}
+
+pub enum Positions<'a> {
+    Float(&'a [[f32; 3]]),
+    /// Spread across the bounds; see `format::dequantize`.
+    Quantized(&'a [[u16; 3]]),
+}
+
+pub enum Normals<'a> {
+    Float(&'a [[f32; 3]]),
+    /// See `format::octahedral_decode`.
+    Octahedral(&'a [[i16; 2]]),
+}
+
+pub enum Indices<'a> {
+    U16(&'a [u16]),
+    U32(&'a [u32]),
+}
+
+/// A mesh in the binary format, read in place: the streams borrow the file's
+/// bytes without copying, so they can be uploaded as they are.
+pub struct MeshFile<'a> {
+    header: format::Header,
+    bytes: &'a [u8],
+}
+
+impl<'a> MeshFile<'a> {
+    /// Checks the header and that every stream lies inside `bytes`, aligned
+    /// for its type.
+    pub fn parse(bytes: &'a [u8]) -> Result<MeshFile<'a>, String> {
+        if cfg!(target_endian = "big") {
+            return Err("binary meshes can only be read on little-endian machines".to_owned());
+        }
+        let header = format::Header::parse(bytes)?;
+        if header.length as usize != bytes.len() {
+            return Err(format!(
+                "header gives a length of {} bytes, but the file has {}",
+                header.length,
+                bytes.len()
+            ));
+        }
+        if header.positions == 0 || header.indices == 0 {
+            return Err("positions or indices are missing".to_owned());
+        }
+        let offsets = [header.positions, header.normals, header.uvs, header.indices];
+        for (offset, size) in offsets.into_iter().zip(header.stream_sizes()) {
+            let offset = offset as usize;
+            if offset != 0
+                && (!offset.is_multiple_of(4)
+                    || offset < format::HEADER_SIZE
+                    || offset + size > bytes.len())
+            {
+                return Err(format!("stream at {} is out of place", offset));
+            }
+        }
+        let file = MeshFile { header, bytes };
+        if !(file.bytes.as_ptr() as usize).is_multiple_of(4) {
+            return Err("the file's bytes are not 4-byte aligned".to_owned());
+        }
+        let vertex_count = header.vertex_count;
+        let out_of_range = match file.indices() {
+            Indices::U16(indices) => indices.iter().any(|&i| i as u32 >= vertex_count),
+            Indices::U32(indices) => indices.iter().any(|&i| i >= vertex_count),
+        };
+        if out_of_range || !header.index_count.is_multiple_of(3) {
+            return Err("indices do not form triangles of the vertices".to_owned());
+        }
+        Ok(file)
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.header.vertex_count as usize
+    }
+
+    pub fn index_count(&self) -> usize {
+        self.header.index_count as usize
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Aabb {
+            min: self.header.min,
+            max: self.header.max,
+        }
+    }
+
+    /// The bytes of the stream at `offset`, which is stream `index` of
+    /// `Header::stream_sizes`.
+    fn stream(&self, offset: u32, index: usize) -> &'a [u8] {
+        let offset = offset as usize;
+        &self.bytes[offset..offset + self.header.stream_sizes()[index]]
+    }
+
+    pub fn positions(&self) -> Positions<'a> {
+        let bytes = self.stream(self.header.positions, 0);
+        if self.header.flags & format::QUANTIZED_POSITIONS != 0 {
+            Positions::Quantized(bytemuck::cast_slice(bytes))
+        } else {
+            Positions::Float(bytemuck::cast_slice(bytes))
+        }
+    }
+
+    pub fn normals(&self) -> Option<Normals<'a>> {
+        if self.header.normals == 0 {
+            return None;
+        }
+        let bytes = self.stream(self.header.normals, 1);
+        Some(if self.header.flags & format::OCTAHEDRAL_NORMALS != 0 {
+            Normals::Octahedral(bytemuck::cast_slice(bytes))
+        } else {
+            Normals::Float(bytemuck::cast_slice(bytes))
+        })
+    }
+
+    pub fn uvs(&self) -> Option<&'a [[f32; 2]]> {
+        (self.header.uvs != 0).then(|| bytemuck::cast_slice(self.stream(self.header.uvs, 2)))
+    }
+
+    pub fn indices(&self) -> Indices<'a> {
+        let bytes = self.stream(self.header.indices, 3);
+        if self.header.flags & format::SHORT_INDICES != 0 {
+            Indices::U16(bytemuck::cast_slice(bytes))
+        } else {
+            Indices::U32(bytemuck::cast_slice(bytes))
+        }
+    }
+
+    /// Copies the streams into a `Mesh`, decoding any compressed ones.
+    pub fn to_mesh(&self) -> Mesh {
+        let (min, max) = (self.header.min, self.header.max);
+        let positions = match self.positions() {
+            Positions::Float(positions) => positions.to_vec(),
+            Positions::Quantized(positions) => positions
+                .iter()
+                .map(|&q| format::dequantize(q, min, max))
+                .collect(),
+        };
+        let normals = match self.normals() {
+            Some(Normals::Float(normals)) => normals.to_vec(),
+            Some(Normals::Octahedral(normals)) => normals
+                .iter()
+                .map(|&n| format::octahedral_decode(n))
+                .collect(),
+            None => Vec::new(),
+        };
+        Mesh {
+            positions: positions
+                .into_iter()
+                .map(|[x, y, z]| Vertex {
+                    position: (x, y, z),
+                })
+                .collect(),
+            normals: normals
+                .into_iter()
+                .map(|[x, y, z]| Normal { normal: (x, y, z) })
+                .collect(),
+            uvs: self
+                .uvs()
+                .unwrap_or_default()
+                .iter()
+                .map(|&[u, v]| TexCoord { tex_coords: (u, v) })
+                .collect(),
+            indices: match self.indices() {
+                Indices::U16(indices) => indices.iter().map(|&i| i as u32).collect(),
+                Indices::U32(indices) => indices.to_vec(),
+            },
+        }
+    }
+}
+
+/// A binary mesh file mapped into memory, read without copying it.
+pub struct MappedMesh {
+    map: Mmap,
+    /// Parsed and checked once, when the file is mapped.
+    header: format::Header,
+}
+
+impl MappedMesh {
+    pub fn open(path: &Path) -> Result<MappedMesh, String> {
+        let file = File::open(path)
+            .map_err(|err| format!("failed to open {}: {}", path.display(), err))?;
+        // Safety: the map is only read, and meshes are not expected to be
+        // rewritten while the viewer has them open.
+        let map = unsafe { Mmap::map(&file) }
+            .map_err(|err| format!("failed to map {}: {}", path.display(), err))?;
+        let header = MeshFile::parse(&map)
+            .map_err(|err| format!("{}: {}", path.display(), err))?
+            .header;
+        Ok(MappedMesh { map, header })
+    }
+
+    pub fn file(&self) -> MeshFile<'_> {
+        MeshFile {
+            header: self.header,
+            bytes: &self.map,
+        }
+    }
+}
+
+/// Encodes `mesh` into the binary format.
+pub fn write(mesh: &Mesh, encoding: Encoding) -> Vec<u8> {
+    let positions: Vec<[f32; 3]> = (0..mesh.positions.len() as u32)
+        .map(|i| mesh.position(i))
+        .collect();
+    let normals: Vec<[f32; 3]> = (0..mesh.normals.len() as u32)
+        .map(|i| mesh.normal(i))
+        .collect();
+    let uvs: Vec<[f32; 2]> = mesh
+        .uvs
+        .iter()
+        .map(|uv| [uv.tex_coords.0, uv.tex_coords.1])
+        .collect();
+    format::write(
+        &format::Streams {
+            positions: &positions,
+            normals: &normals,
+            uvs: &uvs,
+            indices: &mesh.indices,
+        },
+        encoding,
+    )
+}
+
+pub fn save(mesh: &Mesh, path: &Path, encoding: Encoding) -> Result<(), String> {
+    fs::write(path, write(mesh, encoding))
+        .map_err(|err| format!("failed to write {}: {}", path.display(), err))
+}
+
+pub fn load(path: &Path) -> Result<Mesh, String> {
+    Ok(MappedMesh::open(path)?.file().to_mesh())
+}
+
+/// Loads the mesh at `path` with `load`, going through a lossless binary
+/// copy beside it (`bunny.obj` → `bunny.obj.mesh`) that is rewritten
+/// whenever the source is newer.
+pub fn cached(path: &Path, load: impl Fn(&Path) -> Result<Mesh, String>) -> Result<Mesh, String> {
+    let mut cache = path.as_os_str().to_owned();
+    cache.push(".mesh");
+    let cache = PathBuf::from(cache);
+
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    if let (Some(source), Some(cached)) = (modified(path), modified(&cache)) {
+        if cached >= source {
+            match self::load(&cache) {
+                Ok(mesh) => return Ok(mesh),
+                Err(err) => eprintln!("{}; reloading {}", err, path.display()),
+            }
+        }
+    }
+
+    let mesh = load(path)?;
+    if let Err(err) = save(&mesh, &cache, Encoding::default()) {
+        eprintln!("{}", err);
+    }
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITIONS: [[f32; 3]; 4] = [
+        [-1.0, 0.0, 2.0],
+        [3.0, -0.5, 2.0],
+        [0.25, 4.0, -1.5],
+        [1.0, 1.0, 1.0],
+    ];
+    const NORMALS: [[f32; 3]; 4] = [
+        [0.0, 1.0, 0.0],
+        [0.6, 0.0, -0.8],
+        [-0.48, 0.6, -0.64],
+        [0.0, 0.0, -1.0],
+    ];
+    const UVS: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [0.5, 1.0], [0.3, 0.7]];
+    const INDICES: [u32; 6] = [0, 1, 2, 0, 2, 3];
+
+    fn quad() -> Mesh {
+        Mesh {
+            positions: POSITIONS
+                .iter()
+                .map(|&[x, y, z]| Vertex {
+                    position: (x, y, z),
+                })
+                .collect(),
+            normals: NORMALS
+                .iter()
+                .map(|&[x, y, z]| Normal { normal: (x, y, z) })
+                .collect(),
+            uvs: UVS
+                .iter()
+                .map(|&[u, v]| TexCoord { tex_coords: (u, v) })
+                .collect(),
+            indices: INDICES.to_vec(),
+        }
+    }
+
+    /// `bytes` copied into 4-byte aligned storage, as `parse` needs.
+    fn aligned(bytes: &[u8]) -> Vec<u32> {
+        let mut words = vec![0u32; bytes.len().div_ceil(4)];
+        bytemuck::cast_slice_mut(&mut words)[..bytes.len()].copy_from_slice(bytes);
+        words
+    }
+
+    /// Writes `mesh`, then reads it back through `MeshFile::parse`.
+    fn round_trip(mesh: &Mesh, encoding: Encoding, check: impl Fn(&MeshFile) -> Mesh) -> Mesh {
+        let bytes = write(mesh, encoding);
+        let words = aligned(&bytes);
+        let file = MeshFile::parse(&bytemuck::cast_slice(&words)[..bytes.len()]).unwrap();
+        assert_eq!(file.vertex_count(), mesh.vertex_count());
+        assert_eq!(file.index_count(), mesh.indices.len());
+        let bounds = file.bounds();
+        assert_eq!(
+            (bounds.min, bounds.max),
+            ([-1.0, -0.5, -1.5], [3.0, 4.0, 2.0])
+        );
+        check(&file)
+    }
+
+    fn uvs(mesh: &Mesh) -> Vec<[f32; 2]> {
+        mesh.uvs
+            .iter()
+            .map(|uv| [uv.tex_coords.0, uv.tex_coords.1])
+            .collect()
+    }
+
+    #[test]
+    fn meshes_round_trip() {
+        let mesh = round_trip(&quad(), Encoding::default(), |file| {
+            assert!(matches!(file.positions(), Positions::Float(_)));
+            assert!(matches!(file.normals(), Some(Normals::Float(_))));
+            assert!(matches!(file.indices(), Indices::U16(_)));
+            file.to_mesh()
+        });
+        for i in 0..4 {
+            assert_eq!(mesh.position(i), POSITIONS[i as usize]);
+            assert_eq!(mesh.normal(i), NORMALS[i as usize]);
+        }
+        assert_eq!(uvs(&mesh), UVS);
+        assert_eq!(mesh.indices, INDICES);
+    }
+
+    /// What `rusty_glad convert --compress` writes.
+    #[test]
+    fn compressed_meshes_round_trip() {
+        let encoding = Encoding {
+            quantize_positions: true,
+            octahedral_normals: true,
+        };
+        let mesh = round_trip(&quad(), encoding, |file| {
+            assert!(matches!(file.positions(), Positions::Quantized(_)));
+            assert!(matches!(file.normals(), Some(Normals::Octahedral(_))));
+            file.to_mesh()
+        });
+        // Half a quantization step per axis, plus rounding in the arithmetic.
+        let step = [4.0, 4.5, 3.5].map(|extent| extent / 65535.0);
+        for i in 0..4 {
+            let (p, q) = (POSITIONS[i as usize], mesh.position(i));
+            for axis in 0..3 {
+                assert!(
+                    (p[axis] - q[axis]).abs() <= step[axis] / 2.0 + 1e-6,
+                    "{:?} {:?}",
+                    p,
+                    q
+                );
+            }
+            // Octahedral snorm16 keeps normals to within a tenth of a degree.
+            let (n, m) = (NORMALS[i as usize], mesh.normal(i));
+            let dot: f32 = (0..3).map(|axis| n[axis] * m[axis]).sum();
+            assert!(dot > 1.0 - 1.5e-6, "{:?} {:?}", n, m);
+        }
+        assert_eq!(uvs(&mesh), UVS);
+        assert_eq!(mesh.indices, INDICES);
+    }
+
+    #[test]
+    fn missing_streams_stay_missing() {
+        let mut quad = quad();
+        quad.normals.clear();
+        quad.uvs.clear();
+        let mesh = round_trip(&quad, Encoding::default(), |file| {
+            assert!(file.normals().is_none() && file.uvs().is_none());
+            file.to_mesh()
+        });
+        assert!(mesh.normals.is_empty() && mesh.uvs.is_empty());
+        assert_eq!(mesh.indices, INDICES);
+    }
+
+    #[test]
+    fn large_meshes_use_long_indices() {
+        let plane = crate::primitives::plane(1.0, 1.0, 256, 256);
+        assert!(plane.vertex_count() > 65536);
+        let bytes = write(&plane, Encoding::default());
+        let words = aligned(&bytes);
+        let file = MeshFile::parse(&bytemuck::cast_slice(&words)[..bytes.len()]).unwrap();
+        assert!(matches!(file.indices(), Indices::U32(_)));
+        assert_eq!(file.to_mesh().indices, plane.indices);
+    }
+
+    #[test]
+    fn broken_files_are_refused() {
+        let bytes = write(&quad(), Encoding::default());
+        let words = aligned(&bytes);
+        let bytes: &[u8] = &bytemuck::cast_slice(&words)[..bytes.len()];
+        assert!(MeshFile::parse(&bytes[..bytes.len() - 4]).is_err());
+        assert!(MeshFile::parse(&bytes[..format::HEADER_SIZE / 2]).is_err());
+
+        let mut out_of_range = quad();
+        out_of_range.indices[5] = 4;
+        let bytes = write(&out_of_range, Encoding::default());
+        let words = aligned(&bytes);
+        assert!(MeshFile::parse(&bytemuck::cast_slice(&words)[..bytes.len()]).is_err());
+    }
+
+    #[test]
+    fn mapped_meshes_read_saved_files() {
+        let path =
+            std::env::temp_dir().join(format!("rusty_glad_mapped_{}.mesh", std::process::id()));
+        save(&quad(), &path, Encoding::default()).unwrap();
+        let mapped = MappedMesh::open(&path).unwrap();
+        for _ in 0..2 {
+            let file = mapped.file();
+            assert_eq!((file.vertex_count(), file.index_count()), (4, 6));
+        }
+        let mesh = load(&path).unwrap();
+        assert_eq!(uvs(&mesh), UVS);
+        assert_eq!(mesh.indices, INDICES);
+
+        fs::write(&path, b"not a mesh").unwrap();
+        assert!(MappedMesh::open(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,291 @@
+//! Layout of the binary mesh format, kept free of crate dependencies so the
+//! build script can write files with it too.
+//!
+//! A file is a 64-byte header followed by the attribute streams and the
+//! indices, each starting on a 4-byte boundary. All values are little-endian.
+//!
+//! | offset | contents                                           |
+//! |--------|----------------------------------------------------|
+//! | 0      | `MAGIC`                                            |
+//! | 4      | `VERSION`: u16, then the `flags`: u16              |
+//! | 8      | vertex count, index count: u32                     |
+//! | 16     | bounds minimum, then maximum: 3 × f32 each         |
+//! | 40     | offsets of positions, normals, UVs, indices: u32   |
+//! | 56     | total file length: u32, then 4 reserved bytes      |
+//!
+//! Streams a mesh lacks have offset 0. Positions are 3 × f32, or 3 × u16
+//! across the bounds with `QUANTIZED_POSITIONS`; normals are 3 × f32, or
+//! 2 × i16 octahedral coordinates with `OCTAHEDRAL_NORMALS`; UVs are 2 × f32;
+//! indices are u32, or u16 with `SHORT_INDICES`.
+
+pub const MAGIC: [u8; 4] = *b"RGMF";
+pub const VERSION: u16 = 1;
+pub const HEADER_SIZE: usize = 64;
+
+pub const QUANTIZED_POSITIONS: u16 = 1;
+pub const OCTAHEDRAL_NORMALS: u16 = 2;
+pub const SHORT_INDICES: u16 = 4;
+
+/// Vertex data to write, one entry per vertex in each non-empty stream.
+pub struct Streams<'a> {
+    pub positions: &'a [[f32; 3]],
+    pub normals: &'a [[f32; 3]],
+    pub uvs: &'a [[f32; 2]],
+    pub indices: &'a [u32],
+}
+
+/// Lossy encodings that trade precision for size. Indices are always stored
+/// as u16 when every vertex fits, which loses nothing.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Encoding {
+    /// Stores positions in 16 bits per component across the bounds.
+    pub quantize_positions: bool,
+    /// Stores normals as two 16-bit octahedral coordinates.
+    pub octahedral_normals: bool,
+}
+
+/// The fixed-size start of a file.
+#[derive(Copy, Clone, Debug)]
+pub struct Header {
+    pub flags: u16,
+    pub vertex_count: u32,
+    pub index_count: u32,
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+    pub positions: u32,
+    pub normals: u32,
+    pub uvs: u32,
+    pub indices: u32,
+    pub length: u32,
+}
+
+impl Header {
+    /// Reads a header, checking only the magic and version.
+    pub fn parse(bytes: &[u8]) -> Result<Header, String> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(format!("{} bytes is too short for a header", bytes.len()));
+        }
+        if bytes[0..4] != MAGIC {
+            return Err("not a binary mesh file".to_owned());
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version != VERSION {
+            return Err(format!(
+                "unsupported version {} (expected {})",
+                version, VERSION
+            ));
+        }
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        let vec3_at = |offset: usize| [0, 1, 2].map(|i| f32::from_bits(u32_at(offset + 4 * i)));
+        Ok(Header {
+            flags: u16::from_le_bytes([bytes[6], bytes[7]]),
+            vertex_count: u32_at(8),
+            index_count: u32_at(12),
+            min: vec3_at(16),
+            max: vec3_at(28),
+            positions: u32_at(40),
+            normals: u32_at(44),
+            uvs: u32_at(48),
+            indices: u32_at(52),
+            length: u32_at(56),
+        })
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.flags.to_le_bytes());
+        for value in [self.vertex_count, self.index_count] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in self.min.iter().chain(&self.max) {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [
+            self.positions,
+            self.normals,
+            self.uvs,
+            self.indices,
+            self.length,
+            0,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    /// Byte sizes of the position, normal, UV and index streams.
+    pub fn stream_sizes(&self) -> [usize; 4] {
+        let vertices = self.vertex_count as usize;
+        let flag = |flag: u16, set: usize, unset: usize| {
+            if self.flags & flag != 0 {
+                set
+            } else {
+                unset
+            }
+        };
+        let present = |offset: u32, size: usize| if offset != 0 { size } else { 0 };
+        [
+            present(self.positions, vertices * flag(QUANTIZED_POSITIONS, 6, 12)),
+            present(self.normals, vertices * flag(OCTAHEDRAL_NORMALS, 4, 12)),
+            present(self.uvs, vertices * 8),
+            present(
+                self.indices,
+                self.index_count as usize * flag(SHORT_INDICES, 2, 4),
+            ),
+        ]
+    }
+}
+
+/// Encodes a mesh into a complete file.
+pub fn write(streams: &Streams, encoding: Encoding) -> Vec<u8> {
+    let vertex_count = streams.positions.len();
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for p in streams.positions {
+        min = [0, 1, 2].map(|i| min[i].min(p[i]));
+        max = [0, 1, 2].map(|i| max[i].max(p[i]));
+    }
+    if vertex_count == 0 {
+        (min, max) = ([0.0; 3], [0.0; 3]);
+    }
+
+    let mut flags = 0;
+    if encoding.quantize_positions {
+        flags |= QUANTIZED_POSITIONS;
+    }
+    if encoding.octahedral_normals && !streams.normals.is_empty() {
+        flags |= OCTAHEDRAL_NORMALS;
+    }
+    if vertex_count <= 1 << 16 {
+        flags |= SHORT_INDICES;
+    }
+
+    let mut body = Vec::new();
+    let mut stream = |write: &mut dyn FnMut(&mut Vec<u8>)| {
+        let offset = (HEADER_SIZE + body.len()) as u32;
+        write(&mut body);
+        body.resize(body.len().next_multiple_of(4), 0);
+        offset
+    };
+    let positions = stream(&mut |out| {
+        for p in streams.positions {
+            if flags & QUANTIZED_POSITIONS != 0 {
+                for v in quantize(*p, min, max) {
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+            } else {
+                for v in p {
+                    out.extend_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+    });
+    let normals = if streams.normals.is_empty() {
+        0
+    } else {
+        stream(&mut |out| {
+            for n in streams.normals {
+                if flags & OCTAHEDRAL_NORMALS != 0 {
+                    for v in octahedral_encode(*n) {
+                        out.extend_from_slice(&v.to_le_bytes());
+                    }
+                } else {
+                    for v in n {
+                        out.extend_from_slice(&v.to_le_bytes());
+                    }
+                }
+            }
+        })
+    };
+    let uvs = if streams.uvs.is_empty() {
+        0
+    } else {
+        stream(&mut |out| {
+            for v in streams.uvs.iter().flatten() {
+                out.extend_from_slice(&v.to_le_bytes());
+            }
+        })
+    };
+    let indices = stream(&mut |out| {
+        for &i in streams.indices {
+            if flags & SHORT_INDICES != 0 {
+                out.extend_from_slice(&(i as u16).to_le_bytes());
+            } else {
+                out.extend_from_slice(&i.to_le_bytes());
+            }
+        }
+    });
+
+    let header = Header {
+        flags,
+        vertex_count: vertex_count as u32,
+        index_count: streams.indices.len() as u32,
+        min,
+        max,
+        positions,
+        normals,
+        uvs,
+        indices,
+        length: (HEADER_SIZE + body.len()) as u32,
+    };
+    let mut out = Vec::with_capacity(header.length as usize);
+    header.write(&mut out);
+    out.extend_from_slice(&body);
+    out
+}
+
+/// Maps `p` within the bounds onto the full range of u16.
+pub fn quantize(p: [f32; 3], min: [f32; 3], max: [f32; 3]) -> [u16; 3] {
+    [0, 1, 2].map(|i| {
+        let extent = max[i] - min[i];
+        if extent > 0.0 {
+            ((p[i] - min[i]) / extent * 65535.0).round() as u16
+        } else {
+            0
+        }
+    })
+}
+
+pub fn dequantize(q: [u16; 3], min: [f32; 3], max: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|i| min[i] + (max[i] - min[i]) * (q[i] as f32 / 65535.0))
+}
+
+/// Projects a unit vector onto the octahedron |x| + |y| + |z| = 1 and unfolds
+/// it into the unit square, stored as two snorm16 values.
+pub fn octahedral_encode([x, y, z]: [f32; 3]) -> [i16; 2] {
+    let l1 = x.abs() + y.abs() + z.abs();
+    if l1 == 0.0 {
+        return [0, 0];
+    }
+    let (mut u, mut v) = (x / l1, y / l1);
+    if z < 0.0 {
+        (u, v) = ((1.0 - v.abs()) * sign(u), (1.0 - u.abs()) * sign(v));
+    }
+    [u, v].map(|c| (c.clamp(-1.0, 1.0) * 32767.0).round() as i16)
+}
+
+pub fn octahedral_decode(encoded: [i16; 2]) -> [f32; 3] {
+    let [mut x, mut y] = encoded.map(|c| (c as f32 / 32767.0).max(-1.0));
+    let z = 1.0 - x.abs() - y.abs();
+    if z < 0.0 {
+        (x, y) = ((1.0 - y.abs()) * sign(x), (1.0 - x.abs()) * sign(y));
+    }
+    let length = (x * x + y * y + z * z).sqrt();
+    [x / length, y / length, z / length]
+}
+
+/// Like `f32::signum`, but without NaN and with +1 for both zeroes.
+fn sign(value: f32) -> f32 {
+    if value < 0.0 {
+        -1.0
+    } else {
+        1.0
+    }
+}
@@ -0,0 +1,502 @@
+use crate::math::{self, Vec3};
+use crate::mesh::{Mesh, MeshBuilder};
+use crate::triangulate::triangulate;
+use serde::{Deserialize, Serialize};
+
+/// Profile corners turning by more than this many degrees get split normals.
+const CREASE_DEGREES: f32 = 30.0;
+
+/// A mesh built from a 2D profile, as referenced from scene files.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Shape {
+    /// `profile` points are `(radius, height)`; see `lathe`.
+    Lathe {
+        profile: Vec<[f32; 2]>,
+        segments: u32,
+        #[serde(default = "full_turn")]
+        degrees: f32,
+    },
+    Extrude {
+        outline: Vec<[f32; 2]>,
+        #[serde(default)]
+        holes: Vec<Vec<[f32; 2]>>,
+        direction: Vec3,
+    },
+    Sweep {
+        profile: Vec<[f32; 2]>,
+        path: Vec<Vec3>,
+        #[serde(default)]
+        options: SweepOptions,
+    },
+}
+
+fn full_turn() -> f32 {
+    360.0
+}
+
+impl Shape {
+    pub fn build(&self) -> Result<Mesh, String> {
+        match self {
+            Shape::Lathe {
+                profile,
+                segments,
+                degrees,
+            } => lathe(profile, *segments, *degrees),
+            Shape::Extrude {
+                outline,
+                holes,
+                direction,
+            } => extrude(outline, holes, *direction),
+            Shape::Sweep {
+                profile,
+                path,
+                options,
+            } => sweep(profile, path, options),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SweepOptions {
+    /// Spline samples between consecutive path points.
+    #[serde(default = "default_samples")]
+    pub samples: u32,
+    /// Rotation of the profile at the end of the path, in degrees, spread
+    /// evenly along its length.
+    #[serde(default)]
+    pub twist: f32,
+    /// Profile scale at the start and end of the path.
+    #[serde(default = "default_scale")]
+    pub scale: [f32; 2],
+    /// Joins the last profile point back to the first.
+    #[serde(default = "default_true")]
+    pub closed: bool,
+    /// Closes both ends of a closed profile with flat caps.
+    #[serde(default = "default_true")]
+    pub caps: bool,
+}
+
+fn default_samples() -> u32 {
+    8
+}
+
+fn default_scale() -> [f32; 2] {
+    [1.0, 1.0]
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for SweepOptions {
+    fn default() -> SweepOptions {
+        SweepOptions {
+            samples: default_samples(),
+            twist: 0.0,
+            scale: default_scale(),
+            closed: true,
+            caps: true,
+        }
+    }
+}
+
+/// A vertex of a profile with its outward 2D normal and its distance along
+/// the profile. Corners sharper than `CREASE_DEGREES` appear twice, once
+/// with the normal of each edge.
+struct ProfilePoint {
+    position: [f32; 2],
+    normal: [f32; 2],
+    distance: f32,
+}
+
+/// A profile ready to be swept: its points and the pairs of them joined by
+/// each edge.
+struct Profile {
+    points: Vec<ProfilePoint>,
+    spans: Vec<(usize, usize)>,
+}
+
+/// Splits `profile` into points and the spans joining them. The outward side
+/// is to the right of the direction of travel, so counter-clockwise loops
+/// face out.
+fn profile_points(profile: &[[f32; 2]], closed: bool) -> Result<Profile, String> {
+    let mut profile = profile.to_vec();
+    profile.dedup();
+    if closed && profile.len() > 1 && profile.first() == profile.last() {
+        profile.pop();
+    }
+    if profile.len() < if closed { 3 } else { 2 } {
+        return Err("profile needs more distinct points".to_string());
+    }
+
+    let count = profile.len();
+    let edges = if closed { count } else { count - 1 };
+    let edge_normals: Vec<[f32; 2]> = (0..edges)
+        .map(|e| {
+            let (a, b) = (profile[e], profile[(e + 1) % count]);
+            let [x, y, _] = math::normalize([b[0] - a[0], b[1] - a[1], 0.0]);
+            [y, -x]
+        })
+        .collect();
+
+    // The normal shared by both edges at vertex `v`, if it is smooth there.
+    let smooth = |v: usize| -> Option<[f32; 2]> {
+        if !closed && (v == 0 || v == count - 1) {
+            return None;
+        }
+        let (before, after) = (
+            edge_normals[(v + edges - 1) % edges],
+            edge_normals[v % edges],
+        );
+        let cos = before[0] * after[0] + before[1] * after[1];
+        if cos < CREASE_DEGREES.to_radians().cos() {
+            return None;
+        }
+        let [x, y, _] = math::normalize([before[0] + after[0], before[1] + after[1], 0.0]);
+        Some([x, y])
+    };
+
+    let mut points: Vec<ProfilePoint> = Vec::new();
+    let mut spans = Vec::with_capacity(edges);
+    let mut distance = 0.0;
+    let mut shared = None;
+
+    for (e, &edge_normal) in edge_normals.iter().enumerate() {
+        let (a, b) = (profile[e], profile[(e + 1) % count]);
+        let start = match shared {
+            Some(index) => index,
+            None => {
+                points.push(ProfilePoint {
+                    position: a,
+                    normal: smooth(e).unwrap_or(edge_normal),
+                    distance,
+                });
+                points.len() - 1
+            }
+        };
+
+        distance += math::length([b[0] - a[0], b[1] - a[1], 0.0]);
+        let end_normal = smooth(e + 1);
+        points.push(ProfilePoint {
+            position: b,
+            normal: end_normal.unwrap_or(edge_normal),
+            distance,
+        });
+        spans.push((start, points.len() - 1));
+        shared = end_normal.map(|_| points.len() - 1);
+    }
+
+    Ok(Profile { points, spans })
+}
+
+/// Joins each pair of consecutive rows along every profile span into quads.
+/// Vertex `row * stride + point` belongs to profile point `point`; the
+/// surface faces the side of `span direction × row direction`. Collapsed
+/// triangles (where a profile touches the lathe axis) are left out.
+fn connect(
+    builder: &mut MeshBuilder,
+    positions: &[Vec3],
+    base: u32,
+    stride: usize,
+    spans: &[(usize, usize)],
+) {
+    let collapsed = |corners: [usize; 3]| {
+        let [pa, pb, pc] = corners.map(|i| positions[i]);
+        pa == pb || pb == pc || pc == pa
+    };
+
+    for row in 0..positions.len() / stride - 1 {
+        for &(start, end) in spans {
+            let a = row * stride + start;
+            let (b, c, d) = (row * stride + end, a + stride - start + end, a + stride);
+            for corners in [[a, b, c], [a, c, d]] {
+                if !collapsed(corners) {
+                    let [x, y, z] = corners.map(|i| base + i as u32);
+                    builder.triangle(x, y, z);
+                }
+            }
+        }
+    }
+}
+
+/// Texture coordinates mapping the bounds of `points` onto the unit square.
+fn planar_uvs(points: &[[f32; 2]]) -> Vec<[f32; 2]> {
+    let (min, max) = points
+        .iter()
+        .fold(([f32::MAX; 2], [f32::MIN; 2]), |(min, max), p| {
+            (
+                [min[0].min(p[0]), min[1].min(p[1])],
+                [max[0].max(p[0]), max[1].max(p[1])],
+            )
+        });
+    let size = [0, 1].map(|k| (max[k] - min[k]).max(f32::EPSILON));
+    points
+        .iter()
+        .map(|p| [(p[0] - min[0]) / size[0], (p[1] - min[1]) / size[1]])
+        .collect()
+}
+
+/// Revolves `profile`, given as `(radius, height)` points, by `degrees`
+/// around the Y axis in `segments` steps. Profiles running upwards face
+/// away from the axis. Points on the axis close the surface there; partial
+/// turns are left open.
+pub fn lathe(profile: &[[f32; 2]], segments: u32, degrees: f32) -> Result<Mesh, String> {
+    let Profile { points, spans } = profile_points(profile, false)?;
+    let segments = segments.max(3) as usize;
+    let length = points.last().map_or(1.0, |p| p.distance).max(f32::EPSILON);
+    let mut builder = MeshBuilder::new();
+    let mut positions = Vec::with_capacity((segments + 1) * points.len());
+
+    for i in 0..=segments {
+        let u = i as f32 / segments as f32;
+        // Close full turns exactly, so the seam welds.
+        let angle = if i == segments && degrees.rem_euclid(360.0) == 0.0 {
+            0.0
+        } else {
+            degrees.to_radians() * u
+        };
+        let (sin, cos) = angle.sin_cos();
+        for point in &points {
+            let ([r, h], [nr, nh]) = (point.position, point.normal);
+            let position = [r * cos, h, r * sin];
+            builder.vertex(
+                position,
+                [nr * cos, nh, nr * sin],
+                [u, point.distance / length],
+            );
+            positions.push(position);
+        }
+    }
+
+    connect(&mut builder, &positions, 0, points.len(), &spans);
+    Ok(builder.build())
+}
+
+/// Extrudes a polygon in the XY plane along `direction`, capping both ends.
+/// `holes` are cut through the whole solid. Rings may be given in either
+/// winding.
+pub fn extrude(
+    outline: &[[f32; 2]],
+    holes: &[Vec<[f32; 2]>],
+    direction: Vec3,
+) -> Result<Mesh, String> {
+    if direction[2] == 0.0 {
+        return Err("extrusion direction lies in the polygon's plane".to_string());
+    }
+    let triangles = triangulate(outline, holes)?;
+    let front = direction[2] > 0.0;
+    let mut builder = MeshBuilder::new();
+
+    // Sides: the outline runs counter-clockwise seen from the tip of
+    // `direction` and the holes clockwise, so every edge faces out of the solid.
+    for (i, ring) in std::iter::once(outline)
+        .chain(holes.iter().map(Vec::as_slice))
+        .enumerate()
+    {
+        let mut ring = ring.to_vec();
+        let counter_clockwise = signed_area(&ring) > 0.0;
+        if counter_clockwise != ((i == 0) == front) {
+            ring.reverse();
+        }
+
+        let Profile { points, spans } = profile_points(&ring, true)?;
+        let length = points.last().map_or(1.0, |p| p.distance).max(f32::EPSILON);
+        let base = builder.vertex_count();
+        let mut positions = Vec::with_capacity(points.len() * 2);
+        for v in [0.0, 1.0] {
+            for point in &points {
+                let ([x, y], [nx, ny]) = (point.position, point.normal);
+                let position = math::add([x, y, 0.0], math::scale(direction, v));
+                let normal = math::normalize(math::cross([-ny, nx, 0.0], direction));
+                builder.vertex(position, normal, [point.distance / length, v]);
+                positions.push(position);
+            }
+        }
+        connect(&mut builder, &positions, base, points.len(), &spans);
+    }
+
+    // Caps, with UVs spanning the outline's bounds.
+    let points: Vec<[f32; 2]> = outline
+        .iter()
+        .chain(holes.iter().flatten())
+        .copied()
+        .collect();
+    let uvs = planar_uvs(&points);
+    let z = if front { 1.0 } else { -1.0 };
+
+    for (offset, normal) in [([0.0; 3], [0.0, 0.0, -z]), (direction, [0.0, 0.0, z])] {
+        let base = builder.vertex_count();
+        for (&[x, y], &uv) in points.iter().zip(&uvs) {
+            builder.vertex(math::add([x, y, 0.0], offset), normal, uv);
+        }
+        // `triangulate` yields counter-clockwise triangles seen from +Z.
+        let facing_z = normal[2] > 0.0;
+        for [a, b, c] in &triangles {
+            if facing_z {
+                builder.triangle(base + a, base + b, base + c);
+            } else {
+                builder.triangle(base + a, base + c, base + b);
+            }
+        }
+    }
+
+    Ok(builder.build())
+}
+
+fn signed_area(ring: &[[f32; 2]]) -> f32 {
+    let mut sum = 0.0;
+    for (i, a) in ring.iter().enumerate() {
+        let b = ring[(i + 1) % ring.len()];
+        sum += a[0] * b[1] - b[0] * a[1];
+    }
+    sum / 2.0
+}
+
+/// Samples a Catmull-Rom spline through `path`, `samples` steps per segment.
+fn spline(path: &[Vec3], samples: u32) -> Vec<Vec3> {
+    let last = path.len() - 1;
+    let point = |i: isize| -> Vec3 {
+        if i < 0 {
+            math::sub(math::scale(path[0], 2.0), path[1])
+        } else if i as usize > last {
+            math::sub(math::scale(path[last], 2.0), path[last - 1])
+        } else {
+            path[i as usize]
+        }
+    };
+
+    let mut out = Vec::with_capacity(last * samples as usize + 1);
+    for segment in 0..last as isize {
+        let [p0, p1, p2, p3] = [segment - 1, segment, segment + 1, segment + 2].map(point);
+        for step in 0..samples {
+            let t = step as f32 / samples as f32;
+            let (t2, t3) = (t * t, t * t * t);
+            let weights = [
+                -t3 + 2.0 * t2 - t,
+                3.0 * t3 - 5.0 * t2 + 2.0,
+                -3.0 * t3 + 4.0 * t2 + t,
+                t3 - t2,
+            ];
+            let mut p = [0.0; 3];
+            for (q, w) in [p0, p1, p2, p3].into_iter().zip(weights) {
+                p = math::add(p, math::scale(q, w / 2.0));
+            }
+            out.push(p);
+        }
+    }
+    out.push(path[last]);
+    out.dedup();
+    out
+}
+
+/// Sweeps `profile` along a Catmull-Rom spline through `path`. The profile's
+/// x and y axes follow a rotation-minimising frame around the path tangent,
+/// so a counter-clockwise profile faces out of the tube.
+pub fn sweep(profile: &[[f32; 2]], path: &[Vec3], options: &SweepOptions) -> Result<Mesh, String> {
+    if path.len() < 2 {
+        return Err("sweep path needs at least 2 points".to_string());
+    }
+    let Profile { points, spans } = profile_points(profile, options.closed)?;
+    let samples = spline(path, options.samples.max(1));
+    if samples.len() < 2 {
+        return Err("sweep path has no length".to_string());
+    }
+
+    let count = samples.len();
+    let tangents: Vec<Vec3> = (0..count)
+        .map(|i| {
+            math::normalize(math::sub(
+                samples[(i + 1).min(count - 1)],
+                samples[i.saturating_sub(1)],
+            ))
+        })
+        .collect();
+    let mut distances = vec![0.0; count];
+    for i in 1..count {
+        distances[i] = distances[i - 1] + math::length(math::sub(samples[i], samples[i - 1]));
+    }
+    let length = distances[count - 1];
+
+    // Rotation-minimising frames by double reflection (Wang et al. 2008).
+    let t0 = tangents[0];
+    let axis = if t0[1].abs() < 0.9 {
+        [0.0, 1.0, 0.0]
+    } else {
+        [1.0, 0.0, 0.0]
+    };
+    let mut normals = vec![math::normalize(math::sub(
+        axis,
+        math::scale(t0, math::dot(axis, t0)),
+    ))];
+    let reflect = |v: Vec3, axis: Vec3| {
+        let c = math::dot(axis, axis);
+        if c < 1e-12 {
+            v
+        } else {
+            math::sub(v, math::scale(axis, 2.0 / c * math::dot(axis, v)))
+        }
+    };
+    for i in 0..count - 1 {
+        let v1 = math::sub(samples[i + 1], samples[i]);
+        let normal = reflect(normals[i], v1);
+        let tangent = reflect(tangents[i], v1);
+        normals.push(reflect(normal, math::sub(tangents[i + 1], tangent)));
+    }
+
+    let profile_length = points.last().map_or(1.0, |p| p.distance).max(f32::EPSILON);
+    let mut builder = MeshBuilder::new();
+    let mut positions = Vec::with_capacity(count * points.len());
+    let mut frames = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let s = if length > 0.0 {
+            distances[i] / length
+        } else {
+            0.0
+        };
+        let (sin, cos) = (options.twist.to_radians() * s).sin_cos();
+        let scale = options.scale[0] + (options.scale[1] - options.scale[0]) * s;
+        let binormal = math::cross(tangents[i], normals[i]);
+        // Profile x and y axes after twisting.
+        let x_axis = math::add(math::scale(normals[i], cos), math::scale(binormal, sin));
+        let y_axis = math::sub(math::scale(binormal, cos), math::scale(normals[i], sin));
+        let place =
+            move |[x, y]: [f32; 2]| math::add(math::scale(x_axis, x), math::scale(y_axis, y));
+
+        for point in &points {
+            let position = math::add(samples[i], math::scale(place(point.position), scale));
+            let normal = math::normalize(place(point.normal));
+            builder.vertex(position, normal, [point.distance / profile_length, s]);
+            positions.push(position);
+        }
+        frames.push((place, scale));
+    }
+
+    connect(&mut builder, &positions, 0, points.len(), &spans);
+
+    if options.closed && options.caps {
+        let triangles = triangulate(profile, &[])?;
+        let uvs = planar_uvs(profile);
+        let ends = [
+            (0, math::scale(tangents[0], -1.0)),
+            (count - 1, tangents[count - 1]),
+        ];
+        for (index, (i, normal)) in ends.into_iter().enumerate() {
+            let (place, scale) = &frames[i];
+            let base = builder.vertex_count();
+            for (&p, &uv) in profile.iter().zip(&uvs) {
+                let position = math::add(samples[i], math::scale(place(p), *scale));
+                builder.vertex(position, normal, uv);
+            }
+            for &[a, b, c] in &triangles {
+                if index == 0 {
+                    builder.triangle(base + a, base + c, base + b);
+                } else {
+                    builder.triangle(base + a, base + b, base + c);
+                }
+            }
+        }
+    }
+
+    Ok(builder.build())
+}
@@ -0,0 +1,171 @@
+use crate::mesh::{Mesh, Normal, TexCoord, Vertex};
+use std::{collections::HashMap, fmt::Write, fs, path::Path};
+
+/// Loads a Wavefront OBJ file, fan-triangulating polygons and merging the
+/// separate position/uv/normal index streams into a single vertex index.
+pub fn load(path: &Path) -> Result<Mesh, String> {
+    let source = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+
+    parse(&source).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+pub fn save(mesh: &Mesh, path: &Path) -> Result<(), String> {
+    fs::write(path, write(mesh))
+        .map_err(|err| format!("failed to write {}: {}", path.display(), err))
+}
+
+/// Writes `mesh` as OBJ text with one `v`, `vt` and `vn` per vertex, the
+/// latter two only when the mesh has them.
+pub fn write(mesh: &Mesh) -> String {
+    let has_normals = mesh.normals.len() == mesh.positions.len();
+    let has_uvs = mesh.uvs.len() == mesh.positions.len();
+    let mut out = String::new();
+    for vertex in &mesh.positions {
+        let (x, y, z) = vertex.position;
+        writeln!(out, "v {} {} {}", x, y, z).unwrap();
+    }
+    if has_uvs {
+        for uv in &mesh.uvs {
+            let (u, v) = uv.tex_coords;
+            writeln!(out, "vt {} {}", u, v).unwrap();
+        }
+    }
+    if has_normals {
+        for normal in &mesh.normals {
+            let (x, y, z) = normal.normal;
+            writeln!(out, "vn {} {} {}", x, y, z).unwrap();
+        }
+    }
+    for [a, b, c] in mesh.triangles() {
+        // Back to the counter-clockwise order OBJ faces use.
+        out.push('f');
+        for index in [a, c, b] {
+            let i = index + 1;
+            match (has_uvs, has_normals) {
+                (false, false) => write!(out, " {}", i),
+                (true, false) => write!(out, " {}/{}", i, i),
+                (false, true) => write!(out, " {}//{}", i, i),
+                (true, true) => write!(out, " {}/{}/{}", i, i, i),
+            }
+            .unwrap();
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub fn parse(source: &str) -> Result<Mesh, String> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut uvs: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+
+    let mut mesh = Mesh::default();
+    let mut has_uvs = false;
+    let mut has_normals = false;
+    let mut vertices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+
+    for (line_number, line) in source.lines().enumerate() {
+        let line_number = line_number + 1;
+        let mut parts = line.split_whitespace();
+
+        match parts.next() {
+            Some("v") => positions.push(parse_floats(parts, line_number)?),
+            Some("vt") => uvs.push(parse_floats(parts, line_number)?),
+            Some("vn") => normals.push(parse_floats(parts, line_number)?),
+            Some("f") => {
+                let mut face = Vec::new();
+                for corner in parts {
+                    let mut refs = corner.split('/');
+                    let position = resolve(refs.next(), positions.len(), line_number)?
+                        .ok_or_else(|| format!("line {}: face without position", line_number))?;
+                    let uv = resolve(refs.next(), uvs.len(), line_number)?;
+                    let normal = resolve(refs.next(), normals.len(), line_number)?;
+                    has_uvs |= uv.is_some();
+                    has_normals |= normal.is_some();
+
+                    let index = *vertices.entry((position, uv, normal)).or_insert_with(|| {
+                        let [x, y, z] = positions[position];
+                        let [u, v] = uv.map_or([0.0, 0.0], |i| uvs[i]);
+                        let [nx, ny, nz] = normal.map_or([0.0, 0.0, 0.0], |i| normals[i]);
+                        mesh.positions.push(Vertex {
+                            position: (x, y, z),
+                        });
+                        mesh.uvs.push(TexCoord { tex_coords: (u, v) });
+                        mesh.normals.push(Normal {
+                            normal: (nx, ny, nz),
+                        });
+                        mesh.positions.len() as u32 - 1
+                    });
+                    face.push(index);
+                }
+
+                if face.len() < 3 {
+                    return Err(format!(
+                        "line {}: face with fewer than 3 vertices",
+                        line_number
+                    ));
+                }
+                // OBJ faces are counter-clockwise; store them in the mesh winding.
+                for i in 1..face.len() - 1 {
+                    mesh.indices
+                        .extend_from_slice(&[face[0], face[i + 1], face[i]]);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if !has_uvs {
+        mesh.uvs.clear();
+    }
+    if !has_normals {
+        mesh.compute_normals();
+    }
+
+    Ok(mesh)
+}
+
+fn parse_floats<'a, const N: usize>(
+    parts: impl Iterator<Item = &'a str>,
+    line_number: usize,
+) -> Result<[f32; N], String> {
+    let mut out = [0.0; N];
+    let mut parts = parts;
+    for value in out.iter_mut() {
+        *value = parts
+            .next()
+            .and_then(|p| p.parse().ok())
+            .ok_or_else(|| format!("line {}: expected {} numbers", line_number, N))?;
+    }
+    Ok(out)
+}
+
+/// Resolves a 1-based (or negative, relative) OBJ index into a 0-based one.
+fn resolve(
+    reference: Option<&str>,
+    len: usize,
+    line_number: usize,
+) -> Result<Option<usize>, String> {
+    let reference = match reference {
+        Some(r) if !r.is_empty() => r,
+        _ => return Ok(None),
+    };
+
+    let index: i64 = reference
+        .parse()
+        .map_err(|_| format!("line {}: bad index `{}`", line_number, reference))?;
+    let resolved = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+
+    if resolved < 0 || resolved >= len as i64 {
+        return Err(format!(
+            "line {}: index {} out of range",
+            line_number, index
+        ));
+    }
+    Ok(Some(resolved as usize))
+}
@@ -0,0 +1,310 @@
+use crate::math::{self, Vec3};
+use crate::mesh::Mesh;
+use std::fmt;
+
+/// Size of the FIFO cache `analyze` simulates and the optimizers target,
+/// close to what current GPUs reuse post-transform vertices from.
+const FIFO_CACHE_SIZE: usize = 16;
+
+/// How far reordering for overdraw may worsen the cache miss ratio.
+const OVERDRAW_THRESHOLD: f32 = 1.05;
+
+/// Post-transform vertex cache efficiency of an index buffer.
+#[derive(Copy, Clone, Debug)]
+pub struct CacheStats {
+    /// Average cache miss ratio: vertices transformed per triangle, between
+    /// 0.5 for a perfect large grid and 3 when no vertex is reused.
+    pub acmr: f32,
+    /// Average transform to vertex ratio: vertices transformed per vertex
+    /// used, 1 when every vertex is transformed only once.
+    pub atvr: f32,
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ACMR {:.3}, ATVR {:.3}", self.acmr, self.atvr)
+    }
+}
+
+/// Simulates a FIFO post-transform cache over `indices`.
+pub fn analyze(indices: &[u32], vertex_count: usize) -> CacheStats {
+    // Time each vertex entered the cache; it is still there while fewer
+    // than `FIFO_CACHE_SIZE` misses have happened since.
+    let mut entered = vec![None; vertex_count];
+    let mut used = vec![false; vertex_count];
+    let mut misses = 0usize;
+    for &index in indices {
+        let slot = &mut entered[index as usize];
+        if slot.is_none_or(|time| misses - time >= FIFO_CACHE_SIZE) {
+            *slot = Some(misses);
+            misses += 1;
+        }
+        used[index as usize] = true;
+    }
+    let triangles = (indices.len() / 3).max(1);
+    let vertices = used.iter().filter(|&&u| u).count().max(1);
+    CacheStats {
+        acmr: misses as f32 / triangles as f32,
+        atvr: misses as f32 / vertices as f32,
+    }
+}
+
+/// Reorders triangles, then vertices, for drawing speed: triangles for
+/// vertex cache reuse and front-to-back order, vertices in the order the
+/// triangles first use them. Unused vertices are dropped.
+///
+/// The mesh comes back unchanged unless the new triangle order has a lower
+/// cache miss ratio than the old one. Otherwise its triangle and vertex
+/// numbers, which picking and `rusty_glad inspect` report, no longer match
+/// the source's.
+pub fn optimize(mesh: &Mesh) -> Mesh {
+    let vertex_count = mesh.positions.len();
+    let acmr = |indices: &[u32]| analyze(indices, vertex_count).acmr;
+    let input = acmr(&mesh.indices);
+    // Meshes that come in strip order can already beat the greedy result.
+    let mut indices = optimize_vertex_cache(&mesh.indices, vertex_count);
+    if acmr(&indices) >= input {
+        indices = mesh.indices.clone();
+    }
+    // Overdraw order gives up some cache reuse, but never so much that the
+    // input order would be better.
+    let sorted = optimize_overdraw(mesh, &indices, OVERDRAW_THRESHOLD);
+    if acmr(&sorted) < input {
+        indices = sorted;
+    }
+    if acmr(&indices) >= input {
+        return mesh.clone();
+    }
+    optimize_vertex_fetch(&Mesh {
+        indices,
+        ..mesh.clone()
+    })
+}
+
+/// The triangles around each vertex.
+struct Adjacency {
+    /// `triangles[offsets[v]..offsets[v + 1]]` are the triangles using `v`.
+    offsets: Vec<usize>,
+    triangles: Vec<u32>,
+}
+
+impl Adjacency {
+    fn new(indices: &[u32], vertex_count: usize) -> Adjacency {
+        let mut offsets = vec![0; vertex_count + 1];
+        for &index in indices {
+            offsets[index as usize + 1] += 1;
+        }
+        for v in 0..vertex_count {
+            offsets[v + 1] += offsets[v];
+        }
+        let mut fill = offsets.clone();
+        let mut triangles = vec![0; indices.len()];
+        for (i, &index) in indices.iter().enumerate() {
+            triangles[fill[index as usize]] = (i / 3) as u32;
+            fill[index as usize] += 1;
+        }
+        Adjacency { offsets, triangles }
+    }
+
+    fn of(&self, v: u32) -> &[u32] {
+        &self.triangles[self.offsets[v as usize]..self.offsets[v as usize + 1]]
+    }
+}
+
+/// Reorders triangles for a FIFO post-transform cache of `cache_size`
+/// entries with Tipsify (Sander, Nehab and Barczak 2007): it fans out
+/// around one vertex at a time, moving on to the neighbour that will still
+/// be in the cache once its own fan is done.
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let cache_size = FIFO_CACHE_SIZE;
+    let adjacency = Adjacency::new(indices, vertex_count);
+    let mut live: Vec<usize> = (0..vertex_count as u32)
+        .map(|v| adjacency.of(v).len())
+        .collect();
+    // Time each vertex last entered the cache, counted in misses.
+    let mut entered = vec![0usize; vertex_count];
+    let mut time = cache_size + 1;
+    let mut emitted = vec![false; indices.len() / 3];
+    let mut dead_ends = Vec::new();
+    let mut cursor = 0;
+    let mut output = Vec::with_capacity(indices.len());
+
+    let mut fanning = (vertex_count > 0).then_some(0u32);
+    while let Some(f) = fanning {
+        let mut candidates = Vec::new();
+        for &t in adjacency.of(f) {
+            let t = t as usize;
+            if emitted[t] {
+                continue;
+            }
+            emitted[t] = true;
+            for &v in &indices[t * 3..t * 3 + 3] {
+                output.push(v);
+                dead_ends.push(v);
+                candidates.push(v);
+                live[v as usize] -= 1;
+                if time - entered[v as usize] > cache_size {
+                    entered[v as usize] = time;
+                    time += 1;
+                }
+            }
+        }
+
+        // Prefer the candidate that entered the cache earliest among those
+        // whose whole fan still fits before it is evicted.
+        let mut best = None;
+        let mut best_priority = None;
+        for &v in &candidates {
+            let v_live = live[v as usize];
+            if v_live == 0 {
+                continue;
+            }
+            let age = time - entered[v as usize];
+            let priority = if age + 2 * v_live <= cache_size {
+                age
+            } else {
+                0
+            };
+            if best_priority.is_none_or(|p| priority > p) {
+                best_priority = Some(priority);
+                best = Some(v);
+            }
+        }
+        fanning = best.or_else(|| {
+            while let Some(v) = dead_ends.pop() {
+                if live[v as usize] > 0 {
+                    return Some(v);
+                }
+            }
+            while cursor < vertex_count {
+                if live[cursor] > 0 {
+                    return Some(cursor as u32);
+                }
+                cursor += 1;
+            }
+            None
+        });
+    }
+    output
+}
+
+/// Reorders clusters of triangles so that those facing outwards most, which
+/// tend to hide the rest, come first. A cluster ends once its own cache miss
+/// ratio, counted from an empty cache, is within `threshold` of the whole
+/// order's, so moving clusters around costs little reuse; the new order is
+/// dropped if its miss ratio ends up worse than `threshold` times the old.
+pub fn optimize_overdraw(mesh: &Mesh, indices: &[u32], threshold: f32) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    if triangle_count < 2 {
+        return indices.to_vec();
+    }
+    let before = analyze(indices, mesh.positions.len()).acmr;
+
+    let mut starts = vec![0];
+    let mut entered = vec![None; mesh.positions.len()];
+    let mut misses = 0usize;
+    let mut cluster_misses = 0usize;
+    // Vertices that entered before the cluster's first miss count as
+    // evicted, as if each cluster started with an empty cache.
+    let mut cluster_time = 0;
+    for t in 0..triangle_count {
+        for &index in &indices[t * 3..t * 3 + 3] {
+            let slot = &mut entered[index as usize];
+            if slot.is_none_or(|time| time < cluster_time || misses - time >= FIFO_CACHE_SIZE) {
+                *slot = Some(misses);
+                misses += 1;
+                cluster_misses += 1;
+            }
+        }
+        let cluster_triangles = t + 1 - starts[starts.len() - 1];
+        if t + 1 < triangle_count
+            && cluster_misses as f32 <= threshold * before * cluster_triangles as f32
+        {
+            starts.push(t + 1);
+            cluster_misses = 0;
+            cluster_time = misses;
+        }
+    }
+    starts.push(triangle_count);
+
+    let mesh_centroid = centroid(mesh, indices);
+    let mut clusters: Vec<(f32, &[u32])> = starts
+        .windows(2)
+        .map(|range| {
+            let cluster = &indices[range[0] * 3..range[1] * 3];
+            let normal = cluster.chunks_exact(3).fold([0.0; 3], |sum, t| {
+                math::add(sum, mesh.face_normal([t[0], t[1], t[2]]))
+            });
+            let offset = math::sub(centroid(mesh, cluster), mesh_centroid);
+            (math::dot(offset, math::normalize(normal)), cluster)
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let sorted: Vec<u32> = clusters
+        .iter()
+        .flat_map(|(_, cluster)| cluster.iter().copied())
+        .collect();
+    let after = analyze(&sorted, mesh.positions.len()).acmr;
+    if after <= before * threshold {
+        sorted
+    } else {
+        indices.to_vec()
+    }
+}
+
+/// Area-weighted centroid of the triangles in `indices`.
+fn centroid(mesh: &Mesh, indices: &[u32]) -> Vec3 {
+    let mut sum = [0.0; 3];
+    let mut total = 0.0;
+    for t in indices.chunks_exact(3) {
+        let area = math::length(mesh.face_normal([t[0], t[1], t[2]]));
+        let center = t
+            .iter()
+            .fold([0.0; 3], |c, &i| math::add(c, mesh.position(i)));
+        sum = math::add(sum, math::scale(center, area / 3.0));
+        total += area;
+    }
+    if total > 0.0 {
+        math::scale(sum, 1.0 / total)
+    } else {
+        sum
+    }
+}
+
+/// Renumbers vertices in the order the triangles first use them, so vertex
+/// fetches walk memory forwards. Unused vertices are dropped.
+pub fn optimize_vertex_fetch(mesh: &Mesh) -> Mesh {
+    mesh.extract(&mesh.indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    #[test]
+    fn optimize_never_raises_the_miss_ratio() {
+        for mesh in [
+            Mesh::teapot(),
+            primitives::cylinder(1.0, 2.0, 32),
+            primitives::cone(1.0, 2.0, 32),
+            primitives::icosphere(1.0, 3),
+        ] {
+            let before = analyze(&mesh.indices, mesh.vertex_count()).acmr;
+            let optimized = optimize(&mesh);
+            let after = analyze(&optimized.indices, optimized.vertex_count()).acmr;
+            assert!(after <= before, "ACMR {} became {}", before, after);
+            if after == before {
+                assert_eq!(optimized.indices, mesh.indices);
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_counts_a_single_triangle() {
+        let stats = analyze(&[0, 1, 2], 3);
+        assert_eq!(stats.acmr, 3.0);
+        assert_eq!(stats.atvr, 1.0);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::bounds::{self, Aabb};
+use crate::math::{self, Mat4, Vec3};
+use crate::mesh::Mesh;
+use crate::scene::{LightKind, Scene};
+
+/// Instanced nodes only get per-vertex and per-face lines on this many of
+/// their instances, which keeps grids of thousands of copies drawable.
+const MAX_INSTANCES: usize = 16;
+
+/// Length of the normal and tangent lines as a fraction of the radius of
+/// the mesh they belong to.
+const LINE_LENGTH: f32 = 0.1;
+
+const NORMAL_COLOR: Vec3 = [0.2, 0.6, 1.0];
+const TANGENT_COLOR: Vec3 = [1.0, 0.3, 0.3];
+const FACE_NORMAL_COLOR: Vec3 = [1.0, 0.4, 1.0];
+const BOUNDS_COLOR: Vec3 = [0.3, 1.0, 0.3];
+const AXIS_COLORS: [Vec3; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// One end of a line segment; consecutive pairs form `LinesList` lines.
+#[derive(Copy, Clone)]
+pub struct LineVertex {
+    pub position: (f32, f32, f32),
+    pub color: (f32, f32, f32),
+}
+
+implement_vertex!(LineVertex, position, color);
+
+/// Which debug lines to draw over the scene.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Overlays {
+    /// Each vertex's normal, from the vertex outwards.
+    pub normals: bool,
+    /// Each vertex's tangent, for meshes with UVs.
+    pub tangents: bool,
+    /// Each triangle's normal, from its centroid.
+    pub face_normals: bool,
+    /// The world-space bounding box of each node.
+    pub bounds: bool,
+    /// A star at each point light and a ray towards each directional light,
+    /// in the light's colour.
+    pub lights: bool,
+    /// The world X, Y and Z axes in red, green and blue.
+    pub axes: bool,
+}
+
+impl Overlays {
+    pub fn any(&self) -> bool {
+        self.normals
+            || self.tangents
+            || self.face_normals
+            || self.bounds
+            || self.lights
+            || self.axes
+    }
+
+    /// Generates the enabled overlays as world-space lines. `meshes` is
+    /// parallel to `scene.meshes`.
+    pub fn lines(&self, scene: &Scene, meshes: &[Mesh]) -> Vec<LineVertex> {
+        let mut lines = Lines::default();
+        let scene_bounds = scene.bounds(meshes);
+        let scene_size = if scene_bounds.is_empty() {
+            1.0
+        } else {
+            math::length(scene_bounds.extents())
+        };
+
+        if self.normals || self.tangents || self.face_normals {
+            for node in &scene.nodes {
+                let Some(index) = scene.mesh_index(&node.mesh) else {
+                    continue;
+                };
+                let mesh = &meshes[index];
+                let tangents = if self.tangents { mesh.tangents() } else { None };
+                let length = LINE_LENGTH * mesh.bounding_sphere().radius;
+                let model = node.transform.matrix();
+                let models = match &node.instances {
+                    Some(instances) => instances
+                        .expand()
+                        .iter()
+                        .take(MAX_INSTANCES)
+                        .map(|instance| math::mul(&model, &instance.transform.matrix()))
+                        .collect(),
+                    None => vec![model],
+                };
+
+                for model in &models {
+                    let normal_matrix = normal_matrix(model);
+                    let scale = bounds::max_scale(model);
+                    // Normals follow the normal matrix, while tangents lie in
+                    // the surface and follow the model matrix itself.
+                    let ray = |lines: &mut Lines,
+                               from: Vec3,
+                               direction: Vec3,
+                               normal: bool,
+                               color: Vec3| {
+                        let matrix = if normal { &normal_matrix } else { model };
+                        let from = math::transform_point(model, from);
+                        let direction = math::normalize(math::transform_vector(matrix, direction));
+                        lines.push(
+                            from,
+                            math::add(from, math::scale(direction, length * scale)),
+                            color,
+                        );
+                    };
+
+                    if self.normals && mesh.normals.len() == mesh.vertex_count() {
+                        for i in 0..mesh.vertex_count() as u32 {
+                            ray(
+                                &mut lines,
+                                mesh.position(i),
+                                mesh.normal(i),
+                                true,
+                                NORMAL_COLOR,
+                            );
+                        }
+                    }
+                    if let Some(tangents) = &tangents {
+                        for (i, &tangent) in tangents.iter().enumerate() {
+                            ray(
+                                &mut lines,
+                                mesh.position(i as u32),
+                                tangent,
+                                false,
+                                TANGENT_COLOR,
+                            );
+                        }
+                    }
+                    if self.face_normals {
+                        for t in mesh.triangles() {
+                            let [a, b, c] = t.map(|i| mesh.position(i));
+                            let centroid = math::scale(math::add(math::add(a, b), c), 1.0 / 3.0);
+                            ray(
+                                &mut lines,
+                                centroid,
+                                mesh.face_normal(t),
+                                true,
+                                FACE_NORMAL_COLOR,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+
+        if self.bounds {
+            for index in 0..scene.nodes.len() {
+                let bounds = scene.node_bounds(index, meshes);
+                if !bounds.is_empty() {
+                    lines.aabb(&bounds, BOUNDS_COLOR);
+                }
+            }
+        }
+
+        if self.lights {
+            let center = if scene_bounds.is_empty() {
+                [0.0; 3]
+            } else {
+                scene_bounds.center()
+            };
+            for light in &scene.lights {
+                let color = light.color;
+                match light.kind {
+                    LightKind::Point { position } => lines.star(position, 0.05 * scene_size, color),
+                    LightKind::Directional { direction } => {
+                        let end =
+                            math::add(center, math::scale(math::normalize(direction), scene_size));
+                        lines.push(center, end, color);
+                        lines.star(end, 0.05 * scene_size, color);
+                    }
+                }
+            }
+        }
+
+        if self.axes {
+            for (axis, color) in AXIS_COLORS.into_iter().enumerate() {
+                let mut end = [0.0; 3];
+                end[axis] = 0.5 * scene_size;
+                lines.push([0.0; 3], end, color);
+            }
+        }
+
+        lines.vertices
+    }
+}
+
+#[derive(Default)]
+struct Lines {
+    vertices: Vec<LineVertex>,
+}
+
+impl Lines {
+    fn push(&mut self, from: Vec3, to: Vec3, color: Vec3) {
+        let color = (color[0], color[1], color[2]);
+        for [x, y, z] in [from, to] {
+            self.vertices.push(LineVertex {
+                position: (x, y, z),
+                color,
+            });
+        }
+    }
+
+    /// The twelve edges of `aabb`.
+    fn aabb(&mut self, aabb: &Aabb, color: Vec3) {
+        let corners = aabb.corners();
+        for [a, b] in bounds::BOX_EDGES {
+            self.push(corners[a], corners[b], color);
+        }
+    }
+
+    /// Three crossed lines of length `size` centred on `center`.
+    fn star(&mut self, center: Vec3, size: f32, color: Vec3) {
+        for axis in 0..3 {
+            let mut offset = [0.0; 3];
+            offset[axis] = size / 2.0;
+            self.push(math::sub(center, offset), math::add(center, offset), color);
+        }
+    }
+}
+
+/// The inverse transpose of `m`, which carries normals the way `m` carries
+/// the surfaces they are perpendicular to.
+fn normal_matrix(m: &Mat4) -> Mat4 {
+    match math::invert(m) {
+        Some(inverse) => {
+            let mut out = math::identity();
+            for (col, out_col) in out.iter_mut().enumerate().take(3) {
+                for (row, value) in out_col.iter_mut().enumerate().take(3) {
+                    *value = inverse[row][col];
+                }
+            }
+            out
+        }
+        None => *m,
+    }
+}
@@ -0,0 +1,298 @@
+//! The editing panels of the viewer: lights, materials, camera, render
+//! settings and an outliner of the scene's nodes. They change the scene and
+//! renderer in place, so edits show up in the next frame's uniforms.
+
+use crate::clock::FrameClock;
+use crate::math::Vec3;
+use crate::overlay::Overlays;
+use crate::render::{RenderMode, Renderer, MAX_LIGHTS};
+use crate::scene::{Light, LightKind, Scene, Transform};
+use egui::{CollapsingHeader, ComboBox, Context, DragValue, Grid, ScrollArea, Slider, Ui};
+
+/// Where the panel window first opens, in points from the top left: below
+/// the HUD.
+const DEFAULT_POS: [f32; 2] = [8.0, 160.0];
+const DEFAULT_WIDTH: f32 = 260.0;
+const OUTLINER_HEIGHT: f32 = 160.0;
+/// The cap that turning the frame cap back on starts at.
+const DEFAULT_FPS_CAP: f32 = 60.0;
+
+/// What the viewer holds that the panels edit.
+pub struct Panels<'a> {
+    pub scene: &'a mut Scene,
+    pub renderer: &'a mut Renderer,
+    pub overlays: &'a mut Overlays,
+    pub show_hud: &'a mut bool,
+    pub selected: &'a mut Option<usize>,
+    pub clock: &'a mut FrameClock,
+}
+
+/// What changed in a frame of the panels, for the viewer to rebuild what it
+/// derives from those parts of the scene.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Changes {
+    pub lights: bool,
+    pub overlays: bool,
+    /// A node's transform or material.
+    pub nodes: bool,
+    pub selection: bool,
+    pub frame_all: bool,
+    pub frame_selected: bool,
+}
+
+impl Panels<'_> {
+    pub fn show(&mut self, context: &Context, changes: &mut Changes) {
+        egui::Window::new("Scene")
+            .default_pos(DEFAULT_POS)
+            .default_width(DEFAULT_WIDTH)
+            .default_height(0.0)
+            .show(context, |ui| {
+                CollapsingHeader::new("Render").show(ui, |ui| self.render(ui, changes));
+                CollapsingHeader::new("Camera").show(ui, |ui| self.camera(ui, changes));
+                CollapsingHeader::new("Lights").show(ui, |ui| self.lights(ui, changes));
+                CollapsingHeader::new("Materials").show(ui, |ui| self.materials(ui));
+                CollapsingHeader::new("Outliner").show(ui, |ui| self.outliner(ui, changes));
+            });
+    }
+
+    fn render(&mut self, ui: &mut Ui, changes: &mut Changes) {
+        let renderer = &mut *self.renderer;
+        ComboBox::from_label("mode")
+            .selected_text(renderer.render_mode.name())
+            .show_ui(ui, |ui| {
+                for mode in RenderMode::ALL {
+                    ui.selectable_value(&mut renderer.render_mode, mode, mode.name());
+                }
+            });
+        ui.horizontal(|ui| {
+            ui.label("clear colour");
+            ui.color_edit_button_rgba_unmultiplied(&mut self.scene.clear_color);
+        });
+        ui.checkbox(&mut renderer.grid, "ground grid");
+        ui.checkbox(&mut renderer.frustum_culling, "frustum culling");
+        ui.checkbox(&mut renderer.level_of_detail, "level of detail");
+        ui.checkbox(self.show_hud, "statistics");
+        ui.horizontal(|ui| {
+            let mut capped = self.clock.fps_cap().is_some();
+            let mut fps = self.clock.fps_cap().unwrap_or(DEFAULT_FPS_CAP);
+            let toggled = ui.checkbox(&mut capped, "frame cap").changed();
+            let slider = Slider::new(&mut fps, 10.0..=240.0).suffix(" fps");
+            if ui.add_enabled(capped, slider).changed() || toggled {
+                if let Err(err) = self.clock.set_fps_cap(capped.then_some(fps)) {
+                    eprintln!("{}", err);
+                }
+            }
+        });
+
+        ui.separator();
+        let overlays = &mut *self.overlays;
+        for (flag, name) in [
+            (&mut overlays.normals, "vertex normals"),
+            (&mut overlays.tangents, "tangents"),
+            (&mut overlays.face_normals, "face normals"),
+            (&mut overlays.bounds, "bounds"),
+            (&mut overlays.lights, "lights"),
+            (&mut overlays.axes, "axes"),
+        ] {
+            changes.overlays |= ui.checkbox(flag, name).changed();
+        }
+    }
+
+    fn camera(&mut self, ui: &mut Ui, changes: &mut Changes) {
+        let camera = &mut self.scene.camera;
+        Grid::new("camera").num_columns(2).show(ui, |ui| {
+            ui.label("position");
+            vec3(ui, &mut camera.position, 0.05);
+            ui.end_row();
+            ui.label("target");
+            vec3(ui, &mut camera.target, 0.05);
+            ui.end_row();
+            ui.label("up");
+            vec3(ui, &mut camera.up, 0.01);
+            ui.end_row();
+            ui.label("fov");
+            ui.add(Slider::new(&mut camera.fov, 10.0..=120.0).suffix("°"));
+            ui.end_row();
+            ui.label("near");
+            ui.add(
+                DragValue::new(&mut camera.znear)
+                    .speed(0.01)
+                    .range(0.001..=camera.zfar),
+            );
+            ui.end_row();
+            ui.label("far");
+            ui.add(
+                DragValue::new(&mut camera.zfar)
+                    .speed(1.0)
+                    .range(camera.znear..=f32::MAX),
+            );
+            ui.end_row();
+        });
+        ui.horizontal(|ui| {
+            changes.frame_all |= ui.button("frame all").clicked();
+            changes.frame_selected |= ui.button("frame selected").clicked();
+        });
+    }
+
+    fn lights(&mut self, ui: &mut Ui, changes: &mut Changes) {
+        let lights = &mut self.scene.lights;
+        let mut remove = None;
+        for (i, light) in lights.iter_mut().enumerate() {
+            ui.push_id(i, |ui| {
+                ui.horizontal(|ui| {
+                    ui.strong(format!("light {}", i));
+                    if ui.small_button("remove").clicked() {
+                        remove = Some(i);
+                    }
+                });
+                changes.lights |= light_editor(ui, light);
+            });
+            ui.separator();
+        }
+        if let Some(i) = remove {
+            lights.remove(i);
+            changes.lights = true;
+        }
+        let add = ui.add_enabled(lights.len() < MAX_LIGHTS, egui::Button::new("add light"));
+        if add.clicked() {
+            lights.push(Light {
+                kind: LightKind::Point {
+                    position: self.scene.camera.position,
+                },
+                color: [1.0; 3],
+                intensity: 1.0,
+            });
+            changes.lights = true;
+        }
+    }
+
+    fn materials(&mut self, ui: &mut Ui) {
+        Grid::new("materials").num_columns(3).show(ui, |ui| {
+            for material in &mut self.scene.materials {
+                ui.label(&material.name);
+                ui.color_edit_button_rgb(&mut material.dark_color)
+                    .on_hover_text("dark colour");
+                ui.color_edit_button_rgb(&mut material.regular_color)
+                    .on_hover_text("regular colour");
+                ui.end_row();
+            }
+        });
+    }
+
+    fn outliner(&mut self, ui: &mut Ui, changes: &mut Changes) {
+        ScrollArea::vertical()
+            .max_height(OUTLINER_HEIGHT)
+            .show(ui, |ui| {
+                for (i, node) in self.scene.nodes.iter().enumerate() {
+                    let selected = *self.selected == Some(i);
+                    let label = format!("{} ({})", node.name, node.mesh);
+                    if ui.selectable_label(selected, label).clicked() {
+                        *self.selected = if selected { None } else { Some(i) };
+                        changes.selection = true;
+                    }
+                }
+            });
+
+        let Some(index) = *self.selected else {
+            return;
+        };
+        let material_names: Vec<String> = self
+            .scene
+            .materials
+            .iter()
+            .map(|material| material.name.clone())
+            .collect();
+        let node = &mut self.scene.nodes[index];
+        ui.separator();
+        ComboBox::from_label("material")
+            .selected_text(node.material.as_deref().unwrap_or("(default)"))
+            .show_ui(ui, |ui| {
+                changes.nodes |= ui
+                    .selectable_value(&mut node.material, None, "(default)")
+                    .changed();
+                for name in material_names {
+                    let value = Some(name.clone());
+                    changes.nodes |= ui
+                        .selectable_value(&mut node.material, value, name)
+                        .changed();
+                }
+            });
+        changes.nodes |= transform_editor(ui, &mut node.transform);
+    }
+}
+
+/// Edits the kind, placement and colour of `light`, returning whether any
+/// changed.
+fn light_editor(ui: &mut Ui, light: &mut Light) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        let directional = matches!(light.kind, LightKind::Directional { .. });
+        // Switching kinds keeps the vector, so a direction becomes a position
+        // along it and back.
+        let vector = match light.kind {
+            LightKind::Directional { direction } => direction,
+            LightKind::Point { position } => position,
+        };
+        if ui.radio(directional, "directional").clicked() && !directional {
+            light.kind = LightKind::Directional { direction: vector };
+            changed = true;
+        }
+        if ui.radio(!directional, "point").clicked() && directional {
+            light.kind = LightKind::Point { position: vector };
+            changed = true;
+        }
+    });
+    Grid::new("light").num_columns(2).show(ui, |ui| {
+        match &mut light.kind {
+            LightKind::Directional { direction } => {
+                ui.label("direction");
+                changed |= vec3(ui, direction, 0.01);
+            }
+            LightKind::Point { position } => {
+                ui.label("position");
+                changed |= vec3(ui, position, 0.05);
+            }
+        }
+        ui.end_row();
+        ui.label("colour");
+        changed |= ui.color_edit_button_rgb(&mut light.color).changed();
+        ui.end_row();
+        ui.label("intensity");
+        changed |= ui
+            .add(Slider::new(&mut light.intensity, 0.0..=4.0))
+            .changed();
+        ui.end_row();
+    });
+    changed
+}
+
+/// Edits `transform`, rotation in degrees, returning whether it changed.
+fn transform_editor(ui: &mut Ui, transform: &mut Transform) -> bool {
+    let mut changed = false;
+    Grid::new("transform").num_columns(2).show(ui, |ui| {
+        ui.label("translation");
+        changed |= vec3(ui, &mut transform.translation, 0.05);
+        ui.end_row();
+        ui.label("rotation");
+        changed |= vec3(ui, &mut transform.rotation, 1.0);
+        ui.end_row();
+        ui.label("scale");
+        changed |= vec3(ui, &mut transform.scale, 0.01);
+        ui.end_row();
+    });
+    changed
+}
+
+/// Three drag values side by side, returning whether any changed.
+fn vec3(ui: &mut Ui, value: &mut Vec3, speed: f64) -> bool {
+    ui.horizontal(|ui| {
+        let mut changed = false;
+        for component in value {
+            changed |= ui
+                .add(DragValue::new(component).speed(speed).max_decimals(3))
+                .changed();
+        }
+        changed
+    })
+    .inner
+}
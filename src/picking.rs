@@ -0,0 +1,484 @@
+use crate::bounds::Aabb;
+use crate::math::{self, Mat4, Vec3};
+use crate::mesh::Mesh;
+use crate::scene::Scene;
+
+/// Triangles per BVH leaf before it is split further.
+const LEAF_SIZE: usize = 4;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    /// The world-space ray under a cursor position given in window pixels,
+    /// found by unprojecting the near and far planes through
+    /// `(perspective * view)⁻¹`.
+    pub fn from_cursor(
+        cursor: (f64, f64),
+        window: (u32, u32),
+        perspective: &Mat4,
+        view: &Mat4,
+    ) -> Option<Ray> {
+        let x = (2.0 * cursor.0 / window.0 as f64 - 1.0) as f32;
+        let y = (1.0 - 2.0 * cursor.1 / window.1 as f64) as f32;
+        let inverse = math::invert(&math::mul(perspective, view))?;
+
+        let near = math::project_point(&inverse, [x, y, -1.0]);
+        let far = math::project_point(&inverse, [x, y, 1.0]);
+        Some(Ray {
+            origin: near,
+            direction: math::normalize(math::sub(far, near)),
+        })
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        math::add(self.origin, math::scale(self.direction, t))
+    }
+
+    /// The same ray in the space `m` maps from. The direction is left
+    /// unnormalised so distances along both rays share the parameter `t`.
+    pub fn transform(&self, m: &Mat4) -> Ray {
+        Ray {
+            origin: math::transform_point(m, self.origin),
+            direction: math::transform_vector(m, self.direction),
+        }
+    }
+
+    /// Slab test; returns the parameter at which the ray enters the box.
+    pub fn intersect_aabb(&self, aabb: &Aabb, max_t: f32) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = max_t;
+
+        for axis in 0..3 {
+            let inverse = 1.0 / self.direction[axis];
+            let mut t0 = (aabb.min[axis] - self.origin[axis]) * inverse;
+            let mut t1 = (aabb.max[axis] - self.origin[axis]) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some(t_min)
+    }
+}
+
+/// Möller–Trumbore ray/triangle intersection, hitting both faces. Returns the
+/// ray parameter and the barycentric `(u, v)` of the hit relative to `b` and `c`.
+pub fn intersect_triangle(ray: &Ray, a: Vec3, b: Vec3, c: Vec3) -> Option<(f32, f32, f32)> {
+    let edge1 = math::sub(b, a);
+    let edge2 = math::sub(c, a);
+    let p = math::cross(ray.direction, edge2);
+    let det = math::dot(edge1, p);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let inverse_det = 1.0 / det;
+    let s = math::sub(ray.origin, a);
+    let u = math::dot(s, p) * inverse_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = math::cross(s, edge1);
+    let v = math::dot(ray.direction, q) * inverse_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = math::dot(edge2, q) * inverse_det;
+    if t <= 0.0 {
+        return None;
+    }
+    Some((t, u, v))
+}
+
+enum BvhNode {
+    Leaf {
+        bounds: Aabb,
+        first: usize,
+        count: usize,
+    },
+    Branch {
+        bounds: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { bounds, .. } | BvhNode::Branch { bounds, .. } => bounds,
+        }
+    }
+}
+
+/// Bounding volume hierarchy over the triangles of one mesh, built by median
+/// splits along the longest axis of the triangle centroids.
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    /// Triangle indices, reordered so every leaf covers a contiguous range.
+    triangles: Vec<usize>,
+}
+
+/// The closest triangle hit found in a single mesh.
+#[derive(Copy, Clone, Debug)]
+pub struct TriangleHit {
+    pub triangle: usize,
+    pub t: f32,
+    /// Weights of the triangle's three corners, in index order.
+    pub barycentric: [f32; 3],
+}
+
+impl Bvh {
+    pub fn new(mesh: &Mesh) -> Bvh {
+        let corners: Vec<[Vec3; 3]> = mesh
+            .triangles()
+            .map(|t| t.map(|i| mesh.position(i)))
+            .collect();
+        let centroids: Vec<Vec3> = corners
+            .iter()
+            .map(|[a, b, c]| math::scale(math::add(math::add(*a, *b), *c), 1.0 / 3.0))
+            .collect();
+
+        let mut bvh = Bvh {
+            nodes: Vec::new(),
+            triangles: (0..corners.len()).collect(),
+        };
+        if !corners.is_empty() {
+            bvh.build(&corners, &centroids, 0, corners.len());
+        }
+        bvh
+    }
+
+    fn build(
+        &mut self,
+        corners: &[[Vec3; 3]],
+        centroids: &[Vec3],
+        first: usize,
+        count: usize,
+    ) -> usize {
+        let range = first..first + count;
+        let bounds = Aabb::from_points(
+            self.triangles[range.clone()]
+                .iter()
+                .flat_map(|&t| corners[t]),
+        );
+
+        let index = self.nodes.len();
+        if count <= LEAF_SIZE {
+            self.nodes.push(BvhNode::Leaf {
+                bounds,
+                first,
+                count,
+            });
+            return index;
+        }
+
+        let centroid_bounds =
+            Aabb::from_points(self.triangles[range.clone()].iter().map(|&t| centroids[t]));
+        let extents = centroid_bounds.extents();
+        let axis = (0..3)
+            .max_by(|&a, &b| extents[a].total_cmp(&extents[b]))
+            .unwrap_or(0);
+
+        let half = count / 2;
+        self.triangles[range].select_nth_unstable_by(half, |&a, &b| {
+            centroids[a][axis].total_cmp(&centroids[b][axis])
+        });
+
+        // Reserve this node's slot before the children push theirs.
+        self.nodes.push(BvhNode::Leaf {
+            bounds,
+            first,
+            count,
+        });
+        let left = self.build(corners, centroids, first, half);
+        let right = self.build(corners, centroids, first + half, count - half);
+        self.nodes[index] = BvhNode::Branch {
+            bounds,
+            left,
+            right,
+        };
+        index
+    }
+
+    /// The closest triangle of `mesh` (the mesh this BVH was built from) hit by `ray`.
+    pub fn intersect(&self, mesh: &Mesh, ray: &Ray) -> Option<TriangleHit> {
+        let mut closest: Option<TriangleHit> = None;
+        let mut stack = Vec::new();
+        if !self.nodes.is_empty() {
+            stack.push(0);
+        }
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let max_t = closest.map_or(f32::INFINITY, |hit| hit.t);
+            if ray.intersect_aabb(node.bounds(), max_t).is_none() {
+                continue;
+            }
+
+            match *node {
+                BvhNode::Branch { left, right, .. } => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+                BvhNode::Leaf { first, count, .. } => {
+                    for &triangle in &self.triangles[first..first + count] {
+                        let [a, b, c] =
+                            [0, 1, 2].map(|k| mesh.position(mesh.indices[triangle * 3 + k]));
+                        if let Some((t, u, v)) = intersect_triangle(ray, a, b, c) {
+                            if closest.is_none_or(|hit| t < hit.t) {
+                                closest = Some(TriangleHit {
+                                    triangle,
+                                    t,
+                                    barycentric: [1.0 - u - v, u, v],
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        closest
+    }
+}
+
+/// A triangle under the cursor, in world space.
+#[derive(Copy, Clone, Debug)]
+pub struct Hit {
+    /// Index into `Scene::nodes`.
+    pub node: usize,
+    /// Which copy of an instanced node was hit.
+    pub instance: Option<usize>,
+    pub triangle: usize,
+    pub barycentric: [f32; 3],
+    pub position: Vec3,
+    pub distance: f32,
+    /// The hit triangle's corners in world space.
+    pub corners: [Vec3; 3],
+}
+
+/// Per-mesh BVHs for ray queries against a scene; parallel to `Scene::meshes`.
+pub struct Picker {
+    bvhs: Vec<Bvh>,
+}
+
+impl Picker {
+    pub fn new(meshes: &[Mesh]) -> Picker {
+        Picker {
+            bvhs: meshes.iter().map(Bvh::new).collect(),
+        }
+    }
+
+    pub fn update_mesh(&mut self, index: usize, mesh: &Mesh) {
+        self.bvhs[index] = Bvh::new(mesh);
+    }
+
+    /// The closest hit of `ray` against every node and instance of `scene`.
+    pub fn pick(&self, scene: &Scene, meshes: &[Mesh], ray: &Ray) -> Option<Hit> {
+        let mut closest: Option<Hit> = None;
+
+        for (node_index, node) in scene.nodes.iter().enumerate() {
+            let mesh_index = match scene.mesh_index(&node.mesh) {
+                Some(index) => index,
+                None => continue,
+            };
+            let node_model = node.transform.matrix();
+
+            let models: Vec<(Option<usize>, Mat4)> = match &node.instances {
+                Some(instances) => instances
+                    .expand()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, instance)| {
+                        (
+                            Some(i),
+                            math::mul(&node_model, &instance.transform.matrix()),
+                        )
+                    })
+                    .collect(),
+                None => vec![(None, node_model)],
+            };
+
+            for (instance, model) in models {
+                let hit = match self.pick_mesh(mesh_index, &meshes[mesh_index], &model, ray) {
+                    Some(hit) => hit,
+                    None => continue,
+                };
+                if closest.is_some_and(|c| c.distance <= hit.t) {
+                    continue;
+                }
+
+                let mesh = &meshes[mesh_index];
+                let corners = [0, 1, 2].map(|k| {
+                    let index = mesh.indices[hit.triangle * 3 + k];
+                    math::transform_point(&model, mesh.position(index))
+                });
+                closest = Some(Hit {
+                    node: node_index,
+                    instance,
+                    triangle: hit.triangle,
+                    barycentric: hit.barycentric,
+                    position: ray.at(hit.t),
+                    distance: hit.t,
+                    corners,
+                });
+            }
+        }
+        closest
+    }
+
+    fn pick_mesh(
+        &self,
+        mesh_index: usize,
+        mesh: &Mesh,
+        model: &Mat4,
+        ray: &Ray,
+    ) -> Option<TriangleHit> {
+        let local_ray = ray.transform(&math::invert(model)?);
+        self.bvhs[mesh_index].intersect(mesh, &local_ray)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+    use crate::scene::{Instance, Instances, MeshDesc, MeshSource, Node, Transform};
+
+    const EPSILON: f32 = 1e-5;
+
+    fn ray(origin: Vec3, direction: Vec3) -> Ray {
+        Ray { origin, direction }
+    }
+
+    #[test]
+    fn unit_triangle() {
+        let (a, b, c) = ([0.0; 3], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]);
+        let down = ray([0.25, 0.5, 2.0], [0.0, 0.0, -1.0]);
+        let (t, u, v) = intersect_triangle(&down, a, b, c).unwrap();
+        assert!((t - 2.0).abs() < EPSILON);
+        assert!((u - 0.25).abs() < EPSILON && (v - 0.5).abs() < EPSILON);
+        // Both faces are hit.
+        let up = ray([0.25, 0.5, -3.0], [0.0, 0.0, 1.0]);
+        assert!((intersect_triangle(&up, a, b, c).unwrap().0 - 3.0).abs() < EPSILON);
+
+        let beside = ray([0.75, 0.75, 2.0], [0.0, 0.0, -1.0]);
+        assert!(intersect_triangle(&beside, a, b, c).is_none());
+        let away = ray([0.25, 0.25, 2.0], [0.0, 0.0, 1.0]);
+        assert!(intersect_triangle(&away, a, b, c).is_none());
+        let parallel = ray([0.25, 0.25, 0.0], [1.0, 0.0, 0.0]);
+        assert!(intersect_triangle(&parallel, a, b, c).is_none());
+    }
+
+    #[test]
+    fn slab_test() {
+        let unit = Aabb {
+            min: [0.0; 3],
+            max: [1.0; 3],
+        };
+        let along_x = ray([-1.0, 0.5, 0.5], [1.0, 0.0, 0.0]);
+        assert_eq!(along_x.intersect_aabb(&unit, f32::INFINITY), Some(1.0));
+        assert_eq!(along_x.intersect_aabb(&unit, 0.5), None);
+        let inside = ray([0.5; 3], [0.0, -1.0, 0.0]);
+        assert_eq!(inside.intersect_aabb(&unit, f32::INFINITY), Some(0.0));
+        let above = ray([-1.0, 2.0, 0.5], [1.0, 0.0, 0.0]);
+        assert_eq!(above.intersect_aabb(&unit, f32::INFINITY), None);
+    }
+
+    #[test]
+    fn bvh_finds_the_closest_triangle() {
+        // A sphere has a front and a back triangle along every ray through it.
+        let sphere = primitives::icosphere(1.0, 3);
+        let bvh = Bvh::new(&sphere);
+        for (x, y) in [(0.0, 0.0), (0.3, -0.2), (-0.5, 0.6), (0.9, 0.1)] {
+            let hit = bvh.intersect(&sphere, &ray([x, y, -5.0], [0.0, 0.0, 1.0]));
+            let hit = hit.unwrap();
+            // Near the flat facets of the unit sphere, at z = -√(1 - x² - y²).
+            let z = -(1.0f32 - x * x - y * y).sqrt();
+            assert!((hit.t - (5.0 + z)).abs() < 0.05, "{:?}", hit);
+            assert!((hit.barycentric.iter().sum::<f32>() - 1.0).abs() < EPSILON);
+
+            let brute_force = sphere
+                .triangles()
+                .filter_map(|t| {
+                    let [a, b, c] = t.map(|i| sphere.position(i));
+                    intersect_triangle(&ray([x, y, -5.0], [0.0, 0.0, 1.0]), a, b, c)
+                })
+                .map(|(t, _, _)| t)
+                .fold(f32::INFINITY, f32::min);
+            assert_eq!(hit.t, brute_force);
+        }
+        let miss = bvh.intersect(&sphere, &ray([2.0, 0.0, -5.0], [0.0, 0.0, 1.0]));
+        assert!(miss.is_none());
+    }
+
+    #[test]
+    fn picks_the_nearest_instance() {
+        let cube = primitives::cube(2.0, 1);
+        let at = |translation: Vec3| Transform {
+            translation,
+            ..Transform::default()
+        };
+        let scene = Scene {
+            meshes: vec![MeshDesc {
+                name: "cube".to_string(),
+                source: MeshSource::Teapot,
+                repair: None,
+                winding: Default::default(),
+                subdivision: None,
+                optimize: false,
+                lod: None,
+            }],
+            nodes: vec![
+                Node {
+                    name: "far".to_string(),
+                    mesh: "cube".to_string(),
+                    material: None,
+                    transform: at([0.0, 0.0, 20.0]),
+                    instances: None,
+                },
+                Node {
+                    name: "row".to_string(),
+                    mesh: "cube".to_string(),
+                    material: None,
+                    transform: at([0.0, 0.0, 5.0]),
+                    instances: Some(Instances::List(vec![
+                        Instance {
+                            transform: at([-3.0, 0.0, 0.0]),
+                            color: [1.0; 3],
+                        },
+                        Instance {
+                            transform: at([0.0, 0.0, 0.0]),
+                            color: [1.0; 3],
+                        },
+                    ])),
+                },
+            ],
+            ..Scene::default()
+        };
+        let meshes = vec![cube];
+        let picker = Picker::new(&meshes);
+
+        let hit = picker
+            .pick(&scene, &meshes, &ray([0.0; 3], [0.0, 0.0, 1.0]))
+            .unwrap();
+        assert_eq!((hit.node, hit.instance), (1, Some(1)));
+        // The cube's near face is one unit before its centre.
+        assert!((hit.distance - 4.0).abs() < EPSILON);
+        assert!((hit.position[2] - 4.0).abs() < EPSILON);
+        assert!(hit.corners.iter().all(|c| (c[2] - 4.0).abs() < EPSILON));
+
+        let miss = picker.pick(&scene, &meshes, &ray([0.0; 3], [0.0, 0.0, -1.0]));
+        assert!(miss.is_none());
+    }
+}
@@ -0,0 +1,492 @@
+use crate::math::{self, Vec3};
+use crate::mesh::{Mesh, MeshBuilder};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{PI, TAU};
+
+/// How close to the Y axis an icosphere point is a pole.
+const POLE_EPSILON: f32 = 1e-6;
+
+/// A procedurally generated shape, as referenced from scene files.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Primitive {
+    UvSphere {
+        radius: f32,
+        segments: u32,
+        rings: u32,
+    },
+    Icosphere {
+        radius: f32,
+        subdivisions: u32,
+    },
+    Cube {
+        size: f32,
+        segments: u32,
+    },
+    Plane {
+        width: f32,
+        depth: f32,
+        segments: u32,
+    },
+    Cylinder {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+    Cone {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    },
+    Capsule {
+        radius: f32,
+        height: f32,
+        segments: u32,
+        rings: u32,
+    },
+    Disk {
+        radius: f32,
+        segments: u32,
+    },
+}
+
+impl Primitive {
+    pub fn build(&self) -> Mesh {
+        match *self {
+            Primitive::UvSphere {
+                radius,
+                segments,
+                rings,
+            } => uv_sphere(radius, segments, rings),
+            Primitive::Icosphere {
+                radius,
+                subdivisions,
+            } => icosphere(radius, subdivisions),
+            Primitive::Cube { size, segments } => cube(size, segments),
+            Primitive::Plane {
+                width,
+                depth,
+                segments,
+            } => plane(width, depth, segments, segments),
+            Primitive::Cylinder {
+                radius,
+                height,
+                segments,
+            } => cylinder(radius, height, segments),
+            Primitive::Cone {
+                radius,
+                height,
+                segments,
+            } => cone(radius, height, segments),
+            Primitive::Torus {
+                major_radius,
+                minor_radius,
+                major_segments,
+                minor_segments,
+            } => torus(major_radius, minor_radius, major_segments, minor_segments),
+            Primitive::Capsule {
+                radius,
+                height,
+                segments,
+                rings,
+            } => capsule(radius, height, segments, rings),
+            Primitive::Disk { radius, segments } => disk(radius, segments),
+        }
+    }
+}
+
+/// Emits a `u_segments × v_segments` grid of quads over the parametric
+/// surface `f(u, v) -> (position, normal)`, with `(u, v)` as UVs. The surface
+/// must face the side that `∂f/∂u × ∂f/∂v` points to. Triangles collapsed by
+/// the parameterisation (at poles and apexes) are left out.
+fn surface(
+    builder: &mut MeshBuilder,
+    u_segments: u32,
+    v_segments: u32,
+    f: impl Fn(f32, f32) -> (Vec3, Vec3),
+) {
+    let base = builder.vertex_count();
+    let stride = u_segments + 1;
+    let mut positions = Vec::with_capacity((stride * (v_segments + 1)) as usize);
+
+    for j in 0..=v_segments {
+        for i in 0..=u_segments {
+            let (u, v) = (i as f32 / u_segments as f32, j as f32 / v_segments as f32);
+            let (position, normal) = f(u, v);
+            builder.vertex(position, normal, [u, v]);
+            positions.push(position);
+        }
+    }
+
+    let collapsed = |a: u32, b: u32, c: u32| {
+        let [pa, pb, pc] = [a, b, c].map(|i| positions[(i - base) as usize]);
+        pa == pb || pb == pc || pc == pa
+    };
+    for j in 0..v_segments {
+        for i in 0..u_segments {
+            let a = base + j * stride + i;
+            let (b, c, d) = (a + 1, a + stride + 1, a + stride);
+            if !collapsed(a, b, c) {
+                builder.triangle(a, b, c);
+            }
+            if !collapsed(a, c, d) {
+                builder.triangle(a, c, d);
+            }
+        }
+    }
+}
+
+/// The angle `t` of the way round a full turn, exactly zero again at `t = 1`
+/// so that seams close.
+fn turn(t: f32) -> f32 {
+    TAU * t.fract()
+}
+
+/// Point on the unit sphere at longitude `theta` and polar angle `phi`, exact
+/// at the poles so that `surface` can drop their collapsed triangles.
+fn spherical(theta: f32, phi: f32) -> Vec3 {
+    let sin_phi = if phi <= 0.0 || phi >= PI {
+        0.0
+    } else {
+        phi.sin()
+    };
+    [sin_phi * theta.cos(), phi.cos(), sin_phi * theta.sin()]
+}
+
+/// A flat disk of radius `radius` centred on `center`, facing +Y if `up` and
+/// -Y otherwise.
+fn cap(builder: &mut MeshBuilder, center: Vec3, radius: f32, segments: u32, up: bool) {
+    let normal = [0.0, if up { 1.0 } else { -1.0 }, 0.0];
+    let middle = builder.vertex(center, normal, [0.5, 0.5]);
+    let base = builder.vertex_count();
+
+    for i in 0..=segments {
+        let theta = turn(i as f32 / segments as f32);
+        let (sin, cos) = theta.sin_cos();
+        let position = math::add(center, [radius * cos, 0.0, radius * sin]);
+        builder.vertex(position, normal, [0.5 + 0.5 * cos, 0.5 + 0.5 * sin]);
+    }
+
+    for i in 0..segments {
+        if up {
+            builder.triangle(middle, base + i + 1, base + i);
+        } else {
+            builder.triangle(middle, base + i, base + i + 1);
+        }
+    }
+}
+
+/// Longitude/latitude sphere with poles on the Y axis.
+pub fn uv_sphere(radius: f32, segments: u32, rings: u32) -> Mesh {
+    let (segments, rings) = (segments.max(3), rings.max(2));
+    let mut builder = MeshBuilder::new();
+
+    surface(&mut builder, segments, rings, |u, v| {
+        let theta = -turn(u);
+        let phi = PI * (1.0 - v);
+        let normal = spherical(theta, phi);
+        (math::scale(normal, radius), normal)
+    });
+    builder.build()
+}
+
+/// Sphere made by repeatedly splitting the faces of an icosahedron, which
+/// spreads the triangles far more evenly than `uv_sphere`. UVs are spherical:
+/// triangles across the seam get copies of their vertices with `u` past 1,
+/// and each triangle at a pole its own pole vertex, as `uv_sphere` has.
+pub fn icosphere(radius: f32, subdivisions: u32) -> Mesh {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+    let mut points: Vec<Vec3> = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .iter()
+    .map(|&p| math::normalize(p))
+    .collect();
+
+    // Counter-clockwise seen from outside.
+    let mut faces: Vec<[u32; 3]> = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = std::collections::HashMap::new();
+        let mut midpoint = |a: u32, b: u32, points: &mut Vec<Vec3>| -> u32 {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let p = math::add(points[a as usize], points[b as usize]);
+                points.push(math::normalize(p));
+                points.len() as u32 - 1
+            })
+        };
+
+        let mut split = Vec::with_capacity(faces.len() * 4);
+        for [a, b, c] in faces {
+            let ab = midpoint(a, b, &mut points);
+            let bc = midpoint(b, c, &mut points);
+            let ca = midpoint(c, a, &mut points);
+            split.extend_from_slice(&[[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]);
+        }
+        faces = split;
+    }
+
+    // Points on the seam itself start at `u = 0`, like all the others there.
+    let uv = |p: Vec3| {
+        [
+            (0.5 + p[2].atan2(p[0]) / TAU).fract(),
+            0.5 + p[1].asin() / PI,
+        ]
+    };
+    let is_pole = |p: Vec3| p[0].abs() < POLE_EPSILON && p[2].abs() < POLE_EPSILON;
+
+    // Vertices by point and whether `u` is wrapped past 1. Pole vertices are
+    // never shared.
+    let mut builder = MeshBuilder::new();
+    let mut vertices = std::collections::HashMap::new();
+    for face in faces {
+        let points = face.map(|i| points[i as usize]);
+        let mut uvs = points.map(uv);
+        let poles = points.map(is_pole);
+        let us = || (0..3).filter(|&k| !poles[k]).map(|k| uvs[k][0]);
+        let wrap = us().fold(f32::MIN, f32::max) - us().fold(f32::MAX, f32::min) > 0.5;
+        for k in 0..3 {
+            if wrap && !poles[k] && uvs[k][0] < 0.5 {
+                uvs[k][0] += 1.0;
+            }
+        }
+        // A pole has every `u`; it takes the middle of the triangle's others.
+        let others = 3 - poles.iter().filter(|&&pole| pole).count();
+        let middle = (0..3)
+            .filter(|&k| !poles[k])
+            .map(|k| uvs[k][0])
+            .sum::<f32>()
+            / others as f32;
+
+        let [a, b, c] = [0, 1, 2].map(|k| {
+            let p = points[k];
+            if poles[k] {
+                return builder.vertex(math::scale(p, radius), p, [middle, uvs[k][1]]);
+            }
+            let wrapped = uvs[k][0] >= 1.0;
+            *vertices
+                .entry((face[k], wrapped))
+                .or_insert_with(|| builder.vertex(math::scale(p, radius), p, uvs[k]))
+        });
+        builder.triangle(a, b, c);
+    }
+    builder.build()
+}
+
+/// Axis-aligned cube centred on the origin, each face split into
+/// `segments × segments` quads with its own normals and UVs.
+pub fn cube(size: f32, segments: u32) -> Mesh {
+    let segments = segments.max(1);
+    let half = size / 2.0;
+    let mut builder = MeshBuilder::new();
+
+    // (normal, u axis, v axis) with u × v = normal.
+    let faces: [(Vec3, Vec3, Vec3); 6] = [
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ];
+
+    for (normal, u_axis, v_axis) in faces {
+        surface(&mut builder, segments, segments, |u, v| {
+            let position = math::add(
+                math::scale(normal, half),
+                math::add(
+                    math::scale(u_axis, (u - 0.5) * size),
+                    math::scale(v_axis, (v - 0.5) * size),
+                ),
+            );
+            (position, normal)
+        });
+    }
+    builder.build()
+}
+
+/// Flat grid on the XZ plane facing +Y, centred on the origin.
+pub fn plane(width: f32, depth: f32, x_segments: u32, z_segments: u32) -> Mesh {
+    let mut builder = MeshBuilder::new();
+    surface(
+        &mut builder,
+        x_segments.max(1),
+        z_segments.max(1),
+        |u, v| {
+            let position = [(u - 0.5) * width, 0.0, (0.5 - v) * depth];
+            (position, [0.0, 1.0, 0.0])
+        },
+    );
+    builder.build()
+}
+
+/// Capped cylinder along Y, centred on the origin.
+pub fn cylinder(radius: f32, height: f32, segments: u32) -> Mesh {
+    let segments = segments.max(3);
+    let half = height / 2.0;
+    let mut builder = MeshBuilder::new();
+
+    surface(&mut builder, segments, 1, |u, v| {
+        let theta = -turn(u);
+        let normal = [theta.cos(), 0.0, theta.sin()];
+        let position = [radius * normal[0], height * v - half, radius * normal[2]];
+        (position, normal)
+    });
+    cap(&mut builder, [0.0, half, 0.0], radius, segments, true);
+    cap(&mut builder, [0.0, -half, 0.0], radius, segments, false);
+    builder.build()
+}
+
+/// Cone along Y with its base on `y = 0` and apex at `y = height`. The apex is
+/// split per segment so each side keeps a smooth, slanted normal.
+pub fn cone(radius: f32, height: f32, segments: u32) -> Mesh {
+    let segments = segments.max(3);
+    let slant = math::normalize([height, radius, 0.0]);
+    let mut builder = MeshBuilder::new();
+
+    surface(&mut builder, segments, 1, |u, v| {
+        let theta = -turn(u);
+        let (sin, cos) = theta.sin_cos();
+        let r = radius * (1.0 - v);
+        let normal = [slant[0] * cos, slant[1], slant[0] * sin];
+        ([r * cos, height * v, r * sin], normal)
+    });
+    cap(&mut builder, [0.0, 0.0, 0.0], radius, segments, false);
+    builder.build()
+}
+
+/// Torus around the Y axis.
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+) -> Mesh {
+    let mut builder = MeshBuilder::new();
+
+    surface(
+        &mut builder,
+        major_segments.max(3),
+        minor_segments.max(3),
+        |u, v| {
+            let theta = -turn(u);
+            let phi = turn(v);
+            let (sin_t, cos_t) = theta.sin_cos();
+            let (sin_p, cos_p) = phi.sin_cos();
+            let normal = [cos_p * cos_t, sin_p, cos_p * sin_t];
+            let ring = major_radius + minor_radius * cos_p;
+            ([ring * cos_t, minor_radius * sin_p, ring * sin_t], normal)
+        },
+    );
+    builder.build()
+}
+
+/// Cylinder of the given `height` with hemispherical ends, along Y. `rings`
+/// is the number of latitude bands in each hemisphere.
+pub fn capsule(radius: f32, height: f32, segments: u32, rings: u32) -> Mesh {
+    let (segments, rings) = (segments.max(3), rings.max(1));
+    let half = height / 2.0;
+    let mut builder = MeshBuilder::new();
+
+    // Latitude bands: `rings` for the bottom cap, one for the straight part,
+    // `rings` for the top cap.
+    let bands = 2 * rings + 1;
+    surface(&mut builder, segments, bands, |u, v| {
+        let theta = -turn(u);
+        let band = (v * bands as f32).round() as u32;
+        let (phi, offset) = if band <= rings {
+            (PI - PI / 2.0 * band as f32 / rings as f32, -half)
+        } else {
+            (
+                PI / 2.0 - PI / 2.0 * (band - rings - 1) as f32 / rings as f32,
+                half,
+            )
+        };
+        let normal = spherical(theta, phi);
+        let position = math::add(math::scale(normal, radius), [0.0, offset, 0.0]);
+        (position, normal)
+    });
+    builder.build()
+}
+
+/// Flat disk on the XZ plane facing +Y.
+pub fn disk(radius: f32, segments: u32) -> Mesh {
+    let mut builder = MeshBuilder::new();
+    cap(&mut builder, [0.0; 3], radius, segments.max(3), true);
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn icosphere_uvs_do_not_jump_across_the_seam() {
+        for subdivisions in 0..4 {
+            let mesh = icosphere(1.0, subdivisions);
+            assert_eq!(mesh.triangle_count(), 20 * 4usize.pow(subdivisions));
+            for triangle in mesh.triangles() {
+                let us = triangle.map(|i| mesh.uvs[i as usize].tex_coords.0);
+                let span = us.iter().fold(f32::MIN, |a, &b| a.max(b))
+                    - us.iter().fold(f32::MAX, |a, &b| a.min(b));
+                assert!(span <= 0.5, "triangle {:?} spans u {:?}", triangle, us);
+            }
+        }
+    }
+
+    #[test]
+    fn icosphere_pole_vertices_belong_to_one_triangle() {
+        let mesh = icosphere(2.0, 2);
+        let mut uses = vec![0; mesh.vertex_count()];
+        for &index in &mesh.indices {
+            uses[index as usize] += 1;
+        }
+        let poles: Vec<usize> = (0..mesh.vertex_count())
+            .filter(|&i| {
+                let [x, _, z] = mesh.position(i as u32);
+                x.abs() < 1e-5 && z.abs() < 1e-5
+            })
+            .collect();
+        // The poles are the midpoints of two icosahedron edges, where six
+        // triangles meet.
+        assert_eq!(poles.len(), 2 * 6);
+        assert!(poles.iter().all(|&i| uses[i] == 1));
+    }
+}
@@ -0,0 +1,1172 @@
+use crate::bounds::{Aabb, Frustum, Sphere};
+use crate::camera::Camera;
+use crate::debug_draw;
+use crate::gizmo;
+use crate::math::{self, Mat4};
+use crate::mesh::{Mesh, Normal, Vertex};
+use crate::overlay::LineVertex;
+use crate::scene::{Light, Node, Scene};
+use crate::simplify::Lod;
+use crate::winding::Winding;
+use glium::{
+    draw_parameters::{BackfaceCullingMode, DepthTest, PolygonMode, PolygonOffset},
+    index::{NoIndices, PrimitiveType},
+    uniforms::{UniformValue, Uniforms},
+    Blend, Depth, Display, DrawParameters, Frame, IndexBuffer, Program, Surface, VertexBuffer,
+};
+use std::ops::Range;
+
+pub const MAX_LIGHTS: usize = 4;
+
+const LIGHT_VECTOR_NAMES: [&str; MAX_LIGHTS] = [
+    "u_light_vectors[0]",
+    "u_light_vectors[1]",
+    "u_light_vectors[2]",
+    "u_light_vectors[3]",
+];
+
+const LIGHT_COLOR_NAMES: [&str; MAX_LIGHTS] = [
+    "u_light_colors[0]",
+    "u_light_colors[1]",
+    "u_light_colors[2]",
+    "u_light_colors[3]",
+];
+
+const VERTEX_SHADER_SRC: &str = r#"
+        #version 150
+
+        in vec3 position;
+        in vec3 normal;
+
+        out vec3 v_normal;
+        out vec3 v_position;
+        out vec3 v_color;
+
+        uniform mat4 perspective;
+        uniform mat4 view;
+        uniform mat4 model;
+
+        void main() {
+            vec4 world_position = model * vec4(position, 1.0);
+            v_position = world_position.xyz;
+            v_normal = transpose(inverse(mat3(model))) * normal;
+            v_color = vec3(1.0);
+            gl_Position = perspective * view * world_position;
+        }
+    "#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+        #version 140
+
+        in vec3 v_normal;
+        in vec3 v_position;
+        in vec3 v_color;
+        out vec4 color;
+
+        uniform vec3 dark_color;
+        uniform vec3 regular_color;
+
+        uniform int u_light_count;
+        uniform vec4 u_light_vectors[4];
+        uniform vec3 u_light_colors[4];
+
+        void main() {
+            vec3 normal = normalize(v_normal);
+            vec3 lit = vec3(0.0);
+
+            for (int i = 0; i < u_light_count; i++) {
+                vec4 light = u_light_vectors[i];
+                vec3 to_light = light.w == 0.0 ? light.xyz : light.xyz - v_position;
+                float brightness = dot(normal, normalize(to_light));
+                lit += mix(dark_color, regular_color, brightness) * u_light_colors[i];
+            }
+
+            color = vec4(lit * v_color, 1.0);
+        }
+    "#;
+
+const INSTANCED_VERTEX_SHADER_SRC: &str = r#"
+        #version 150
+
+        in vec3 position;
+        in vec3 normal;
+        in mat4 instance_model;
+        in vec3 instance_color;
+
+        out vec3 v_normal;
+        out vec3 v_position;
+        out vec3 v_color;
+
+        uniform mat4 perspective;
+        uniform mat4 view;
+        uniform mat4 model;
+
+        void main() {
+            mat4 full_model = model * instance_model;
+            vec4 world_position = full_model * vec4(position, 1.0);
+            v_position = world_position.xyz;
+            v_normal = transpose(inverse(mat3(full_model))) * normal;
+            v_color = instance_color;
+            gl_Position = perspective * view * world_position;
+        }
+    "#;
+
+/// Passes each triangle through with the distances in pixels from its
+/// corners to the opposite edges, interpolated without perspective so that
+/// every fragment knows how far it lies from the nearest edge.
+const WIRE_GEOMETRY_SHADER_SRC: &str = r#"
+        #version 150
+
+        layout(triangles) in;
+        layout(triangle_strip, max_vertices = 3) out;
+
+        in vec3 v_normal[];
+        in vec3 v_position[];
+        in vec3 v_color[];
+
+        out vec3 g_normal;
+        out vec3 g_position;
+        out vec3 g_color;
+        noperspective out vec3 g_edge_distance;
+
+        uniform vec2 u_viewport;
+
+        void main() {
+            vec2 p[3];
+            for (int i = 0; i < 3; i++) {
+                vec4 clip = gl_in[i].gl_Position;
+                p[i] = 0.5 * u_viewport * clip.xy / clip.w;
+            }
+            vec2 a = p[1] - p[0];
+            vec2 b = p[2] - p[0];
+            float area = abs(a.x * b.y - a.y * b.x);
+            vec3 heights = vec3(
+                area / length(p[2] - p[1]),
+                area / length(b),
+                area / length(a)
+            );
+
+            for (int i = 0; i < 3; i++) {
+                g_normal = v_normal[i];
+                g_position = v_position[i];
+                g_color = v_color[i];
+                g_edge_distance = vec3(0.0);
+                g_edge_distance[i] = heights[i];
+                gl_Position = gl_in[i].gl_Position;
+                EmitVertex();
+            }
+            EndPrimitive();
+        }
+    "#;
+
+/// Draws anti-aliased triangle edges. `u_wire_mode` picks what lies between
+/// them: 0 leaves it out, 1 shades it as `FRAGMENT_SHADER_SRC` does and 2
+/// fills it with `u_fill_color`.
+const WIRE_FRAGMENT_SHADER_SRC: &str = r#"
+        #version 150
+
+        in vec3 g_normal;
+        in vec3 g_position;
+        in vec3 g_color;
+        noperspective in vec3 g_edge_distance;
+        out vec4 color;
+
+        uniform vec3 dark_color;
+        uniform vec3 regular_color;
+
+        uniform int u_light_count;
+        uniform vec4 u_light_vectors[4];
+        uniform vec3 u_light_colors[4];
+
+        uniform int u_wire_mode;
+        uniform vec3 u_wire_color;
+        uniform vec3 u_fill_color;
+        uniform float u_line_width;
+
+        void main() {
+            float distance = min(min(g_edge_distance.x, g_edge_distance.y), g_edge_distance.z);
+            float line = 1.0 - smoothstep(u_line_width - 0.5, u_line_width + 0.5, distance);
+
+            if (u_wire_mode == 0) {
+                if (line <= 0.0) {
+                    discard;
+                }
+                color = vec4(u_wire_color, line);
+                return;
+            }
+
+            vec3 fill = u_fill_color;
+            if (u_wire_mode == 1) {
+                vec3 normal = normalize(g_normal);
+                vec3 lit = vec3(0.0);
+
+                for (int i = 0; i < u_light_count; i++) {
+                    vec4 light = u_light_vectors[i];
+                    vec3 to_light = light.w == 0.0 ? light.xyz : light.xyz - g_position;
+                    float brightness = dot(normal, normalize(to_light));
+                    lit += mix(dark_color, regular_color, brightness) * u_light_colors[i];
+                }
+                fill = lit * g_color;
+            }
+
+            color = vec4(mix(fill, u_wire_color, line), 1.0);
+        }
+    "#;
+
+const FLAT_VERTEX_SHADER_SRC: &str = r#"
+        #version 140
+
+        in vec3 position;
+
+        uniform mat4 perspective;
+        uniform mat4 view;
+
+        void main() {
+            gl_Position = perspective * view * vec4(position, 1.0);
+        }
+    "#;
+
+const FLAT_FRAGMENT_SHADER_SRC: &str = r#"
+        #version 140
+
+        out vec4 color;
+        uniform vec4 u_color;
+
+        void main() {
+            color = u_color;
+        }
+    "#;
+
+const LINE_VERTEX_SHADER_SRC: &str = r#"
+        #version 140
+
+        in vec3 position;
+        in vec3 color;
+
+        out vec3 v_color;
+
+        uniform mat4 perspective;
+        uniform mat4 view;
+
+        void main() {
+            v_color = color;
+            gl_Position = perspective * view * vec4(position, 1.0);
+        }
+    "#;
+
+const LINE_FRAGMENT_SHADER_SRC: &str = r#"
+        #version 140
+
+        in vec3 v_color;
+        out vec4 color;
+
+        void main() {
+            color = vec4(v_color, 1.0);
+        }
+    "#;
+
+/// Covers the screen with the ground plane: each pixel's ray from the near to
+/// the far plane, found by unprojecting its corners, meets the plane at most
+/// once.
+const GRID_VERTEX_SHADER_SRC: &str = r#"
+        #version 150
+
+        in vec2 position;
+
+        out vec3 v_near;
+        out vec3 v_far;
+
+        uniform mat4 u_inverse_view_projection;
+
+        vec3 unproject(vec2 xy, float z) {
+            vec4 p = u_inverse_view_projection * vec4(xy, z, 1.0);
+            return p.xyz / p.w;
+        }
+
+        void main() {
+            v_near = unproject(position, -1.0);
+            v_far = unproject(position, 1.0);
+            gl_Position = vec4(position, 0.0, 1.0);
+        }
+    "#;
+
+/// Lines every `u_spacing` across the plane `y = u_height`, with every tenth
+/// line stronger and the X and Z axes in red and blue. The minor lines fade
+/// out where they would crowd together and everything fades out towards
+/// `u_fade_distance` from the camera.
+const GRID_FRAGMENT_SHADER_SRC: &str = r#"
+        #version 150
+
+        in vec3 v_near;
+        in vec3 v_far;
+        out vec4 color;
+
+        uniform mat4 u_view_projection;
+        uniform vec3 u_camera;
+        uniform float u_height;
+        uniform float u_spacing;
+        uniform float u_fade_distance;
+        uniform vec3 u_line_color;
+
+        // 1 on the lines through whole numbers of `coord`, falling to 0 a
+        // pixel away from them.
+        float grid(vec2 coord) {
+            vec2 distance = abs(fract(coord - 0.5) - 0.5) / fwidth(coord);
+            return 1.0 - min(min(distance.x, distance.y), 1.0);
+        }
+
+        float axis(float coord) {
+            return 1.0 - min(abs(coord) / fwidth(coord), 1.0);
+        }
+
+        void main() {
+            float t = (u_height - v_near.y) / (v_far.y - v_near.y);
+            vec3 position = mix(v_near, v_far, t);
+
+            vec4 clip = u_view_projection * vec4(position, 1.0);
+            gl_FragDepth = 0.5 * clip.z / clip.w + 0.5;
+
+            vec2 coord = position.xz / u_spacing;
+            vec2 cells_per_pixel = fwidth(coord);
+            float crowding = smoothstep(0.1, 0.3, max(cells_per_pixel.x, cells_per_pixel.y));
+            float minor = grid(coord) * (1.0 - crowding);
+            float major = grid(coord / 10.0);
+
+            vec3 rgb = u_line_color;
+            float alpha = max(0.25 * minor, 0.5 * major);
+            float x_axis = axis(position.z);
+            float z_axis = axis(position.x);
+            if (x_axis > 0.0) {
+                rgb = vec3(1.0, 0.2, 0.2);
+                alpha = max(alpha, 0.8 * x_axis);
+            }
+            if (z_axis > 0.0) {
+                rgb = vec3(0.3, 0.5, 1.0);
+                alpha = max(alpha, 0.8 * z_axis);
+            }
+            alpha *= 1.0 - smoothstep(0.5 * u_fade_distance, u_fade_distance, distance(position, u_camera));
+
+            // Also catches the NaN of rays parallel to the plane.
+            if (!(t > 0.0 && t < 1.0) || alpha <= 0.0) {
+                discard;
+            }
+            color = vec4(rgb, alpha);
+        }
+    "#;
+
+const HIGHLIGHT_COLOR: [f32; 4] = [1.0, 1.0, 0.0, 1.0];
+
+/// Half the width of the wireframe shader's lines, in pixels.
+const LINE_WIDTH: f32 = 0.75;
+
+/// How `Renderer::draw_scene` draws the triangles of the scene.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RenderMode {
+    #[default]
+    Shaded,
+    /// Shaded edges rasterized with `PolygonMode::Line`: one pixel wide and
+    /// aliased, back faces included.
+    Lines,
+    /// Anti-aliased edges from the wireframe shader, back faces included.
+    Wireframe,
+    /// Anti-aliased edges over the shaded surfaces.
+    ShadedWireframe,
+    /// Anti-aliased edges over surfaces filled with the clear colour, which
+    /// hide the edges behind them.
+    HiddenLine,
+}
+
+impl RenderMode {
+    pub const ALL: [RenderMode; 5] = [
+        RenderMode::Shaded,
+        RenderMode::Lines,
+        RenderMode::Wireframe,
+        RenderMode::ShadedWireframe,
+        RenderMode::HiddenLine,
+    ];
+
+    /// The mode after this one, for cycling through them all.
+    pub fn next(self) -> RenderMode {
+        match self {
+            RenderMode::Shaded => RenderMode::Lines,
+            RenderMode::Lines => RenderMode::Wireframe,
+            RenderMode::Wireframe => RenderMode::ShadedWireframe,
+            RenderMode::ShadedWireframe => RenderMode::HiddenLine,
+            RenderMode::HiddenLine => RenderMode::Shaded,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderMode::Shaded => "shaded",
+            RenderMode::Lines => "lines",
+            RenderMode::Wireframe => "wireframe",
+            RenderMode::ShadedWireframe => "shaded wireframe",
+            RenderMode::HiddenLine => "hidden line",
+        }
+    }
+
+    /// The `u_wire_mode` of `WIRE_FRAGMENT_SHADER_SRC` for the modes drawn
+    /// with it.
+    fn wire_mode(self) -> Option<i32> {
+        match self {
+            RenderMode::Shaded | RenderMode::Lines => None,
+            RenderMode::Wireframe => Some(0),
+            RenderMode::ShadedWireframe => Some(1),
+            RenderMode::HiddenLine => Some(2),
+        }
+    }
+
+    /// Whether the back faces show, which they do where nothing fills the
+    /// triangles to hide them.
+    fn see_through(self) -> bool {
+        matches!(self, RenderMode::Lines | RenderMode::Wireframe)
+    }
+}
+
+/// A corner of a quad given directly in normalized device coordinates.
+#[derive(Copy, Clone)]
+struct ScreenVertex {
+    position: (f32, f32),
+}
+
+implement_vertex!(ScreenVertex, position);
+
+/// Grid lines fade out at this many times the larger of the scene's size
+/// and the camera's distance from its target.
+const GRID_FADE_DISTANCE: f32 = 8.0;
+
+/// Per-instance attributes, streamed alongside `(&positions, &normals)`.
+#[derive(Copy, Clone)]
+pub struct InstanceAttributes {
+    pub instance_model: [[f32; 4]; 4],
+    pub instance_color: [f32; 3],
+}
+
+implement_vertex!(InstanceAttributes, instance_model, instance_color);
+
+/// GPU-side copy of a `Mesh`, drawn as `(&positions, &normals)` with `indices`.
+pub struct GpuMesh {
+    pub positions: VertexBuffer<Vertex>,
+    pub normals: VertexBuffer<Normal>,
+    /// Index buffers from the full mesh down to its coarsest level of detail,
+    /// all into the same vertex buffers.
+    pub levels: Vec<IndexBuffer<u32>>,
+    pub lod: Option<Lod>,
+    /// Winding to cull the back faces by; `None` draws both sides.
+    pub winding: Option<Winding>,
+    pub bounds: Aabb,
+    pub sphere: Sphere,
+}
+
+impl GpuMesh {
+    pub fn new(
+        display: &Display,
+        mesh: &Mesh,
+        lod: Option<&Lod>,
+        winding: Option<Winding>,
+    ) -> GpuMesh {
+        let chain = match lod {
+            Some(lod) => lod.chain(mesh),
+            None => vec![mesh.indices.clone()],
+        };
+
+        GpuMesh {
+            positions: VertexBuffer::new(display, &mesh.positions)
+                .expect("failed to create positions VertexBuffer!"),
+            normals: VertexBuffer::new(display, &mesh.normals)
+                .expect("failed to create normals VertexBuffer!"),
+            levels: chain
+                .iter()
+                .map(|indices| {
+                    IndexBuffer::new(display, PrimitiveType::TrianglesList, indices)
+                        .expect("failed to create indices!")
+                })
+                .collect(),
+            lod: lod.cloned(),
+            winding,
+            bounds: mesh.bounds(),
+            sphere: mesh.bounding_sphere(),
+        }
+    }
+
+    /// The level of detail to draw for a copy of this mesh bounded by the
+    /// world-space `sphere`, seen through `camera` in a viewport `height`
+    /// pixels tall.
+    pub fn level(&self, camera: &Camera, sphere: &Sphere, height: u32) -> usize {
+        match &self.lod {
+            Some(lod) => lod.level(camera.projected_size(sphere, height), self.levels.len()),
+            None => 0,
+        }
+    }
+}
+
+/// Every GPU resource needed to draw a `Scene`: `meshes` is parallel to
+/// `Scene::meshes` and `instances` to `Scene::nodes`.
+pub struct GpuScene {
+    pub meshes: Vec<GpuMesh>,
+    pub instances: Vec<Option<GpuInstances>>,
+}
+
+impl GpuScene {
+    pub fn new(display: &Display, scene: &Scene, meshes: &[Mesh]) -> GpuScene {
+        let gpu_meshes: Vec<GpuMesh> = meshes
+            .iter()
+            .zip(&scene.meshes)
+            .map(|(mesh, desc)| {
+                GpuMesh::new(display, mesh, desc.lod.as_ref(), desc.winding.resolve(mesh))
+            })
+            .collect();
+
+        let instances = scene
+            .nodes
+            .iter()
+            .map(|node| {
+                let mesh = &gpu_meshes[scene.mesh_index(&node.mesh)?];
+                GpuInstances::new(display, node, mesh)
+            })
+            .collect();
+
+        GpuScene {
+            meshes: gpu_meshes,
+            instances,
+        }
+    }
+
+    /// Re-uploads mesh `index` after it changed on the CPU, along with the
+    /// instances of the nodes drawing it, whose culling spheres depend on it.
+    pub fn update_mesh(&mut self, display: &Display, scene: &Scene, index: usize, mesh: &Mesh) {
+        let desc = &scene.meshes[index];
+        let gpu_mesh = GpuMesh::new(display, mesh, desc.lod.as_ref(), desc.winding.resolve(mesh));
+        for (node, instances) in scene.nodes.iter().zip(&mut self.instances) {
+            if scene.mesh_index(&node.mesh) == Some(index) {
+                *instances = GpuInstances::new(display, node, &gpu_mesh);
+            }
+        }
+        self.meshes[index] = gpu_mesh;
+    }
+}
+
+/// The instances of one node. The attributes and bounding spheres stay on the
+/// CPU for culling; each frame the visible subset is written to `buffer`.
+pub struct GpuInstances {
+    attributes: Vec<InstanceAttributes>,
+    spheres: Vec<Sphere>,
+    buffer: VertexBuffer<InstanceAttributes>,
+}
+
+impl GpuInstances {
+    fn new(display: &Display, node: &Node, mesh: &GpuMesh) -> Option<GpuInstances> {
+        let instances = node.instances.as_ref()?.expand();
+
+        let attributes: Vec<_> = instances
+            .iter()
+            .map(|instance| InstanceAttributes {
+                instance_model: instance.transform.matrix(),
+                instance_color: instance.color,
+            })
+            .collect();
+        let spheres = attributes
+            .iter()
+            .map(|a| mesh.sphere.transform(&a.instance_model))
+            .collect();
+        let buffer = VertexBuffer::dynamic(display, &attributes)
+            .expect("failed to create instance VertexBuffer!");
+
+        Some(GpuInstances {
+            attributes,
+            spheres,
+            buffer,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.attributes.len()
+    }
+
+    /// Uploads the instances whose bounds intersect `frustum` (all of them when
+    /// `frustum` is `None`), grouped by the level of detail `level` picks from
+    /// their world-space bounds and by whether `model` and their own transform
+    /// together mirror them. Returns the runs of the buffer each group occupies.
+    fn upload_visible(
+        &self,
+        model: &Mat4,
+        frustum: Option<&Frustum>,
+        level: impl Fn(&Sphere) -> usize,
+    ) -> Vec<Run> {
+        let model_mirrored = math::determinant3(model) < 0.0;
+        let mut visible: Vec<(usize, bool, InstanceAttributes)> = self
+            .attributes
+            .iter()
+            .zip(&self.spheres)
+            .filter_map(|(attributes, sphere)| {
+                let sphere = sphere.transform(model);
+                if frustum.is_some_and(|frustum| !frustum.intersects_sphere(&sphere)) {
+                    return None;
+                }
+                let mirrored =
+                    model_mirrored != (math::determinant3(&attributes.instance_model) < 0.0);
+                Some((level(&sphere), mirrored, *attributes))
+            })
+            .collect();
+        visible.sort_by_key(|&(level, mirrored, _)| (level, mirrored));
+
+        let attributes: Vec<InstanceAttributes> = visible.iter().map(|(_, _, a)| *a).collect();
+        if let Some(slice) = self.buffer.slice(0..attributes.len()) {
+            slice.write(&attributes);
+        }
+
+        let mut runs = Vec::new();
+        let mut start = 0;
+        for run in visible.chunk_by(|a, b| (a.0, a.1) == (b.0, b.1)) {
+            runs.push(Run {
+                level: run[0].0,
+                mirrored: run[0].1,
+                range: start..start + run.len(),
+            });
+            start += run.len();
+        }
+        runs
+    }
+}
+
+/// Consecutive copies of a mesh drawn with one call: one level of detail,
+/// all mirrored or none.
+struct Run {
+    level: usize,
+    /// Whether the copies' transforms mirror space, which turns the winding
+    /// around on screen.
+    mirrored: bool,
+    range: Range<usize>,
+}
+
+/// What a call to `Renderer::draw_scene` submitted.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DrawStats {
+    pub draw_calls: usize,
+    pub instances: usize,
+    pub culled: usize,
+    pub triangles: usize,
+}
+
+/// Adds the scene lights, as `u_light_*` arrays, to another set of uniforms.
+struct LitUniforms<'l, U> {
+    base: U,
+    lights: &'l [Light],
+}
+
+impl<'l, U: Uniforms> Uniforms for LitUniforms<'l, U> {
+    fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut visit: F) {
+        self.base.visit_values(&mut visit);
+
+        let count = self.lights.len().min(MAX_LIGHTS);
+        visit("u_light_count", UniformValue::SignedInt(count as i32));
+        for (i, light) in self.lights.iter().take(count).enumerate() {
+            visit(LIGHT_VECTOR_NAMES[i], UniformValue::Vec4(light.vector()));
+            visit(LIGHT_COLOR_NAMES[i], UniformValue::Vec3(light.radiance()));
+        }
+    }
+}
+
+pub struct Renderer {
+    program: Program,
+    instanced_program: Program,
+    flat_program: Program,
+    line_program: Program,
+    grid_program: Program,
+    screen_quad: VertexBuffer<ScreenVertex>,
+    wire_program: Program,
+    instanced_wire_program: Program,
+    /// Holds the `debug_draw` lines of a frame; replaced by a larger one
+    /// when they outgrow it.
+    debug_lines: Option<VertexBuffer<LineVertex>>,
+    pub render_mode: RenderMode,
+    /// Skip nodes and instances whose bounds lie outside the view frustum.
+    pub frustum_culling: bool,
+    /// Draw meshes with levels of detail at the level their screen size calls for.
+    pub level_of_detail: bool,
+    /// Draw the ground grid under the scene.
+    pub grid: bool,
+}
+
+impl Renderer {
+    pub fn new(display: &Display) -> Renderer {
+        let program = Program::from_source(display, VERTEX_SHADER_SRC, FRAGMENT_SHADER_SRC, None)
+            .expect("failed to create program!");
+
+        let instanced_program = Program::from_source(
+            display,
+            INSTANCED_VERTEX_SHADER_SRC,
+            FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("failed to create instanced program!");
+
+        let flat_program = Program::from_source(
+            display,
+            FLAT_VERTEX_SHADER_SRC,
+            FLAT_FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("failed to create flat program!");
+
+        let line_program = Program::from_source(
+            display,
+            LINE_VERTEX_SHADER_SRC,
+            LINE_FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("failed to create line program!");
+
+        let grid_program = Program::from_source(
+            display,
+            GRID_VERTEX_SHADER_SRC,
+            GRID_FRAGMENT_SHADER_SRC,
+            None,
+        )
+        .expect("failed to create grid program!");
+
+        let screen_quad = VertexBuffer::new(
+            display,
+            &[(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)]
+                .map(|position| ScreenVertex { position }),
+        )
+        .expect("failed to create screen quad VertexBuffer!");
+
+        let wire_program = Program::from_source(
+            display,
+            VERTEX_SHADER_SRC,
+            WIRE_FRAGMENT_SHADER_SRC,
+            Some(WIRE_GEOMETRY_SHADER_SRC),
+        )
+        .expect("failed to create wire program!");
+
+        let instanced_wire_program = Program::from_source(
+            display,
+            INSTANCED_VERTEX_SHADER_SRC,
+            WIRE_FRAGMENT_SHADER_SRC,
+            Some(WIRE_GEOMETRY_SHADER_SRC),
+        )
+        .expect("failed to create instanced wire program!");
+
+        Renderer {
+            program,
+            instanced_program,
+            flat_program,
+            line_program,
+            grid_program,
+            screen_quad,
+            wire_program,
+            instanced_wire_program,
+            debug_lines: None,
+            render_mode: RenderMode::default(),
+            frustum_culling: true,
+            level_of_detail: true,
+            grid: true,
+        }
+    }
+
+    /// Draws every node of `scene`, instanced nodes with one draw call per
+    /// level of detail in view.
+    pub fn draw_scene(&self, target: &mut Frame, scene: &Scene, gpu: &GpuScene) -> DrawStats {
+        let (width, height) = target.get_dimensions();
+        let perspective = scene.camera.perspective(width, height);
+        let view = scene.camera.view_matrix();
+        let frustum = Frustum::from_matrix(&math::mul(&perspective, &view));
+        let frustum = if self.frustum_culling {
+            Some(&frustum)
+        } else {
+            None
+        };
+
+        let [r, g, b, a] = scene.clear_color;
+        target.clear_color_and_depth((r, g, b, a), 1.0);
+
+        let mut params = DrawParameters {
+            depth: Depth {
+                test: DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let (program, instanced_program) = match self.render_mode.wire_mode() {
+            Some(_) => (&self.wire_program, &self.instanced_wire_program),
+            None => (&self.program, &self.instanced_program),
+        };
+        if self.render_mode == RenderMode::Lines {
+            params.polygon_mode = PolygonMode::Line;
+        }
+        if self.render_mode == RenderMode::Wireframe {
+            params.blend = Blend::alpha_blending();
+        }
+        let fill_color = [r, g, b];
+        let wire_color = match self.render_mode {
+            RenderMode::ShadedWireframe => [0.0; 3],
+            _ => contrasting(fill_color),
+        };
+
+        let mut stats = DrawStats::default();
+
+        for (node, instances) in scene.nodes.iter().zip(&gpu.instances) {
+            let mesh = match scene.mesh_index(&node.mesh) {
+                Some(index) => &gpu.meshes[index],
+                None => continue,
+            };
+            let model = node.transform.matrix();
+            let level = |sphere: &Sphere| {
+                if self.level_of_detail {
+                    mesh.level(&scene.camera, sphere, height)
+                } else {
+                    0
+                }
+            };
+
+            let runs = match instances {
+                Some(instances) => instances.upload_visible(&model, frustum, level),
+                None => match frustum {
+                    Some(frustum) if !visible(frustum, mesh, &model) => Vec::new(),
+                    _ => vec![Run {
+                        level: level(&mesh.sphere.transform(&model)),
+                        mirrored: math::determinant3(&model) < 0.0,
+                        range: 0..1,
+                    }],
+                },
+            };
+            let copies: usize = runs.iter().map(|run| run.range.len()).sum();
+            stats.culled += instances.as_ref().map_or(1, GpuInstances::len) - copies;
+            if copies == 0 {
+                continue;
+            }
+
+            let (dark_color, regular_color) = match scene.material(node) {
+                Some(material) => (material.dark_color, material.regular_color),
+                None => ([0.5, 0.5, 0.5], [1.0, 1.0, 1.0]),
+            };
+
+            let uniforms = LitUniforms {
+                base: uniform! {
+                    model: model,
+                    perspective: perspective,
+                    view: view,
+                    dark_color: dark_color,
+                    regular_color: regular_color,
+                    u_viewport: [width as f32, height as f32],
+                    u_wire_mode: self.render_mode.wire_mode().unwrap_or(0),
+                    u_wire_color: wire_color,
+                    u_fill_color: fill_color,
+                    u_line_width: LINE_WIDTH,
+                },
+                lights: &scene.lights,
+            };
+
+            for Run {
+                level,
+                mirrored,
+                range,
+            } in runs
+            {
+                params.backface_culling = if self.render_mode.see_through() {
+                    BackfaceCullingMode::CullingDisabled
+                } else {
+                    culling(mesh.winding, mirrored)
+                };
+                let indices = &mesh.levels[level];
+                match instances {
+                    Some(instances) => {
+                        let visible = instances
+                            .buffer
+                            .slice(range.clone())
+                            .expect("visible instances exceed the instance buffer!");
+                        target
+                            .draw(
+                                (
+                                    &mesh.positions,
+                                    &mesh.normals,
+                                    visible
+                                        .per_instance()
+                                        .expect("instancing is not supported!"),
+                                ),
+                                indices,
+                                instanced_program,
+                                &uniforms,
+                                &params,
+                            )
+                            .expect("failed to draw instanced program!");
+                    }
+                    None => {
+                        target
+                            .draw(
+                                (&mesh.positions, &mesh.normals),
+                                indices,
+                                program,
+                                &uniforms,
+                                &params,
+                            )
+                            .expect("failed to draw program!");
+                    }
+                }
+
+                stats.draw_calls += 1;
+                stats.instances += range.len();
+                stats.triangles += range.len() * indices.len() / 3;
+            }
+        }
+
+        stats
+    }
+}
+
+impl Renderer {
+    /// Draws world-space triangles (such as the picked one) in a flat
+    /// highlight colour, pulled slightly towards the camera so they win the
+    /// depth test against the surface they lie on.
+    pub fn draw_highlight(
+        &self,
+        target: &mut Frame,
+        scene: &Scene,
+        triangles: &VertexBuffer<Vertex>,
+    ) {
+        let (width, height) = target.get_dimensions();
+        let uniforms = uniform! {
+            perspective: scene.camera.perspective(width, height),
+            view: scene.camera.view_matrix(),
+            u_color: HIGHLIGHT_COLOR,
+        };
+        let params = DrawParameters {
+            depth: Depth {
+                test: DepthTest::IfLessOrEqual,
+                write: false,
+                ..Default::default()
+            },
+            polygon_offset: PolygonOffset {
+                factor: -1.0,
+                units: -1.0,
+                fill: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        target
+            .draw(
+                triangles,
+                NoIndices(PrimitiveType::TrianglesList),
+                &self.flat_program,
+                &uniforms,
+                &params,
+            )
+            .expect("failed to draw highlight!");
+    }
+
+    /// Draws world-space debug lines, such as the `overlay` ones, hidden by
+    /// the surfaces in front of them.
+    pub fn draw_lines(&self, target: &mut Frame, scene: &Scene, lines: &VertexBuffer<LineVertex>) {
+        let (width, height) = target.get_dimensions();
+        let uniforms = uniform! {
+            perspective: scene.camera.perspective(width, height),
+            view: scene.camera.view_matrix(),
+        };
+
+        target
+            .draw(
+                lines,
+                NoIndices(PrimitiveType::LinesList),
+                &self.line_program,
+                &uniforms,
+                &line_params(true),
+            )
+            .expect("failed to draw lines!");
+    }
+
+    /// Draws the shapes added through `debug_draw` for this frame from a
+    /// single buffer: the depth-tested ones, then the rest on top. Must run
+    /// once per frame, as it also counts down the shapes' lifetimes.
+    pub fn draw_debug(&mut self, display: &Display, target: &mut Frame, scene: &Scene) {
+        let (vertices, depth_tested) = debug_draw::with(|d| d.end_frame(&scene.camera));
+        if vertices.is_empty() {
+            return;
+        }
+        if self
+            .debug_lines
+            .as_ref()
+            .is_some_and(|buffer| buffer.len() < vertices.len())
+        {
+            self.debug_lines = None;
+        }
+        let buffer = self.debug_lines.get_or_insert_with(|| {
+            VertexBuffer::empty_dynamic(display, vertices.len().next_power_of_two())
+                .expect("failed to create debug VertexBuffer!")
+        });
+        let used = buffer
+            .slice(0..vertices.len())
+            .expect("debug lines exceed the debug buffer!");
+        used.write(&vertices);
+
+        let (width, height) = target.get_dimensions();
+        let uniforms = uniform! {
+            perspective: scene.camera.perspective(width, height),
+            view: scene.camera.view_matrix(),
+        };
+        for (range, depth_test) in [
+            (0..depth_tested, true),
+            (depth_tested..vertices.len(), false),
+        ] {
+            if range.is_empty() {
+                continue;
+            }
+            let lines = buffer
+                .slice(range)
+                .expect("debug lines exceed the debug buffer!");
+            target
+                .draw(
+                    lines,
+                    NoIndices(PrimitiveType::LinesList),
+                    &self.line_program,
+                    &uniforms,
+                    &line_params(depth_test),
+                )
+                .expect("failed to draw debug lines!");
+        }
+    }
+}
+
+impl Renderer {
+    /// Draws an endless grid on the ground plane through the bottom of
+    /// `bounds`, the scene's, with lines spaced by the power of ten that suits
+    /// its size. Does nothing unless `grid` is set.
+    pub fn draw_grid(&self, target: &mut Frame, scene: &Scene, bounds: &Aabb) {
+        if !self.grid {
+            return;
+        }
+        let (height, size) = if bounds.is_empty() {
+            (0.0, 1.0)
+        } else {
+            let extents = bounds.extents();
+            (bounds.min[1], extents[0].max(extents[1]).max(extents[2]))
+        };
+        let spacing = 10f32.powf(size.max(f32::EPSILON).log10().floor() - 1.0);
+
+        let camera = &scene.camera;
+        let (width, window_height) = target.get_dimensions();
+        let view_projection = math::mul(
+            &camera.perspective(width, window_height),
+            &camera.view_matrix(),
+        );
+        let inverse = match math::invert(&view_projection) {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let distance = math::length(camera.direction());
+        let [r, g, b, _] = scene.clear_color;
+
+        let uniforms = uniform! {
+            u_inverse_view_projection: inverse,
+            u_view_projection: view_projection,
+            u_camera: camera.position,
+            u_height: height,
+            u_spacing: spacing,
+            u_fade_distance: GRID_FADE_DISTANCE * size.max(distance),
+            u_line_color: contrasting([r, g, b]),
+        };
+        let params = DrawParameters {
+            depth: Depth {
+                test: DepthTest::IfLess,
+                write: false,
+                ..Default::default()
+            },
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        target
+            .draw(
+                &self.screen_quad,
+                NoIndices(PrimitiveType::TriangleStrip),
+                &self.grid_program,
+                &uniforms,
+                &params,
+            )
+            .expect("failed to draw grid!");
+    }
+
+    /// Draws the `gizmo` for `camera` in its corner of the window, over
+    /// everything else.
+    pub fn draw_gizmo(&self, display: &Display, target: &mut Frame, camera: &Camera) {
+        let lines = VertexBuffer::new(display, &gizmo::lines(camera))
+            .expect("failed to create gizmo VertexBuffer!");
+        let uniforms = uniform! {
+            perspective: math::identity(),
+            view: math::identity(),
+        };
+        let params = DrawParameters {
+            viewport: Some(gizmo::viewport(target.get_dimensions())),
+            ..line_params(false)
+        };
+
+        target
+            .draw(
+                &lines,
+                NoIndices(PrimitiveType::LinesList),
+                &self.line_program,
+                &uniforms,
+                &params,
+            )
+            .expect("failed to draw gizmo!");
+    }
+}
+
+/// Draw parameters for lines that surfaces hide when `depth_test` is set
+/// and that go on top of everything otherwise.
+fn line_params(depth_test: bool) -> DrawParameters<'static> {
+    let depth = if depth_test {
+        Depth {
+            test: DepthTest::IfLessOrEqual,
+            write: true,
+            ..Default::default()
+        }
+    } else {
+        Depth {
+            test: DepthTest::Overwrite,
+            write: false,
+            ..Default::default()
+        }
+    };
+    DrawParameters {
+        depth,
+        ..Default::default()
+    }
+}
+
+/// The culling mode that hides the back faces of a mesh wound `winding`.
+/// Through the left-handed view matrix a clockwise front appears
+/// counter-clockwise on screen, so clockwise meshes cull clockwise.
+fn culling(winding: Option<Winding>, mirrored: bool) -> BackfaceCullingMode {
+    match (winding, mirrored) {
+        (None, _) => BackfaceCullingMode::CullingDisabled,
+        (Some(Winding::Clockwise), false) | (Some(Winding::CounterClockwise), true) => {
+            BackfaceCullingMode::CullClockwise
+        }
+        (Some(Winding::Clockwise), true) | (Some(Winding::CounterClockwise), false) => {
+            BackfaceCullingMode::CullCounterClockwise
+        }
+    }
+}
+
+/// Black or white, whichever stands out more against `color`.
+fn contrasting([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    if luminance > 0.5 {
+        [0.0; 3]
+    } else {
+        [1.0; 3]
+    }
+}
+
+/// Whether the node is in view: both its bounding sphere and its world AABB
+/// must intersect `frustum`. The cheap sphere test rejects most nodes; the
+/// AABB then rejects more of the long, thin meshes the sphere fits loosely.
+fn visible(frustum: &Frustum, mesh: &GpuMesh, model: &Mat4) -> bool {
+    frustum.intersects_sphere(&mesh.sphere.transform(model))
+        && frustum.intersects_aabb(&mesh.bounds.transform(model))
+}
@@ -0,0 +1,564 @@
+use crate::halfedge::HalfEdgeMesh;
+use crate::math::{self, Vec3};
+use crate::mesh::{Mesh, Normal, TexCoord, Vertex};
+use crate::triangulate;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// Problems found in a mesh by `validate`. Triangles are numbered by their
+/// place in the index buffer; edges and borders are given by position since
+/// vertices split for normals or UVs would otherwise hide them.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub vertices: usize,
+    pub triangles: usize,
+    /// Triangles with an index past the end of the vertex arrays.
+    pub out_of_range: Vec<usize>,
+    /// Triangles with two corners at one position, or with no area.
+    pub degenerate: Vec<usize>,
+    /// Triangles with the same corners as an earlier one, in either winding.
+    pub duplicate: Vec<usize>,
+    /// Vertices equal in every attribute to an earlier one.
+    pub duplicate_vertices: Vec<u32>,
+    pub unreferenced: Vec<u32>,
+    /// Edges shared by more than two triangles.
+    pub non_manifold_edges: Vec<[Vec3; 2]>,
+    /// Edges whose two triangles run the same way along them, so one of the
+    /// two faces the wrong way.
+    pub inconsistent_edges: Vec<[Vec3; 2]>,
+    /// The corners around each hole or open border.
+    pub boundary_loops: Vec<Vec<Vec3>>,
+}
+
+impl Report {
+    pub fn is_clean(&self) -> bool {
+        self.out_of_range.is_empty()
+            && self.degenerate.is_empty()
+            && self.duplicate.is_empty()
+            && self.duplicate_vertices.is_empty()
+            && self.unreferenced.is_empty()
+            && self.non_manifold_edges.is_empty()
+            && self.inconsistent_edges.is_empty()
+            && self.boundary_loops.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} vertices, {} triangles",
+            self.vertices, self.triangles
+        )?;
+        if self.is_clean() {
+            return write!(f, ", no problems found");
+        }
+        let problems = [
+            (
+                self.out_of_range.len(),
+                "triangles index past the vertex arrays",
+            ),
+            (self.degenerate.len(), "degenerate triangles"),
+            (self.duplicate.len(), "duplicate triangles"),
+            (self.duplicate_vertices.len(), "duplicate vertices"),
+            (self.unreferenced.len(), "unreferenced vertices"),
+            (self.non_manifold_edges.len(), "non-manifold edges"),
+            (
+                self.inconsistent_edges.len(),
+                "edges with inconsistent winding",
+            ),
+            (self.boundary_loops.len(), "holes or open borders"),
+        ];
+        for (count, problem) in problems {
+            if count > 0 {
+                write!(f, "\n  {} {}", count, problem)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Which repairs `Repair::apply` makes, as set per mesh in scene files.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Repair {
+    /// Merges vertices whose attributes all agree to within `tolerance`.
+    #[serde(default = "default_enabled")]
+    pub weld: bool,
+    #[serde(default = "default_tolerance")]
+    pub tolerance: f32,
+    /// Removes out-of-range, degenerate and duplicate triangles.
+    #[serde(default = "default_enabled")]
+    pub remove_degenerates: bool,
+    #[serde(default = "default_enabled")]
+    pub reorient: bool,
+    /// Closes holes with flat caps; off by default since it changes the shape.
+    #[serde(default)]
+    pub fill_holes: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_tolerance() -> f32 {
+    1e-5
+}
+
+impl Default for Repair {
+    fn default() -> Repair {
+        Repair {
+            weld: true,
+            tolerance: default_tolerance(),
+            remove_degenerates: true,
+            reorient: true,
+            fill_holes: false,
+        }
+    }
+}
+
+impl Repair {
+    /// Welds before removing degenerates, since merging nearly coincident
+    /// corners can collapse triangles.
+    pub fn apply(&self, mesh: &mut Mesh) {
+        if self.weld {
+            weld(mesh, self.tolerance);
+        }
+        if self.remove_degenerates {
+            remove_degenerates(mesh);
+        }
+        if self.reorient {
+            reorient(mesh);
+        }
+        if self.fill_holes {
+            fill_holes(mesh);
+        }
+    }
+}
+
+/// Triangles with every index in range, as welded position numbers.
+fn welded_triangles(mesh: &Mesh, welded: &[u32]) -> Vec<(usize, [u32; 3])> {
+    mesh.triangles()
+        .enumerate()
+        .filter(|(_, t)| t.iter().all(|&i| (i as usize) < welded.len()))
+        .map(|(index, t)| (index, t.map(|i| welded[i as usize])))
+        .collect()
+}
+
+fn is_degenerate(mesh: &Mesh, triangle: [u32; 3], [a, b, c]: [u32; 3]) -> bool {
+    a == b || b == c || c == a || math::length(mesh.face_normal(triangle)) == 0.0
+}
+
+fn sorted(mut corners: [u32; 3]) -> [u32; 3] {
+    corners.sort_unstable();
+    corners
+}
+
+/// Checks `mesh` for broken indices and for the topological problems that
+/// show up as cracks, flicker or inside-out faces.
+pub fn validate(mesh: &Mesh) -> Report {
+    let (welded, positions) = mesh.weld();
+    let vertex_count = mesh.positions.len();
+    let mut report = Report {
+        vertices: vertex_count,
+        triangles: mesh.triangle_count(),
+        ..Report::default()
+    };
+
+    let mut used = vec![false; vertex_count];
+    let mut seen = HashSet::new();
+    let mut faces = Vec::new();
+    for (index, triangle) in mesh.triangles().enumerate() {
+        if triangle.iter().any(|&i| i as usize >= vertex_count) {
+            report.out_of_range.push(index);
+            continue;
+        }
+        triangle.iter().for_each(|&i| used[i as usize] = true);
+        let corners = triangle.map(|i| welded[i as usize]);
+        if is_degenerate(mesh, triangle, corners) {
+            report.degenerate.push(index);
+        } else if !seen.insert(sorted(corners)) {
+            report.duplicate.push(index);
+        } else {
+            faces.push(corners.to_vec());
+        }
+    }
+    report.unreferenced = (0..vertex_count as u32)
+        .filter(|&i| !used[i as usize])
+        .collect();
+
+    let mut vertices = HashSet::new();
+    for i in 0..vertex_count as u32 {
+        if !vertices.insert(vertex_key(mesh, i, 0.0)) {
+            report.duplicate_vertices.push(i);
+        }
+    }
+
+    // Directions the triangles run along each edge, as seen from its
+    // lower-numbered end.
+    let mut edges: HashMap<(u32, u32), Vec<bool>> = HashMap::new();
+    for face in &faces {
+        for k in 0..3 {
+            let (a, b) = (face[k], face[(k + 1) % 3]);
+            edges.entry((a.min(b), a.max(b))).or_default().push(a < b);
+        }
+    }
+    let ends = |(a, b): (u32, u32)| [positions[a as usize], positions[b as usize]];
+    for (&edge, directions) in &edges {
+        match directions[..] {
+            [_] => {}
+            [first, second] if first == second => report.inconsistent_edges.push(ends(edge)),
+            [_, _] => {}
+            _ => report.non_manifold_edges.push(ends(edge)),
+        }
+    }
+
+    let topology = HalfEdgeMesh::new(positions, &faces);
+    report.boundary_loops = topology
+        .boundary_loops()
+        .into_iter()
+        .map(|ring| {
+            ring.iter()
+                .map(|&v| topology.positions[v as usize])
+                .collect()
+        })
+        .collect();
+    report
+}
+
+/// Hashable form of every attribute of vertex `i`, rounded to `tolerance`
+/// when it is positive.
+fn vertex_key(mesh: &Mesh, i: u32, tolerance: f32) -> Vec<u32> {
+    let i = i as usize;
+    let (x, y, z) = mesh.positions[i].position;
+    let mut values = vec![x, y, z];
+    if let Some(normal) = mesh.normals.get(i) {
+        let (x, y, z) = normal.normal;
+        values.extend([x, y, z]);
+    }
+    if let Some(uv) = mesh.uvs.get(i) {
+        let (u, v) = uv.tex_coords;
+        values.extend([u, v]);
+    }
+    values
+        .into_iter()
+        .map(|value| {
+            let value = if tolerance > 0.0 {
+                (value / tolerance).round()
+            } else {
+                value
+            };
+            // Adding zero folds -0.0 into 0.0 so both hash alike.
+            (value + 0.0).to_bits()
+        })
+        .collect()
+}
+
+/// Merges vertices whose positions, normals and UVs agree to within
+/// `tolerance` and drops vertices no triangle uses, along with triangles
+/// indexing past the vertex arrays, which have no vertices to keep. Returns
+/// how many vertices went away.
+pub fn weld(mesh: &mut Mesh, tolerance: f32) -> usize {
+    let before = mesh.positions.len();
+    let mut first = HashMap::new();
+    let remap: Vec<u32> = (0..before as u32)
+        .map(|i| *first.entry(vertex_key(mesh, i, tolerance)).or_insert(i))
+        .collect();
+    let indices: Vec<u32> = welded_triangles(mesh, &remap)
+        .into_iter()
+        .flat_map(|(_, corners)| corners)
+        .collect();
+    *mesh = mesh.extract(&indices);
+    before - mesh.positions.len()
+}
+
+/// Removes triangles with out-of-range indices, degenerate triangles and
+/// repeats of earlier triangles. Returns how many went away.
+pub fn remove_degenerates(mesh: &mut Mesh) -> usize {
+    let (welded, _) = mesh.weld();
+    let before = mesh.triangle_count();
+    let mut seen = HashSet::new();
+    let indices: Vec<u32> = welded_triangles(mesh, &welded)
+        .into_iter()
+        .filter(|&(index, corners)| {
+            let triangle = [0, 1, 2].map(|k| mesh.indices[index * 3 + k]);
+            !is_degenerate(mesh, triangle, corners) && seen.insert(sorted(corners))
+        })
+        .flat_map(|(index, _)| [0, 1, 2].map(|k| mesh.indices[index * 3 + k]))
+        .collect();
+    mesh.indices = indices;
+    before - mesh.triangle_count()
+}
+
+/// Flips triangles so that neighbours agree on their winding, then turns
+/// each connected piece outwards: the mesh's front faces enclose negative
+/// signed volume. Pieces without volume, like a single plane, keep the
+/// winding most of their triangles had. Returns how many triangles flipped.
+pub fn reorient(mesh: &mut Mesh) -> usize {
+    let (welded, _) = mesh.weld();
+    let triangles = welded_triangles(mesh, &welded);
+
+    // Neighbours across manifold edges, and whether both run the same way
+    // along the edge (so one of them must flip to agree).
+    let mut edges: HashMap<(u32, u32), Vec<(usize, bool)>> = HashMap::new();
+    for (t, (_, corners)) in triangles.iter().enumerate() {
+        for k in 0..3 {
+            let (a, b) = (corners[k], corners[(k + 1) % 3]);
+            edges
+                .entry((a.min(b), a.max(b)))
+                .or_default()
+                .push((t, a < b));
+        }
+    }
+    let mut neighbours = vec![Vec::new(); triangles.len()];
+    for sharing in edges.values() {
+        if let [(t1, d1), (t2, d2)] = sharing[..] {
+            neighbours[t1].push((t2, d1 == d2));
+            neighbours[t2].push((t1, d1 == d2));
+        }
+    }
+
+    let mut flip: Vec<Option<bool>> = vec![None; triangles.len()];
+    let mut flipped = 0;
+    for seed in 0..triangles.len() {
+        if flip[seed].is_some() {
+            continue;
+        }
+        flip[seed] = Some(false);
+        let mut component = vec![seed];
+        let mut queue = VecDeque::from([seed]);
+        while let Some(t) = queue.pop_front() {
+            let flip_t = flip[t] == Some(true);
+            for &(n, same) in &neighbours[t] {
+                if flip[n].is_none() {
+                    flip[n] = Some(flip_t != same);
+                    component.push(n);
+                    queue.push_back(n);
+                }
+            }
+        }
+
+        // Signed volume about the piece's centroid, with the flips so far.
+        let corners = |t: usize| {
+            let [a, b, c] = [0, 1, 2].map(|k| mesh.indices[triangles[t].0 * 3 + k]);
+            if flip[t] == Some(true) {
+                [a, c, b]
+            } else {
+                [a, b, c]
+            }
+        };
+        let count = (component.len() * 3) as f32;
+        let centroid = component
+            .iter()
+            .flat_map(|&t| corners(t))
+            .fold([0.0; 3], |sum, i| {
+                math::add(sum, math::scale(mesh.position(i), 1.0 / count))
+            });
+        let volume: f32 = component
+            .iter()
+            .map(|&t| {
+                let [a, b, c] = corners(t).map(|i| math::sub(mesh.position(i), centroid));
+                math::dot(a, math::cross(b, c))
+            })
+            .sum();
+        let flips = component.iter().filter(|&&t| flip[t] == Some(true)).count();
+        let turn_over = if volume.abs() > 1e-6 * bounds_volume(mesh, &component, &corners) {
+            volume > 0.0
+        } else {
+            flips * 2 > component.len()
+        };
+        for &t in &component {
+            let value = flip[t] == Some(true);
+            flip[t] = Some(value != turn_over);
+        }
+        flipped += component.iter().filter(|&&t| flip[t] == Some(true)).count();
+    }
+
+    for (t, (index, _)) in triangles.iter().enumerate() {
+        if flip[t] == Some(true) {
+            mesh.indices.swap(index * 3 + 1, index * 3 + 2);
+        }
+    }
+    flipped
+}
+
+/// Volume of the box around a piece, to tell pieces with a real inside from
+/// flat ones whose signed volume is rounding noise.
+fn bounds_volume(mesh: &Mesh, component: &[usize], corners: &impl Fn(usize) -> [u32; 3]) -> f32 {
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for p in component
+        .iter()
+        .flat_map(|&t| corners(t))
+        .map(|i| mesh.position(i))
+    {
+        for k in 0..3 {
+            min[k] = min[k].min(p[k]);
+            max[k] = max[k].max(p[k]);
+        }
+    }
+    let size = math::sub(max, min);
+    size[0] * size[1] * size[2]
+}
+
+/// Closes every hole with a flat cap of new vertices, triangulated in the
+/// plane that best fits the hole's border. Returns how many holes closed.
+pub fn fill_holes(mesh: &mut Mesh) -> usize {
+    let report = validate(mesh);
+    let has_uvs = !mesh.uvs.is_empty();
+    let mut filled = 0;
+
+    for ring in &report.boundary_loops {
+        if ring.len() < 3 {
+            continue;
+        }
+        // The border runs against the winding of the missing faces, so the
+        // cap's front (Newell's normal of the border) faces outwards.
+        let front = (0..ring.len()).fold([0.0; 3], |sum, k| {
+            math::add(sum, math::cross(ring[k], ring[(k + 1) % ring.len()]))
+        });
+        if math::length(front) == 0.0 {
+            continue;
+        }
+        let normal = math::normalize(front);
+        let helper = if normal[0].abs() < 0.9 {
+            [1.0, 0.0, 0.0]
+        } else {
+            [0.0, 1.0, 0.0]
+        };
+        let u = math::normalize(math::cross(helper, normal));
+        let v = math::cross(normal, u);
+        let outline: Vec<[f32; 2]> = ring
+            .iter()
+            .map(|&p| [math::dot(p, u), math::dot(p, v)])
+            .collect();
+        // Borders that fold over themselves in the plane fall back to a fan.
+        let triangles = triangulate::triangulate(&outline, &[]).unwrap_or_else(|_| {
+            let fan = (1..ring.len() as u32 - 1).map(|k| [0, k, k + 1]);
+            if ring_area(&outline) >= 0.0 {
+                fan.collect()
+            } else {
+                fan.map(|[a, b, c]| [a, c, b]).collect()
+            }
+        });
+
+        let base = mesh.positions.len() as u32;
+        for &[x, y, z] in ring {
+            mesh.positions.push(Vertex {
+                position: (x, y, z),
+            });
+            let [nx, ny, nz] = normal;
+            mesh.normals.push(Normal {
+                normal: (nx, ny, nz),
+            });
+            if has_uvs {
+                mesh.uvs.push(TexCoord {
+                    tex_coords: (0.0, 0.0),
+                });
+            }
+        }
+        // Counter-clockwise in the (u, v) plane faces towards `normal` in
+        // the opposite winding to the mesh's, hence the swap.
+        for [a, b, c] in triangles {
+            mesh.indices
+                .extend_from_slice(&[base + a, base + c, base + b]);
+        }
+        filled += 1;
+    }
+    filled
+}
+
+fn ring_area(points: &[[f32; 2]]) -> f32 {
+    (0..points.len())
+        .map(|k| {
+            let (a, b) = (points[k], points[(k + 1) % points.len()]);
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    #[test]
+    fn a_cube_is_clean() {
+        let report = validate(&primitives::cube(1.0, 1));
+        assert!(report.is_clean(), "{}", report);
+        assert_eq!((report.vertices, report.triangles), (24, 12));
+    }
+
+    #[test]
+    fn missing_face_is_a_hole_that_fill_holes_closes() {
+        let mut cube = primitives::cube(1.0, 1);
+        cube.indices.truncate(cube.indices.len() - 6);
+        let report = validate(&cube);
+        assert_eq!(report.boundary_loops.len(), 1);
+        assert_eq!(report.boundary_loops[0].len(), 4);
+        // The square's corners are all at half a unit from the centre.
+        for corner in &report.boundary_loops[0] {
+            assert!(corner.iter().all(|c| c.abs() == 0.5));
+        }
+
+        assert_eq!(fill_holes(&mut cube), 1);
+        assert_eq!(cube.triangle_count(), 12);
+        let report = validate(&cube);
+        assert!(report.boundary_loops.is_empty());
+        assert!(report.inconsistent_edges.is_empty());
+    }
+
+    #[test]
+    fn flipped_triangle_is_inconsistent_until_reoriented() {
+        let cube = primitives::cube(1.0, 1);
+        let mut mesh = cube.clone();
+        mesh.indices.swap(1, 2);
+        // Each of its three edges runs the same way as its neighbour's.
+        assert_eq!(validate(&mesh).inconsistent_edges.len(), 3);
+
+        assert_eq!(reorient(&mut mesh), 1);
+        assert_eq!(mesh.indices, cube.indices);
+        assert!(validate(&mesh).is_clean());
+    }
+
+    #[test]
+    fn broken_triangles_are_reported_and_removed() {
+        let cube = primitives::cube(1.0, 1);
+        let mut mesh = cube.clone();
+        let [a, b, c] = [0, 1, 2].map(|k| cube.indices[k]);
+        // A repeat of the first triangle wound the other way, one with an
+        // index past the end and one with a repeated corner.
+        mesh.indices
+            .extend_from_slice(&[a, c, b, a, b, 999, a, a, b]);
+        let report = validate(&mesh);
+        assert_eq!(report.duplicate, [12]);
+        assert_eq!(report.out_of_range, [13]);
+        assert_eq!(report.degenerate, [14]);
+
+        assert_eq!(remove_degenerates(&mut mesh), 3);
+        assert_eq!(mesh.indices, cube.indices);
+    }
+
+    #[test]
+    fn triangles_collapsed_by_welding_are_removed() {
+        let mut mesh = primitives::cube(1.0, 1);
+        // A sliver from a corner to a copy of it a hair's breadth away,
+        // which only the tolerant weld merges.
+        let corner = mesh.indices[0] as usize;
+        let (x, y, z) = mesh.positions[corner].position;
+        mesh.positions.push(Vertex {
+            position: (x + 1e-6, y, z),
+        });
+        mesh.normals.push(mesh.normals[corner]);
+        mesh.uvs.push(mesh.uvs[corner]);
+        let copy = mesh.positions.len() as u32 - 1;
+        mesh.indices
+            .extend_from_slice(&[corner as u32, copy, mesh.indices[1]]);
+        assert!(validate(&mesh).degenerate.is_empty());
+
+        Repair::default().apply(&mut mesh);
+        assert_eq!(mesh.triangle_count(), 12);
+        let report = validate(&mesh);
+        assert!(report.is_clean(), "{}", report);
+    }
+}
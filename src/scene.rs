@@ -0,0 +1,657 @@
+use crate::bounds::Aabb;
+use crate::camera::Camera;
+use crate::gltf;
+use crate::math::{self, Mat4, Vec3};
+use crate::mesh::Mesh;
+use crate::meshfile;
+use crate::modeling::Shape;
+use crate::obj;
+use crate::optimize::{self, CacheStats};
+use crate::primitives::Primitive;
+use crate::repair::Repair;
+use crate::simplify::Lod;
+use crate::subdivide::Subdivision;
+use crate::teapot_patches;
+use crate::winding::{self, WindingMode};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Everything the viewer draws, as described by a `.ron` or `.json` scene file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Scene {
+    #[serde(default = "default_clear_color")]
+    pub clear_color: [f32; 4],
+    #[serde(default)]
+    pub camera: Camera,
+    #[serde(default)]
+    pub lights: Vec<Light>,
+    #[serde(default)]
+    pub materials: Vec<Material>,
+    #[serde(default)]
+    pub meshes: Vec<MeshDesc>,
+    #[serde(default)]
+    pub nodes: Vec<Node>,
+}
+
+fn default_clear_color() -> [f32; 4] {
+    [0.0, 0.0, 1.0, 1.0]
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MeshDesc {
+    pub name: String,
+    pub source: MeshSource,
+    /// Fixes broken topology after loading, before anything else uses it.
+    #[serde(default)]
+    pub repair: Option<Repair>,
+    /// How the winding is found, and so which side of each triangle is culled.
+    #[serde(default)]
+    pub winding: WindingMode,
+    /// Smooths the mesh by subdividing it after loading.
+    #[serde(default)]
+    pub subdivision: Option<Subdivision>,
+    /// Reorders triangles and vertices for faster drawing once loaded, so the
+    /// triangle and vertex numbers picking reports are the reordered ones.
+    /// Turn it off to see the source's numbering.
+    #[serde(default = "default_optimize")]
+    pub optimize: bool,
+    /// Builds simplified versions of the mesh, drawn when it covers few pixels.
+    #[serde(default)]
+    pub lod: Option<Lod>,
+}
+
+fn default_optimize() -> bool {
+    true
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum MeshSource {
+    /// The built-in teapot.
+    Teapot,
+    /// Newell's 32 Bezier patches, each split into `subdivisions²` quads;
+    /// at most `teapot_patches::MAX_SUBDIVISIONS`.
+    BezierTeapot { subdivisions: u32 },
+    /// A Wavefront OBJ file, relative to the scene file. It is parsed once
+    /// and cached beside it in the binary format.
+    Obj(PathBuf),
+    /// A mesh in the binary format, relative to the scene file.
+    Binary(PathBuf),
+    /// Mesh `mesh` of a `.gltf` or `.glb` file, relative to the scene file.
+    Gltf { path: PathBuf, mesh: usize },
+    /// A procedurally generated shape.
+    Primitive(Primitive),
+    /// A lathed, extruded or swept profile.
+    Shape(Shape),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Material {
+    pub name: String,
+    pub dark_color: Vec3,
+    pub regular_color: Vec3,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Light {
+    pub kind: LightKind,
+    #[serde(default = "default_light_color")]
+    pub color: Vec3,
+    #[serde(default = "default_light_intensity")]
+    pub intensity: f32,
+}
+
+fn default_light_color() -> Vec3 {
+    [1.0, 1.0, 1.0]
+}
+
+fn default_light_intensity() -> f32 {
+    1.0
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum LightKind {
+    /// Light arriving from infinitely far away; `direction` points towards the light.
+    Directional {
+        direction: Vec3,
+    },
+    Point {
+        position: Vec3,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Node {
+    pub name: String,
+    /// Name of an entry in `Scene::meshes`.
+    pub mesh: String,
+    /// Name of an entry in `Scene::materials`; the first material is used when unset.
+    #[serde(default)]
+    pub material: Option<String>,
+    #[serde(default)]
+    pub transform: Transform,
+    /// When set, the mesh is drawn once per instance in a single instanced
+    /// draw call, each instance placed by its own transform inside `transform`.
+    #[serde(default)]
+    pub instances: Option<Instances>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Instances {
+    List(Vec<Instance>),
+    /// `rows × columns` copies centred on the origin of the XZ plane, coloured
+    /// by a gradient across the grid.
+    Grid {
+        rows: u32,
+        columns: u32,
+        spacing: f32,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Instance {
+    #[serde(default)]
+    pub transform: Transform,
+    #[serde(default = "default_instance_color")]
+    pub color: Vec3,
+}
+
+/// Grids with more instances than this are refused, as is any grid too large
+/// to count.
+const MAX_GRID_INSTANCES: u32 = 1 << 20;
+
+fn default_instance_color() -> Vec3 {
+    [1.0, 1.0, 1.0]
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Transform {
+    #[serde(default)]
+    pub translation: Vec3,
+    /// Euler angles in degrees.
+    #[serde(default)]
+    pub rotation: Vec3,
+    #[serde(default = "default_scale")]
+    pub scale: Vec3,
+}
+
+fn default_scale() -> Vec3 {
+    [1.0, 1.0, 1.0]
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform {
+            translation: [0.0; 3],
+            rotation: [0.0; 3],
+            scale: default_scale(),
+        }
+    }
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Mat4 {
+        let rotation_scale = math::mul(&math::rotation(self.rotation), &math::scaling(self.scale));
+        math::mul(&math::translation(self.translation), &rotation_scale)
+    }
+}
+
+impl Instances {
+    /// Expands the description into one `Instance` per drawn copy.
+    pub fn expand(&self) -> Vec<Instance> {
+        match self {
+            Instances::List(instances) => instances.clone(),
+            Instances::Grid {
+                rows,
+                columns,
+                spacing,
+            } => {
+                let x_offset = (*columns as f32 - 1.0) * spacing / 2.0;
+                let z_offset = (*rows as f32 - 1.0) * spacing / 2.0;
+                let mut instances = Vec::with_capacity(self.len());
+
+                for row in 0..*rows {
+                    for column in 0..*columns {
+                        let u = column as f32 / (*columns).max(2).saturating_sub(1) as f32;
+                        let v = row as f32 / (*rows).max(2).saturating_sub(1) as f32;
+                        instances.push(Instance {
+                            transform: Transform {
+                                translation: [
+                                    column as f32 * spacing - x_offset,
+                                    0.0,
+                                    row as f32 * spacing - z_offset,
+                                ],
+                                ..Transform::default()
+                            },
+                            color: [u, v, 1.0 - u],
+                        });
+                    }
+                }
+                instances
+            }
+        }
+    }
+
+    /// The number of instances; `usize::MAX` for a grid too large to count,
+    /// which `Scene::validate` refuses.
+    pub fn len(&self) -> usize {
+        match self {
+            Instances::List(instances) => instances.len(),
+            Instances::Grid { rows, columns, .. } => rows
+                .checked_mul(*columns)
+                .map_or(usize::MAX, |n| n as usize),
+        }
+    }
+}
+
+impl Light {
+    /// The light as a `vec4`: xyz is the direction (w = 0) or position (w = 1).
+    pub fn vector(&self) -> [f32; 4] {
+        match self.kind {
+            LightKind::Directional {
+                direction: [x, y, z],
+            } => [x, y, z, 0.0],
+            LightKind::Point {
+                position: [x, y, z],
+            } => [x, y, z, 1.0],
+        }
+    }
+
+    pub fn radiance(&self) -> Vec3 {
+        math::scale(self.color, self.intensity)
+    }
+}
+
+/// What `Scene::load_mesh` did to a mesh on top of loading it, for the
+/// caller to report.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MeshLog {
+    /// Whether the mesh was found wound counter-clockwise and flipped.
+    pub flipped: bool,
+    /// Vertex cache efficiency before and after optimizing.
+    pub optimized: Option<(CacheStats, CacheStats)>,
+}
+
+impl MeshLog {
+    /// Prints a line for each change made to the mesh called `name`.
+    pub fn print(&self, name: &str) {
+        if self.flipped {
+            println!("mesh `{}`: flipped to clockwise winding", name);
+        }
+        if let Some((before, after)) = self.optimized {
+            println!(
+                "mesh `{}`: {} before optimizing, {} after",
+                name, before, after
+            );
+        }
+    }
+}
+
+enum Format {
+    Ron,
+    Json,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Result<Format, String> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("ron") => Ok(Format::Ron),
+            Some("json") => Ok(Format::Json),
+            _ => Err(format!(
+                "{}: unknown scene format, expected .ron or .json",
+                path.display()
+            )),
+        }
+    }
+}
+
+impl Default for Scene {
+    /// The single red teapot the viewer has always shown, framed by the
+    /// camera when loaded.
+    fn default() -> Scene {
+        Scene {
+            clear_color: default_clear_color(),
+            camera: Camera::default(),
+            lights: vec![Light {
+                kind: LightKind::Directional {
+                    direction: [-1.0, 0.4, 0.9],
+                },
+                color: default_light_color(),
+                intensity: default_light_intensity(),
+            }],
+            materials: vec![Material {
+                name: "red".to_owned(),
+                dark_color: [0.5, 0.0, 0.0],
+                regular_color: [1.0, 0.0, 0.0],
+            }],
+            meshes: vec![MeshDesc {
+                name: "teapot".to_owned(),
+                source: MeshSource::Teapot,
+                repair: None,
+                winding: WindingMode::default(),
+                subdivision: None,
+                optimize: default_optimize(),
+                lod: None,
+            }],
+            nodes: vec![Node {
+                name: "teapot".to_owned(),
+                mesh: "teapot".to_owned(),
+                material: None,
+                transform: Transform::default(),
+                instances: None,
+            }],
+        }
+    }
+}
+
+impl Scene {
+    pub fn load(path: &Path) -> Result<Scene, String> {
+        let format = Format::from_path(path)?;
+        let source = fs::read_to_string(path)
+            .map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+
+        let scene: Scene = match format {
+            Format::Ron => ron::from_str(&source).map_err(|err| err.to_string()),
+            Format::Json => serde_json::from_str(&source).map_err(|err| err.to_string()),
+        }
+        .map_err(|err| format!("{}: {}", path.display(), err))?;
+
+        scene.validate()?;
+        Ok(scene)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = match Format::from_path(path)? {
+            Format::Ron => ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+                .map_err(|err| err.to_string())?,
+            Format::Json => serde_json::to_string_pretty(self).map_err(|err| err.to_string())?,
+        };
+
+        fs::write(path, text).map_err(|err| format!("failed to write {}: {}", path.display(), err))
+    }
+
+    /// Checks that every node refers to a mesh and material that exist, and
+    /// that instanced nodes have at least one instance and not too many.
+    pub fn validate(&self) -> Result<(), String> {
+        for node in &self.nodes {
+            if node.instances.as_ref().is_some_and(|i| i.len() == 0) {
+                return Err(format!("node `{}` has no instances", node.name));
+            }
+            if let Some(Instances::Grid { rows, columns, .. }) = &node.instances {
+                if rows
+                    .checked_mul(*columns)
+                    .is_none_or(|count| count > MAX_GRID_INSTANCES)
+                {
+                    return Err(format!(
+                        "node `{}`: a {} × {} grid is more than the {} instances allowed",
+                        node.name, rows, columns, MAX_GRID_INSTANCES
+                    ));
+                }
+            }
+            if self.mesh_index(&node.mesh).is_none() {
+                return Err(format!(
+                    "node `{}` refers to unknown mesh `{}`",
+                    node.name, node.mesh
+                ));
+            }
+            if let Some(material) = &node.material {
+                if !self.materials.iter().any(|m| &m.name == material) {
+                    return Err(format!(
+                        "node `{}` refers to unknown material `{}`",
+                        node.name, material
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn mesh_index(&self, name: &str) -> Option<usize> {
+        self.meshes.iter().position(|m| m.name == name)
+    }
+
+    pub fn material(&self, node: &Node) -> Option<&Material> {
+        match &node.material {
+            Some(name) => self.materials.iter().find(|m| &m.name == name),
+            None => self.materials.first(),
+        }
+    }
+
+    /// World-space bounds of one node, covering all of its instances.
+    /// `meshes` is parallel to `self.meshes`.
+    pub fn node_bounds(&self, index: usize, meshes: &[Mesh]) -> Aabb {
+        let node = &self.nodes[index];
+        let local = match self.mesh_index(&node.mesh) {
+            Some(mesh) => meshes[mesh].bounds(),
+            None => return Aabb::empty(),
+        };
+        let model = node.transform.matrix();
+
+        match &node.instances {
+            Some(instances) => instances
+                .expand()
+                .iter()
+                .map(|instance| local.transform(&math::mul(&model, &instance.transform.matrix())))
+                .fold(Aabb::empty(), |all, bounds| all.union(&bounds)),
+            None => local.transform(&model),
+        }
+    }
+
+    pub fn bounds(&self, meshes: &[Mesh]) -> Aabb {
+        (0..self.nodes.len())
+            .map(|index| self.node_bounds(index, meshes))
+            .fold(Aabb::empty(), |all, bounds| all.union(&bounds))
+    }
+
+    /// Loads the geometry for every entry of `meshes`, in order, with what
+    /// was done to each. Relative paths are resolved against `base_dir`.
+    pub fn load_meshes(&self, base_dir: &Path) -> Result<(Vec<Mesh>, Vec<MeshLog>), String> {
+        (0..self.meshes.len())
+            .map(|index| self.load_mesh(index, base_dir))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|loaded| loaded.into_iter().unzip())
+    }
+
+    /// Loads the mesh at `index` in `meshes`, repaired, subdivided and
+    /// optimized as it asks to be.
+    pub fn load_mesh(&self, index: usize, base_dir: &Path) -> Result<(Mesh, MeshLog), String> {
+        let desc = &self.meshes[index];
+        let mut mesh = match &desc.source {
+            MeshSource::Teapot => Mesh::teapot(),
+            MeshSource::BezierTeapot { subdivisions } => teapot_patches::tessellate(*subdivisions)
+                .map_err(|err| format!("mesh `{}`: {}", desc.name, err))?,
+            MeshSource::Obj(path) => meshfile::cached(&base_dir.join(path), obj::load)?,
+            MeshSource::Binary(path) => meshfile::load(&base_dir.join(path))?,
+            MeshSource::Gltf { path, mesh } => gltf::load_mesh(&base_dir.join(path), *mesh)?,
+            MeshSource::Primitive(primitive) => primitive.build(),
+            MeshSource::Shape(shape) => shape
+                .build()
+                .map_err(|err| format!("mesh `{}`: {}", desc.name, err))?,
+        };
+        let mut log = MeshLog::default();
+        if let Some(repair) = &desc.repair {
+            repair.apply(&mut mesh);
+        }
+        if desc.winding == WindingMode::Flip {
+            log.flipped = winding::orient(&mut mesh);
+        }
+        if let Some(subdivision) = &desc.subdivision {
+            mesh = subdivision.apply(&mesh);
+        }
+        if desc.optimize {
+            let before = optimize::analyze(&mesh.indices, mesh.positions.len());
+            mesh = optimize::optimize(&mesh);
+            let after = optimize::analyze(&mesh.indices, mesh.positions.len());
+            log.optimized = Some((before, after));
+        }
+        Ok((mesh, log))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The default scene with a second mesh and material, and a node drawn
+    /// from a list of instances and one from a grid.
+    fn scene() -> Scene {
+        let mut scene = Scene::default();
+        scene.materials.push(Material {
+            name: "green".to_owned(),
+            dark_color: [0.0, 0.5, 0.0],
+            regular_color: [0.0, 1.0, 0.0],
+        });
+        scene.meshes.push(MeshDesc {
+            name: "cube".to_owned(),
+            source: MeshSource::Primitive(Primitive::Cube {
+                size: 1.0,
+                segments: 2,
+            }),
+            repair: None,
+            winding: WindingMode::default(),
+            subdivision: None,
+            optimize: false,
+            lod: None,
+        });
+        let node = |name: &str, instances| Node {
+            name: name.to_owned(),
+            mesh: "cube".to_owned(),
+            material: Some("green".to_owned()),
+            transform: Transform {
+                translation: [1.0, 2.0, 3.0],
+                rotation: [0.0, 45.0, 0.0],
+                scale: [2.0, 2.0, 2.0],
+            },
+            instances: Some(instances),
+        };
+        scene.nodes.push(node(
+            "pair",
+            Instances::List(vec![
+                Instance {
+                    transform: Transform::default(),
+                    color: [1.0, 0.0, 0.0],
+                },
+                Instance {
+                    transform: Transform {
+                        translation: [3.0, 0.0, 0.0],
+                        ..Transform::default()
+                    },
+                    color: default_instance_color(),
+                },
+            ]),
+        ));
+        scene.nodes.push(node(
+            "grid",
+            Instances::Grid {
+                rows: 2,
+                columns: 3,
+                spacing: 1.5,
+            },
+        ));
+        scene
+    }
+
+    fn ron(scene: &Scene) -> String {
+        ron::ser::to_string(scene).unwrap()
+    }
+
+    #[test]
+    fn scenes_round_trip() {
+        let scene = scene();
+        scene.validate().unwrap();
+        for extension in ["ron", "json"] {
+            let path = std::env::temp_dir().join(format!(
+                "rusty_glad_scene_{}.{}",
+                std::process::id(),
+                extension
+            ));
+            scene.save(&path).unwrap();
+            let loaded = Scene::load(&path).unwrap();
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(ron(&loaded), ron(&scene), "{}", extension);
+            assert_eq!(loaded.nodes[2].instances.as_ref().unwrap().len(), 6);
+            let (meshes, _) = loaded.load_meshes(Path::new(".")).unwrap();
+            assert_eq!(meshes.len(), 2);
+            assert_eq!(meshes[1].triangle_count(), 48);
+        }
+    }
+
+    #[test]
+    fn unknown_formats_are_refused() {
+        let path = std::env::temp_dir().join("scene.toml");
+        assert!(scene().save(&path).is_err());
+        assert!(Scene::load(&path).is_err());
+    }
+
+    #[test]
+    fn broken_references_are_refused() {
+        let mut unknown_mesh = scene();
+        unknown_mesh.nodes[1].mesh = "sphere".to_owned();
+        let err = unknown_mesh.validate().unwrap_err();
+        assert!(err.contains("unknown mesh `sphere`"), "{}", err);
+
+        let mut unknown_material = scene();
+        unknown_material.nodes[1].material = Some("blue".to_owned());
+        let err = unknown_material.validate().unwrap_err();
+        assert!(err.contains("unknown material `blue`"), "{}", err);
+    }
+
+    #[test]
+    fn empty_instance_lists_are_refused() {
+        let empty = [
+            Instances::List(Vec::new()),
+            Instances::Grid {
+                rows: 0,
+                columns: 3,
+                spacing: 1.0,
+            },
+        ];
+        for instances in empty {
+            let mut scene = scene();
+            scene.nodes[1].instances = Some(instances);
+            let err = scene.validate().unwrap_err();
+            assert_eq!(err, "node `pair` has no instances");
+        }
+    }
+
+    #[test]
+    fn oversized_grids_are_refused() {
+        for (rows, columns) in [(65536, 65536), (u32::MAX, 2), (1025, 1024)] {
+            let mut scene = scene();
+            scene.nodes[2].instances = Some(Instances::Grid {
+                rows,
+                columns,
+                spacing: 1.0,
+            });
+            let err = scene.validate().unwrap_err();
+            assert!(err.starts_with("node `grid`: a "), "{}", err);
+        }
+        let largest = Instances::Grid {
+            rows: 1024,
+            columns: 1024,
+            spacing: 1.0,
+        };
+        assert_eq!(largest.len(), MAX_GRID_INSTANCES as usize);
+        let mut scene = scene();
+        scene.nodes[2].instances = Some(largest);
+        scene.validate().unwrap();
+    }
+
+    #[test]
+    fn invalid_files_are_refused_on_load() {
+        let path =
+            std::env::temp_dir().join(format!("rusty_glad_invalid_{}.ron", std::process::id()));
+        let mut scene = scene();
+        scene.nodes[0].mesh = "sphere".to_owned();
+        scene.save(&path).unwrap();
+        assert!(Scene::load(&path).unwrap_err().contains("unknown mesh"));
+
+        fs::write(&path, "(nodes: [(name: 1)])").unwrap();
+        let err = Scene::load(&path).unwrap_err();
+        assert!(err.starts_with(&path.display().to_string()), "{}", err);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,522 @@
+use crate::math::{self, Vec3};
+use crate::mesh::Mesh;
+use crate::optimize;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Weight of the planes that keep border and attribute-seam edges in place,
+/// relative to the area-weighted surface planes.
+const FEATURE_WEIGHT: f64 = 100.0;
+
+/// Reduces `mesh` to at most `target_triangles` triangles by quadric error
+/// edge collapses (Garland and Heckbert). See `simplify_indices`.
+pub fn simplify(mesh: &Mesh, target_triangles: usize) -> Mesh {
+    mesh.extract(&simplify_indices(mesh, &mesh.indices, target_triangles))
+}
+
+/// Simplifies the triangles in `indices` (into `mesh`'s vertices) down to
+/// at most `target_triangles`, stopping early when no collapse is left that
+/// keeps the surface intact.
+///
+/// Every collapse moves a vertex onto one of its neighbours, so the result
+/// indexes the same vertex arrays: normals and UVs are kept as they are and
+/// all levels of a LOD chain can share one vertex buffer. Vertices with equal
+/// positions are treated as one, so UV and normal seams neither crack nor
+/// drift, and border edges only collapse along the border.
+pub fn simplify_indices(mesh: &Mesh, indices: &[u32], target_triangles: usize) -> Vec<u32> {
+    if indices.len() / 3 <= target_triangles {
+        return indices.to_vec();
+    }
+
+    let mut state = Collapser::new(mesh, indices);
+    let mut heap = BinaryHeap::new();
+    for group in 0..state.positions.len() as u32 {
+        state.push_collapses(group, &mut heap);
+    }
+
+    while state.triangle_count > target_triangles {
+        let collapse = match heap.pop() {
+            Some(collapse) => collapse,
+            None => break,
+        };
+        if state.is_current(&collapse) && state.try_collapse(collapse.from, collapse.to) {
+            state.push_collapses(collapse.to, &mut heap);
+        }
+    }
+
+    state
+        .triangles
+        .iter()
+        .zip(&state.alive)
+        .filter(|(_, &alive)| alive)
+        .flat_map(|(triangle, _)| *triangle)
+        .collect()
+}
+
+/// How a mesh is reduced into levels of detail, as set per mesh in scene
+/// files.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lod {
+    /// Number of levels, including the full mesh.
+    #[serde(default = "default_levels")]
+    pub levels: u32,
+    /// Triangle count of each level relative to the one before.
+    #[serde(default = "default_ratio")]
+    pub ratio: f32,
+    /// On-screen diameter, in pixels, from which the full mesh is drawn.
+    #[serde(default = "default_pixels")]
+    pub pixels: f32,
+}
+
+fn default_levels() -> u32 {
+    4
+}
+
+fn default_ratio() -> f32 {
+    0.5
+}
+
+fn default_pixels() -> f32 {
+    300.0
+}
+
+impl Default for Lod {
+    fn default() -> Lod {
+        Lod {
+            levels: default_levels(),
+            ratio: default_ratio(),
+            pixels: default_pixels(),
+        }
+    }
+}
+
+impl Lod {
+    /// Index lists for every level, starting with the mesh's own. Each level
+    /// is simplified from the one before and reordered for the vertex cache;
+    /// the chain ends early once a level cannot be reduced any further.
+    pub fn chain(&self, mesh: &Mesh) -> Vec<Vec<u32>> {
+        let ratio = self.ratio.clamp(0.01, 0.99);
+        let mut chain = vec![mesh.indices.clone()];
+
+        for _ in 1..self.levels {
+            let previous = chain.last().map_or(0, |indices| indices.len() / 3);
+            let target = (previous as f32 * ratio) as usize;
+            let indices = simplify_indices(mesh, &chain[chain.len() - 1], target);
+            if indices.len() / 3 >= previous || indices.is_empty() {
+                break;
+            }
+            chain.push(optimize::optimize_vertex_cache(
+                &indices,
+                mesh.positions.len(),
+            ));
+        }
+        chain
+    }
+
+    /// The level to draw for something `size` pixels across, out of `count`
+    /// levels. Coarser levels take over as the on-screen area, and with it
+    /// the useful triangle count, drops by `ratio`.
+    pub fn level(&self, size: f32, count: usize) -> usize {
+        if size >= self.pixels || count <= 1 {
+            return 0;
+        }
+        let area = (size.max(f32::EPSILON) / self.pixels).powi(2);
+        let level = area.ln() / self.ratio.clamp(0.01, 0.99).ln();
+        (level as usize).min(count - 1)
+    }
+}
+
+/// Symmetric 4×4 error quadric, stored as its upper triangle.
+#[derive(Copy, Clone, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Squared distance to the plane `n·p + d = 0`, scaled by `weight`.
+    fn plane(normal: Vec3, point: Vec3, weight: f64) -> Quadric {
+        let [a, b, c] = normal.map(f64::from);
+        let d = -(a * point[0] as f64 + b * point[1] as f64 + c * point[2] as f64);
+        Quadric([
+            a * a,
+            a * b,
+            a * c,
+            a * d,
+            b * b,
+            b * c,
+            b * d,
+            c * c,
+            c * d,
+            d * d,
+        ])
+        .scaled(weight)
+    }
+
+    fn scaled(self, weight: f64) -> Quadric {
+        Quadric(self.0.map(|q| q * weight))
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (q, o) in self.0.iter_mut().zip(other.0) {
+            *q += o;
+        }
+    }
+
+    fn error(&self, p: Vec3) -> f64 {
+        let [x, y, z] = p.map(f64::from);
+        let q = &self.0;
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+/// A candidate move of every vertex at position `from` onto position `to`.
+/// `stamps` record both positions' versions when the cost was computed.
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    stamps: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Collapse) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Collapse) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    /// Reversed, so `BinaryHeap` pops the cheapest collapse first.
+    fn cmp(&self, other: &Collapse) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Simplification state. Vertices ("wedges") sharing a position form a
+/// group; collapses act on groups so that seams stay closed.
+struct Collapser {
+    /// Position of each group.
+    positions: Vec<Vec3>,
+    /// Group of each vertex of the mesh.
+    group: Vec<u32>,
+    quadrics: Vec<Quadric>,
+    /// Bumped whenever a group's quadric or neighbourhood changes.
+    versions: Vec<u32>,
+    /// Triangles around each group; may include dead ones.
+    incident: Vec<Vec<u32>>,
+    triangles: Vec<[u32; 3]>,
+    alive: Vec<bool>,
+    triangle_count: usize,
+}
+
+impl Collapser {
+    fn new(mesh: &Mesh, indices: &[u32]) -> Collapser {
+        let (group, positions) = mesh.weld();
+
+        let triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .filter(|t| {
+                let [a, b, c] = t.map(|i| group[i as usize]);
+                a != b && b != c && c != a
+            })
+            .collect();
+
+        let mut incident = vec![Vec::new(); positions.len()];
+        for (i, triangle) in triangles.iter().enumerate() {
+            for &wedge in triangle {
+                incident[group[wedge as usize] as usize].push(i as u32);
+            }
+        }
+
+        let mut state = Collapser {
+            versions: vec![0; positions.len()],
+            quadrics: vec![Quadric::default(); positions.len()],
+            positions,
+            group,
+            incident,
+            alive: vec![true; triangles.len()],
+            triangle_count: triangles.len(),
+            triangles,
+        };
+
+        for t in 0..state.triangles.len() {
+            let groups = state.groups(t as u32);
+            let [a, b, c] = groups.map(|g| state.positions[g as usize]);
+            let normal = math::cross(math::sub(b, a), math::sub(c, a));
+            let area = math::length(normal) as f64 / 2.0;
+            let normal = math::normalize(normal);
+
+            let quadric = Quadric::plane(normal, a, area);
+            for g in groups {
+                state.quadrics[g as usize].add(&quadric);
+            }
+
+            // Planes through feature edges, perpendicular to the surface,
+            // hold them in place.
+            for k in 0..3 {
+                let (from, to) = (groups[k], groups[(k + 1) % 3]);
+                if !state.is_feature_edge(from, to) {
+                    continue;
+                }
+                let (p, q) = (state.positions[from as usize], state.positions[to as usize]);
+                let edge = math::sub(q, p);
+                let length = math::dot(edge, edge) as f64;
+                let normal = math::normalize(math::cross(edge, normal));
+                let quadric = Quadric::plane(normal, p, FEATURE_WEIGHT * length);
+                state.quadrics[from as usize].add(&quadric);
+                state.quadrics[to as usize].add(&quadric);
+            }
+        }
+        state
+    }
+
+    fn groups(&self, triangle: u32) -> [u32; 3] {
+        self.triangles[triangle as usize].map(|w| self.group[w as usize])
+    }
+
+    fn live_incident(&self, group: u32) -> impl Iterator<Item = u32> + '_ {
+        self.incident[group as usize]
+            .iter()
+            .copied()
+            .filter(|&t| self.alive[t as usize])
+    }
+
+    /// Wedge of `group` in `triangle`, if the triangle touches it.
+    fn wedge(&self, triangle: u32, group: u32) -> Option<u32> {
+        self.triangles[triangle as usize]
+            .iter()
+            .copied()
+            .find(|&w| self.group[w as usize] == group)
+    }
+
+    fn neighbours(&self, group: u32) -> Vec<u32> {
+        let mut neighbours: Vec<u32> = self
+            .live_incident(group)
+            .flat_map(|t| self.groups(t))
+            .filter(|&g| g != group)
+            .collect();
+        neighbours.sort_unstable();
+        neighbours.dedup();
+        neighbours
+    }
+
+    /// Whether the edge `a`–`b` lies on a border, is shared by more than two
+    /// triangles, or separates different UVs or normals.
+    fn is_feature_edge(&self, a: u32, b: u32) -> bool {
+        let mut faces = 0;
+        let mut first = None;
+        let mut seam = false;
+        for t in self.live_incident(a) {
+            if let (Some(wa), Some(wb)) = (self.wedge(t, a), self.wedge(t, b)) {
+                faces += 1;
+                match first {
+                    None => first = Some((wa, wb)),
+                    Some(pair) => seam |= pair != (wa, wb),
+                }
+            }
+        }
+        faces != 2 || seam
+    }
+
+    /// Collapses of `from` the mesh topology allows: a group on a border or
+    /// seam may only slide along it (or, at its end, into it), and one where
+    /// several meet stays put.
+    fn allowed_targets(&self, from: u32) -> Vec<u32> {
+        let neighbours = self.neighbours(from);
+        let features: Vec<u32> = neighbours
+            .iter()
+            .copied()
+            .filter(|&n| self.is_feature_edge(from, n))
+            .collect();
+        match features.len() {
+            0 => neighbours,
+            1 | 2 => features,
+            _ => Vec::new(),
+        }
+    }
+
+    fn push_collapses(&self, group: u32, heap: &mut BinaryHeap<Collapse>) {
+        let mut push = |from: u32, to: u32| {
+            let mut quadric = self.quadrics[from as usize];
+            quadric.add(&self.quadrics[to as usize]);
+            heap.push(Collapse {
+                cost: quadric.error(self.positions[to as usize]),
+                from,
+                to,
+                stamps: (self.versions[from as usize], self.versions[to as usize]),
+            });
+        };
+
+        for to in self.allowed_targets(group) {
+            push(group, to);
+        }
+        for from in self.neighbours(group) {
+            if self.allowed_targets(from).contains(&group) {
+                push(from, group);
+            }
+        }
+    }
+
+    fn is_current(&self, collapse: &Collapse) -> bool {
+        collapse.stamps
+            == (
+                self.versions[collapse.from as usize],
+                self.versions[collapse.to as usize],
+            )
+    }
+
+    /// Moves group `from` onto group `to` unless that would fold a triangle
+    /// over, pinch the surface or leave a wedge without a counterpart.
+    fn try_collapse(&mut self, from: u32, to: u32) -> bool {
+        if !self.allowed_targets(from).contains(&to) {
+            return false;
+        }
+
+        let around: Vec<u32> = self.live_incident(from).collect();
+        let (shared, moved): (Vec<u32>, Vec<u32>) =
+            around.iter().partition(|&&t| self.wedge(t, to).is_some());
+        if shared.is_empty() {
+            return false;
+        }
+
+        // Each wedge of `from` becomes the wedge of `to` across the edge.
+        let wedges: HashMap<u32, u32> = shared
+            .iter()
+            .filter_map(|&t| Some((self.wedge(t, from)?, self.wedge(t, to)?)))
+            .collect();
+        if moved.iter().any(|&t| {
+            self.wedge(t, from)
+                .is_some_and(|w| !wedges.contains_key(&w))
+        }) {
+            return false;
+        }
+
+        // Link condition: the only neighbours the two groups may share are
+        // the opposite corners of the triangles on the collapsed edge.
+        let to_neighbours = self.neighbours(to);
+        let common = self
+            .neighbours(from)
+            .into_iter()
+            .filter(|n| to_neighbours.binary_search(n).is_ok())
+            .count();
+        if common != shared.len() {
+            return false;
+        }
+
+        let target = self.positions[to as usize];
+        for &t in &moved {
+            let before = self.groups(t).map(|g| self.positions[g as usize]);
+            let after = self.groups(t).map(|g| {
+                if g == from {
+                    target
+                } else {
+                    self.positions[g as usize]
+                }
+            });
+            let normal_before = math::cross(
+                math::sub(before[1], before[0]),
+                math::sub(before[2], before[0]),
+            );
+            let normal_after =
+                math::cross(math::sub(after[1], after[0]), math::sub(after[2], after[0]));
+            if math::dot(normal_before, normal_after) <= 0.0 {
+                return false;
+            }
+        }
+
+        for &t in &shared {
+            self.alive[t as usize] = false;
+            self.triangle_count -= 1;
+        }
+        for &t in &moved {
+            for wedge in self.triangles[t as usize].iter_mut() {
+                if let Some(&new) = wedges.get(wedge) {
+                    *wedge = new;
+                }
+            }
+            self.incident[to as usize].push(t);
+        }
+
+        let quadric = self.quadrics[from as usize];
+        self.quadrics[to as usize].add(&quadric);
+        self.incident[from as usize].clear();
+        let alive = &self.alive;
+        self.incident[to as usize].retain(|&t| alive[t as usize]);
+        self.versions[from as usize] += 1;
+        self.versions[to as usize] += 1;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    fn area(mesh: &Mesh, indices: &[u32]) -> f32 {
+        indices
+            .chunks_exact(3)
+            .map(|t| math::length(mesh.face_normal([t[0], t[1], t[2]])) / 2.0)
+            .sum()
+    }
+
+    #[test]
+    fn flat_grid_collapses_to_two_triangles() {
+        // Every collapse inside a plane, or along its straight border, costs
+        // nothing, so the 8×8 grid goes all the way down to one quad.
+        let plane = primitives::plane(2.0, 2.0, 8, 8);
+        assert_eq!(plane.triangle_count(), 128);
+        let indices = simplify_indices(&plane, &plane.indices, 2);
+        assert_eq!(indices.len(), 6);
+        assert!((area(&plane, &indices) - 4.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn subdivided_cube_keeps_its_shape() {
+        let cube = primitives::cube(2.0, 4);
+        let simplified = simplify(&cube, 12);
+        assert!(simplified.triangle_count() < cube.triangle_count() / 4);
+        // Faces stay flat and edges straight, so the surface is unchanged.
+        assert!((area(&simplified, &simplified.indices) - 24.0).abs() < 1e-4);
+        let bounds = simplified.bounds();
+        assert_eq!((bounds.min, bounds.max), ([-1.0; 3], [1.0; 3]));
+    }
+
+    #[test]
+    fn meshes_under_the_target_are_untouched() {
+        let cube = primitives::cube(1.0, 1);
+        assert_eq!(simplify_indices(&cube, &cube.indices, 12), cube.indices);
+    }
+
+    #[test]
+    fn lod_levels_follow_the_on_screen_area() {
+        let lod = Lod {
+            levels: 4,
+            ratio: 0.5,
+            pixels: 300.0,
+        };
+        assert_eq!(lod.level(600.0, 4), 0);
+        assert_eq!(lod.level(300.0, 4), 0);
+        // Half the area is one level down, a quarter two.
+        assert_eq!(lod.level(300.0 / 2.0f32.sqrt() - 0.01, 4), 1);
+        assert_eq!(lod.level(150.0 - 0.01, 4), 2);
+        assert_eq!(lod.level(1.0, 4), 3);
+        assert_eq!(lod.level(1.0, 1), 0);
+    }
+}
@@ -0,0 +1,655 @@
+use crate::halfedge::HalfEdgeMesh;
+use crate::math::{self, Vec3};
+use crate::mesh::{Mesh, Normal, TexCoord, Vertex};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Scheme {
+    /// Loop subdivision: every triangle splits into four.
+    Loop,
+    /// Catmull-Clark subdivision: every n-sided face splits into n quads.
+    /// Triangle meshes are first paired back into quads where possible.
+    CatmullClark,
+}
+
+/// How a mesh is smoothed after loading, as set per mesh in scene files.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Subdivision {
+    pub scheme: Scheme,
+    pub levels: u32,
+    /// Edges whose faces meet at more than this many degrees become creases.
+    #[serde(default)]
+    pub crease_angle: Option<f32>,
+    /// How many levels creases stay sharp for before they start to round
+    /// off; fractional values give soft creases. Open borders always stay
+    /// sharp.
+    #[serde(default = "default_sharpness")]
+    pub sharpness: f32,
+}
+
+fn default_sharpness() -> f32 {
+    f32::INFINITY
+}
+
+impl Subdivision {
+    pub fn new(scheme: Scheme, levels: u32) -> Subdivision {
+        Subdivision {
+            scheme,
+            levels,
+            crease_angle: None,
+            sharpness: default_sharpness(),
+        }
+    }
+
+    pub fn apply(&self, mesh: &Mesh) -> Mesh {
+        let mut surface = Surface::from_mesh(mesh, self.scheme == Scheme::CatmullClark);
+        if let Some(angle) = self.crease_angle {
+            surface.mark_creases(angle, self.sharpness);
+        }
+        for _ in 0..self.levels {
+            surface = match self.scheme {
+                Scheme::Loop => surface.loop_subdivide(),
+                Scheme::CatmullClark => surface.catmull_clark(),
+            };
+        }
+        surface.to_mesh()
+    }
+}
+
+/// A polygon mesh being subdivided: welded positions, face-varying UVs and
+/// crease sharpness per edge.
+struct Surface {
+    mesh: HalfEdgeMesh,
+    /// UVs of every face corner, per face; empty without UVs.
+    uvs: Vec<Vec<[f32; 2]>>,
+    /// Sharpness of crease edges, keyed by their sorted end points.
+    creases: HashMap<(u32, u32), f32>,
+}
+
+fn key(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
+
+fn lerp(a: Vec3, b: Vec3, t: f32) -> Vec3 {
+    math::add(a, math::scale(math::sub(b, a), t))
+}
+
+fn midpoint(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0]
+}
+
+/// Sharpness and the sharp neighbours of a vertex, gathered from its edges.
+#[derive(Clone, Default)]
+struct VertexCreases {
+    valence: u32,
+    /// Sum of neighbouring positions.
+    neighbours: Vec3,
+    sharp: Vec<Vec3>,
+    sharpness: f32,
+}
+
+impl VertexCreases {
+    /// Blends `smooth` towards the crease or corner rule by the vertex's
+    /// sharpness; darts (a single crease edge) stay smooth.
+    fn apply(&self, position: Vec3, smooth: Vec3, crease: impl Fn(Vec3, Vec3) -> Vec3) -> Vec3 {
+        let sharp = match self.sharp.len() {
+            0 | 1 => return smooth,
+            2 => crease(self.sharp[0], self.sharp[1]),
+            _ => position,
+        };
+        let weight = self.sharpness / self.sharp.len() as f32;
+        lerp(smooth, sharp, weight.min(1.0))
+    }
+}
+
+impl Surface {
+    /// Welds `mesh` by position and, for `quads`, pairs its triangles into
+    /// quads across their shared longest edges.
+    fn from_mesh(mesh: &Mesh, quads: bool) -> Surface {
+        let (welded, positions) = mesh.weld();
+        let has_uvs = mesh.uvs.len() == mesh.positions.len();
+
+        let corners: Vec<[u32; 3]> = mesh
+            .triangles()
+            .filter(|t| {
+                let [a, b, c] = t.map(|i| welded[i as usize]);
+                a != b && b != c && c != a
+            })
+            .collect();
+
+        let mut faces = Vec::with_capacity(corners.len());
+        let mut uvs = Vec::new();
+        let mut push = |wedges: Vec<u32>| {
+            if has_uvs {
+                uvs.push(
+                    wedges
+                        .iter()
+                        .map(|&w| {
+                            let (u, v) = mesh.uvs[w as usize].tex_coords;
+                            [u, v]
+                        })
+                        .collect(),
+                );
+            }
+            faces.push(wedges.iter().map(|&w| welded[w as usize]).collect());
+        };
+
+        let partners = if quads {
+            pair_triangles(&corners, &welded, &positions)
+        } else {
+            vec![None; corners.len()]
+        };
+        for (t, corner) in corners.iter().enumerate() {
+            match partners[t] {
+                None => push(corner.to_vec()),
+                Some((partner, _)) if partner < t => {}
+                Some((partner, rotation)) => {
+                    // `corner` rotated so its longest edge runs from
+                    // corner 1 to corner 2; the partner's far corner goes
+                    // between them.
+                    let [x, p, q] = [0, 1, 2].map(|k| corner[(k + rotation) % 3]);
+                    let far = corners[partner]
+                        .iter()
+                        .copied()
+                        .find(|&w| {
+                            welded[w as usize] != welded[p as usize]
+                                && welded[w as usize] != welded[q as usize]
+                        })
+                        .expect("paired triangles share an edge");
+                    push(vec![x, p, far, q]);
+                }
+            }
+        }
+
+        Surface {
+            mesh: HalfEdgeMesh::new(positions, &faces),
+            uvs,
+            creases: HashMap::new(),
+        }
+    }
+
+    /// Marks edges whose faces meet at more than `degrees` as creases.
+    fn mark_creases(&mut self, degrees: f32, sharpness: f32) {
+        let normals: Vec<Vec3> = (0..self.mesh.face_count() as u32)
+            .map(|f| math::normalize(self.face_normal(f)))
+            .collect();
+        let threshold = degrees.to_radians().cos();
+
+        let he = &self.mesh;
+        for h in he.edges() {
+            if let Some(twin) = he.twin(h) {
+                let (a, b) = (he.face(h), he.face(twin));
+                if math::dot(normals[a as usize], normals[b as usize]) < threshold {
+                    self.creases
+                        .insert(key(he.origin(h), he.destination(h)), sharpness);
+                }
+            }
+        }
+    }
+
+    /// Newell normal of a face, facing its front in the mesh winding.
+    fn face_normal(&self, f: u32) -> Vec3 {
+        let he = &self.mesh;
+        he.face_half_edges(f).fold([0.0; 3], |normal, h| {
+            let a = he.positions[he.origin(h) as usize];
+            let b = he.positions[he.destination(h) as usize];
+            math::add(normal, math::cross(b, a))
+        })
+    }
+
+    /// Sharpness of the edge of half-edge `h`; borders are infinitely sharp.
+    fn sharpness(&self, h: u32) -> f32 {
+        let he = &self.mesh;
+        match he.twin(h) {
+            None => f32::INFINITY,
+            Some(_) => self
+                .creases
+                .get(&key(he.origin(h), he.destination(h)))
+                .copied()
+                .unwrap_or(0.0),
+        }
+    }
+
+    fn vertex_creases(&self) -> Vec<VertexCreases> {
+        let he = &self.mesh;
+        let mut vertices = vec![VertexCreases::default(); he.vertex_count()];
+        for h in he.edges() {
+            let (a, b) = (he.origin(h), he.destination(h));
+            let sharpness = self.sharpness(h);
+            for (v, other) in [(a, b), (b, a)] {
+                let vertex = &mut vertices[v as usize];
+                let position = he.positions[other as usize];
+                vertex.valence += 1;
+                vertex.neighbours = math::add(vertex.neighbours, position);
+                if sharpness > 0.0 {
+                    vertex.sharp.push(position);
+                    vertex.sharpness += sharpness;
+                }
+            }
+        }
+        vertices
+    }
+
+    /// Crease sharpness of the two halves of every edge after a split.
+    fn child_creases(&self, first_edge_point: u32) -> HashMap<(u32, u32), f32> {
+        let he = &self.mesh;
+        let mut creases = HashMap::new();
+        for (e, h) in he.edges().enumerate() {
+            let sharpness = self.sharpness(h) - 1.0;
+            if he.twin(h).is_some() && sharpness > 0.0 {
+                let middle = first_edge_point + e as u32;
+                creases.insert(key(he.origin(h), middle), sharpness);
+                creases.insert(key(middle, he.destination(h)), sharpness);
+            }
+        }
+        creases
+    }
+
+    fn triangulated(self) -> Surface {
+        let he = &self.mesh;
+        if (0..he.face_count() as u32).all(|f| he.face_half_edges(f).len() == 3) {
+            return self;
+        }
+        let mut faces = Vec::new();
+        let mut uvs = Vec::new();
+        for f in 0..he.face_count() as u32 {
+            let face: Vec<u32> = he.face_vertices(f).collect();
+            for k in 1..face.len() - 1 {
+                faces.push(vec![face[0], face[k], face[k + 1]]);
+                if let Some(corners) = self.uvs.get(f as usize) {
+                    uvs.push(vec![corners[0], corners[k], corners[k + 1]]);
+                }
+            }
+        }
+        Surface {
+            mesh: HalfEdgeMesh::new(self.mesh.positions, &faces),
+            uvs,
+            creases: self.creases,
+        }
+    }
+
+    /// One level of Loop subdivision, with Hoppe-style crease rules blended
+    /// in by sharpness.
+    fn loop_subdivide(self) -> Surface {
+        let surface = self.triangulated();
+        let he = &surface.mesh;
+        let p = &he.positions;
+        let n = p.len() as u32;
+
+        let vertices = surface.vertex_creases();
+        let mut positions: Vec<Vec3> = vertices
+            .iter()
+            .enumerate()
+            .map(|(v, creases)| {
+                let position = p[v];
+                let valence = creases.valence as f32;
+                if creases.valence == 0 {
+                    return position;
+                }
+                let beta = if creases.valence == 3 {
+                    3.0 / 16.0
+                } else {
+                    3.0 / (8.0 * valence)
+                };
+                let smooth = math::add(
+                    math::scale(position, 1.0 - valence * beta),
+                    math::scale(creases.neighbours, beta),
+                );
+                creases.apply(position, smooth, |a, b| {
+                    math::add(
+                        math::scale(position, 0.75),
+                        math::scale(math::add(a, b), 0.125),
+                    )
+                })
+            })
+            .collect();
+
+        for h in he.edges() {
+            let (a, b) = (p[he.origin(h) as usize], p[he.destination(h) as usize]);
+            let middle = math::scale(math::add(a, b), 0.5);
+            let sharpness = surface.sharpness(h);
+            let point = match he.twin(h) {
+                Some(twin) if sharpness < 1.0 => {
+                    let c = p[he.origin(he.prev(h)) as usize];
+                    let d = p[he.origin(he.prev(twin)) as usize];
+                    let smooth = math::add(
+                        math::scale(math::add(a, b), 0.375),
+                        math::scale(math::add(c, d), 0.125),
+                    );
+                    lerp(smooth, middle, sharpness)
+                }
+                _ => middle,
+            };
+            positions.push(point);
+        }
+
+        let mut faces = Vec::with_capacity(he.face_count() * 4);
+        let mut uvs = Vec::with_capacity(surface.uvs.len() * 4);
+        for f in 0..he.face_count() as u32 {
+            let h = he.face_half_edges(f).start;
+            let [m0, m1, m2] = [h, h + 1, h + 2].map(|h| n + he.edge(h));
+            let [a, b, c] = [h, h + 1, h + 2].map(|h| he.origin(h));
+            faces.extend([
+                vec![a, m0, m2],
+                vec![m0, b, m1],
+                vec![m2, m1, c],
+                vec![m0, m1, m2],
+            ]);
+
+            if let Some(corners) = surface.uvs.get(f as usize) {
+                let [ua, ub, uc] = [corners[0], corners[1], corners[2]];
+                let [u0, u1, u2] = [midpoint(ua, ub), midpoint(ub, uc), midpoint(uc, ua)];
+                uvs.extend([
+                    vec![ua, u0, u2],
+                    vec![u0, ub, u1],
+                    vec![u2, u1, uc],
+                    vec![u0, u1, u2],
+                ]);
+            }
+        }
+
+        Surface {
+            creases: surface.child_creases(n),
+            mesh: HalfEdgeMesh::new(positions, &faces),
+            uvs,
+        }
+    }
+
+    /// One level of Catmull-Clark subdivision, with crease rules blended in
+    /// by sharpness (DeRose et al. 1998).
+    fn catmull_clark(self) -> Surface {
+        let he = &self.mesh;
+        let p = &he.positions;
+        let n = p.len() as u32;
+        let first_face_point = n + he.edge_count() as u32;
+
+        let face_points: Vec<Vec3> = (0..he.face_count() as u32)
+            .map(|f| {
+                let sum = he
+                    .face_vertices(f)
+                    .fold([0.0; 3], |sum, v| math::add(sum, p[v as usize]));
+                math::scale(sum, 1.0 / he.face_half_edges(f).len() as f32)
+            })
+            .collect();
+
+        let vertices = self.vertex_creases();
+        let mut positions: Vec<Vec3> = vertices
+            .iter()
+            .enumerate()
+            .map(|(v, creases)| {
+                let position = p[v];
+                let face_count = he.outgoing(v as u32).len();
+                if creases.valence == 0 || face_count == 0 {
+                    return position;
+                }
+                let face_sum = he
+                    .vertex_faces(v as u32)
+                    .fold([0.0; 3], |sum, f| math::add(sum, face_points[f as usize]));
+                let valence = creases.valence as f32;
+                let q = math::scale(face_sum, 1.0 / face_count as f32);
+                // Average edge midpoint: (v + average neighbour) / 2.
+                let r = math::scale(
+                    math::add(position, math::scale(creases.neighbours, 1.0 / valence)),
+                    0.5,
+                );
+                let smooth = math::scale(
+                    math::add(
+                        math::add(q, math::scale(r, 2.0)),
+                        math::scale(position, valence - 3.0),
+                    ),
+                    1.0 / valence,
+                );
+                creases.apply(position, smooth, |a, b| {
+                    math::scale(
+                        math::add(math::add(a, b), math::scale(position, 6.0)),
+                        0.125,
+                    )
+                })
+            })
+            .collect();
+
+        for h in he.edges() {
+            let (a, b) = (p[he.origin(h) as usize], p[he.destination(h) as usize]);
+            let middle = math::scale(math::add(a, b), 0.5);
+            let sharpness = self.sharpness(h);
+            let point = match he.twin(h) {
+                Some(twin) if sharpness < 1.0 => {
+                    let f1 = face_points[he.face(h) as usize];
+                    let f2 = face_points[he.face(twin) as usize];
+                    let smooth = math::scale(math::add(math::add(a, b), math::add(f1, f2)), 0.25);
+                    lerp(smooth, middle, sharpness)
+                }
+                _ => middle,
+            };
+            positions.push(point);
+        }
+        positions.extend(face_points);
+
+        let mut faces = Vec::new();
+        let mut uvs = Vec::new();
+        for f in 0..he.face_count() as u32 {
+            let center = first_face_point + f;
+            let corners = self.uvs.get(f as usize);
+            let center_uv = corners.map(|c| {
+                let sum = c
+                    .iter()
+                    .fold([0.0; 2], |s, uv| [s[0] + uv[0], s[1] + uv[1]]);
+                [sum[0] / c.len() as f32, sum[1] / c.len() as f32]
+            });
+
+            let half_edges = he.face_half_edges(f);
+            let count = half_edges.len();
+            for (k, h) in half_edges.enumerate() {
+                let before = he.prev(h);
+                faces.push(vec![
+                    he.origin(h),
+                    n + he.edge(h),
+                    center,
+                    n + he.edge(before),
+                ]);
+                if let (Some(c), Some(center_uv)) = (corners, center_uv) {
+                    let (next, before) = ((k + 1) % count, (k + count - 1) % count);
+                    uvs.push(vec![
+                        c[k],
+                        midpoint(c[k], c[next]),
+                        center_uv,
+                        midpoint(c[before], c[k]),
+                    ]);
+                }
+            }
+        }
+
+        Surface {
+            creases: self.child_creases(n),
+            mesh: HalfEdgeMesh::new(positions, &faces),
+            uvs,
+        }
+    }
+
+    /// Triangulates the polygons into a `Mesh`. Normals are averaged around
+    /// each vertex but kept apart across borders and remaining creases.
+    fn to_mesh(&self) -> Mesh {
+        let he = &self.mesh;
+        let face_normals: Vec<Vec3> = (0..he.face_count() as u32)
+            .map(|f| self.face_normal(f))
+            .collect();
+
+        let mut mesh = Mesh::default();
+        let mut lookup = HashMap::new();
+
+        for f in 0..he.face_count() as u32 {
+            let corners: Vec<u32> = he
+                .face_half_edges(f)
+                .enumerate()
+                .map(|(k, h)| {
+                    let normal = math::normalize(self.corner_normal(h, &face_normals));
+                    let v = he.origin(h);
+                    let uv = self.uvs.get(f as usize).map(|c| c[k]);
+                    let id = (
+                        v,
+                        normal.map(f32::to_bits),
+                        uv.map(|uv| uv.map(f32::to_bits)),
+                    );
+
+                    *lookup.entry(id).or_insert_with(|| {
+                        let [x, y, z] = he.positions[v as usize];
+                        let [nx, ny, nz] = normal;
+                        mesh.positions.push(Vertex {
+                            position: (x, y, z),
+                        });
+                        mesh.normals.push(Normal {
+                            normal: (nx, ny, nz),
+                        });
+                        if let Some([u, v]) = uv {
+                            mesh.uvs.push(TexCoord { tex_coords: (u, v) });
+                        }
+                        mesh.positions.len() as u32 - 1
+                    })
+                })
+                .collect();
+
+            for k in 1..corners.len() - 1 {
+                mesh.indices
+                    .extend_from_slice(&[corners[0], corners[k], corners[k + 1]]);
+            }
+        }
+        mesh
+    }
+
+    /// Sum of the face normals around the origin of `h`, walking both ways
+    /// until a crease or a border stops the fan.
+    fn corner_normal(&self, h: u32, face_normals: &[Vec3]) -> Vec3 {
+        let he = &self.mesh;
+        let smooth = |h: u32| self.sharpness(h) <= 0.0;
+        let mut normal = face_normals[he.face(h) as usize];
+
+        // Backwards: across the edge coming into the vertex.
+        let mut current = h;
+        loop {
+            let incoming = he.prev(current);
+            let twin = match he.twin(incoming) {
+                Some(twin) if smooth(incoming) => twin,
+                _ => break,
+            };
+            if twin == h {
+                // Went all the way around.
+                return normal;
+            }
+            normal = math::add(normal, face_normals[he.face(twin) as usize]);
+            current = twin;
+        }
+
+        // Forwards: across the edge leaving the vertex.
+        let mut current = h;
+        loop {
+            let twin = match he.twin(current) {
+                Some(twin) if smooth(current) => twin,
+                _ => break,
+            };
+            let outgoing = he.next(twin);
+            normal = math::add(normal, face_normals[he.face(outgoing) as usize]);
+            current = outgoing;
+        }
+        normal
+    }
+}
+
+/// For Catmull-Clark input: pairs each triangle with the neighbour across
+/// its longest edge when that edge is the neighbour's longest too, as it is
+/// for the diagonals of triangulated quads. Returns each triangle's partner
+/// and the rotation that puts its longest edge from corner 1 to corner 2.
+fn pair_triangles(
+    triangles: &[[u32; 3]],
+    welded: &[u32],
+    positions: &[Vec3],
+) -> Vec<Option<(usize, usize)>> {
+    let longest: Vec<(usize, (u32, u32))> = triangles
+        .iter()
+        .map(|t| {
+            let [a, b, c] = t.map(|w| welded[w as usize]);
+            let length = |x: u32, y: u32| {
+                math::length(math::sub(positions[x as usize], positions[y as usize]))
+            };
+            // Rotation `r` starts the triangle at the corner opposite the edge.
+            [(0, (b, c)), (1, (c, a)), (2, (a, b))]
+                .into_iter()
+                .max_by(|(_, (x1, y1)), (_, (x2, y2))| {
+                    length(*x1, *y1).total_cmp(&length(*x2, *y2))
+                })
+                .expect("triangles have three edges")
+        })
+        .collect();
+
+    let mut by_edge: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for (t, (_, (a, b))) in longest.iter().enumerate() {
+        by_edge.entry(key(*a, *b)).or_default().push(t);
+    }
+
+    let mut partners = vec![None; triangles.len()];
+    for (t, (rotation, (a, b))) in longest.iter().enumerate() {
+        if let [first, second] = by_edge[&key(*a, *b)][..] {
+            let other = if first == t { second } else { first };
+            // The shared edge must run in opposite directions.
+            if longest[other].1 == (*b, *a) {
+                partners[t] = Some((other, *rotation));
+            }
+        }
+    }
+    partners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    fn topology(mesh: &Mesh) -> (usize, usize, usize) {
+        let mesh = HalfEdgeMesh::from_mesh(mesh);
+        (mesh.vertex_count(), mesh.edge_count(), mesh.face_count())
+    }
+
+    #[test]
+    fn loop_splits_every_triangle_into_four() {
+        let icosahedron = primitives::icosphere(1.0, 0);
+        for levels in 0..3 {
+            let mesh = Subdivision::new(Scheme::Loop, levels).apply(&icosahedron);
+            let faces = 20 * 4usize.pow(levels);
+            assert_eq!(mesh.triangle_count(), faces);
+            // Each level adds a vertex per edge; a closed surface keeps
+            // V - E + F = 2.
+            let (v, e, f) = topology(&mesh);
+            assert_eq!((v, e, f), (faces / 2 + 2, faces * 3 / 2, faces));
+            // The limit surface lies inside the convex control mesh.
+            for i in 0..mesh.vertex_count() as u32 {
+                assert!(math::length(mesh.position(i)) <= 1.0 + 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn catmull_clark_cube() {
+        let cube = primitives::cube(2.0, 1);
+        let once = Subdivision::new(Scheme::CatmullClark, 1).apply(&cube);
+        // The 6 faces become 24 quads, drawn as 48 triangles, around the 8
+        // corners, 12 edge points and 6 face points.
+        assert_eq!(once.triangle_count(), 48);
+        assert_eq!(topology(&once).0, 26);
+
+        // A corner moves to (F + 2R + (n - 3)P) / n with n = 3: the average
+        // of its face points, (1, 1, 1) / 3, and twice that of its edge
+        // midpoints, (2, 2, 2) / 3, over 3.
+        let corner = (0..once.vertex_count() as u32)
+            .map(|i| once.position(i))
+            .max_by(|a, b| a.iter().sum::<f32>().total_cmp(&b.iter().sum()))
+            .unwrap();
+        for c in corner {
+            assert!((c - 5.0 / 9.0).abs() < 1e-5, "{:?}", corner);
+        }
+
+        let twice = Subdivision::new(Scheme::CatmullClark, 2).apply(&cube);
+        assert_eq!(twice.triangle_count(), 192);
+        let (v, e, f) = topology(&twice);
+        assert_eq!(v as i64 - e as i64 + f as i64, 2);
+    }
+}
@@ -0,0 +1,351 @@
+use crate::math::{self, Vec3};
+use crate::mesh::{Mesh, Normal, TexCoord, Vertex};
+
+/// Control point indices of the ten distinct patches of Newell's teapot, as
+/// published with GLUT. Rim, body, lid and bottom are mirrored into all four
+/// quadrants and the handle and spout into both halves, giving the original
+/// 32 bicubic patches.
+const PATCHES: [[usize; 16]; 10] = [
+    // rim
+    [102, 103, 104, 105, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    // body
+    [
+        12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27,
+    ],
+    [
+        24, 25, 26, 27, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40,
+    ],
+    // lid
+    [
+        96, 96, 96, 96, 97, 98, 99, 100, 101, 101, 101, 101, 0, 1, 2, 3,
+    ],
+    [
+        0, 1, 2, 3, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117,
+    ],
+    // bottom
+    [
+        118, 118, 118, 118, 124, 122, 119, 121, 123, 126, 125, 120, 40, 39, 38, 37,
+    ],
+    // handle
+    [
+        41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56,
+    ],
+    [
+        53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 28, 65, 66, 67,
+    ],
+    // spout
+    [
+        68, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83,
+    ],
+    [
+        80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91, 92, 93, 94, 95,
+    ],
+];
+
+/// Patches before this index are mirrored four ways, the rest two ways.
+const FOUR_WAY_PATCHES: usize = 6;
+
+/// Control points in the original Z-up coordinates.
+const CONTROL_POINTS: [Vec3; 127] = [
+    [0.2, 0.0, 2.7],
+    [0.2, -0.112, 2.7],
+    [0.112, -0.2, 2.7],
+    [0.0, -0.2, 2.7],
+    [1.3375, 0.0, 2.53125],
+    [1.3375, -0.749, 2.53125],
+    [0.749, -1.3375, 2.53125],
+    [0.0, -1.3375, 2.53125],
+    [1.4375, 0.0, 2.53125],
+    [1.4375, -0.805, 2.53125],
+    [0.805, -1.4375, 2.53125],
+    [0.0, -1.4375, 2.53125],
+    [1.5, 0.0, 2.4],
+    [1.5, -0.84, 2.4],
+    [0.84, -1.5, 2.4],
+    [0.0, -1.5, 2.4],
+    [1.75, 0.0, 1.875],
+    [1.75, -0.98, 1.875],
+    [0.98, -1.75, 1.875],
+    [0.0, -1.75, 1.875],
+    [2.0, 0.0, 1.35],
+    [2.0, -1.12, 1.35],
+    [1.12, -2.0, 1.35],
+    [0.0, -2.0, 1.35],
+    [2.0, 0.0, 0.9],
+    [2.0, -1.12, 0.9],
+    [1.12, -2.0, 0.9],
+    [0.0, -2.0, 0.9],
+    [-2.0, 0.0, 0.9],
+    [2.0, 0.0, 0.45],
+    [2.0, -1.12, 0.45],
+    [1.12, -2.0, 0.45],
+    [0.0, -2.0, 0.45],
+    [1.5, 0.0, 0.225],
+    [1.5, -0.84, 0.225],
+    [0.84, -1.5, 0.225],
+    [0.0, -1.5, 0.225],
+    [1.5, 0.0, 0.15],
+    [1.5, -0.84, 0.15],
+    [0.84, -1.5, 0.15],
+    [0.0, -1.5, 0.15],
+    [-1.6, 0.0, 2.025],
+    [-1.6, -0.3, 2.025],
+    [-1.5, -0.3, 2.25],
+    [-1.5, 0.0, 2.25],
+    [-2.3, 0.0, 2.025],
+    [-2.3, -0.3, 2.025],
+    [-2.5, -0.3, 2.25],
+    [-2.5, 0.0, 2.25],
+    [-2.7, 0.0, 2.025],
+    [-2.7, -0.3, 2.025],
+    [-3.0, -0.3, 2.25],
+    [-3.0, 0.0, 2.25],
+    [-2.7, 0.0, 1.8],
+    [-2.7, -0.3, 1.8],
+    [-3.0, -0.3, 1.8],
+    [-3.0, 0.0, 1.8],
+    [-2.7, 0.0, 1.575],
+    [-2.7, -0.3, 1.575],
+    [-3.0, -0.3, 1.35],
+    [-3.0, 0.0, 1.35],
+    [-2.5, 0.0, 1.125],
+    [-2.5, -0.3, 1.125],
+    [-2.65, -0.3, 0.9375],
+    [-2.65, 0.0, 0.9375],
+    [-2.0, -0.3, 0.9],
+    [-1.9, -0.3, 0.6],
+    [-1.9, 0.0, 0.6],
+    [1.7, 0.0, 1.425],
+    [1.7, -0.66, 1.425],
+    [1.7, -0.66, 0.6],
+    [1.7, 0.0, 0.6],
+    [2.6, 0.0, 1.425],
+    [2.6, -0.66, 1.425],
+    [3.1, -0.66, 0.825],
+    [3.1, 0.0, 0.825],
+    [2.3, 0.0, 2.1],
+    [2.3, -0.25, 2.1],
+    [2.4, -0.25, 2.025],
+    [2.4, 0.0, 2.025],
+    [2.7, 0.0, 2.4],
+    [2.7, -0.25, 2.4],
+    [3.3, -0.25, 2.4],
+    [3.3, 0.0, 2.4],
+    [2.8, 0.0, 2.475],
+    [2.8, -0.25, 2.475],
+    [3.525, -0.25, 2.49375],
+    [3.525, 0.0, 2.49375],
+    [2.9, 0.0, 2.475],
+    [2.9, -0.15, 2.475],
+    [3.45, -0.15, 2.5125],
+    [3.45, 0.0, 2.5125],
+    [2.8, 0.0, 2.4],
+    [2.8, -0.15, 2.4],
+    [3.2, -0.15, 2.4],
+    [3.2, 0.0, 2.4],
+    [0.0, 0.0, 3.15],
+    [0.8, 0.0, 3.15],
+    [0.8, -0.45, 3.15],
+    [0.45, -0.8, 3.15],
+    [0.0, -0.8, 3.15],
+    [0.0, 0.0, 2.85],
+    [1.4, 0.0, 2.4],
+    [1.4, -0.784, 2.4],
+    [0.784, -1.4, 2.4],
+    [0.0, -1.4, 2.4],
+    [0.4, 0.0, 2.55],
+    [0.4, -0.224, 2.55],
+    [0.224, -0.4, 2.55],
+    [0.0, -0.4, 2.55],
+    [1.3, 0.0, 2.55],
+    [1.3, -0.728, 2.55],
+    [0.728, -1.3, 2.55],
+    [0.0, -1.3, 2.55],
+    [1.3, 0.0, 2.4],
+    [1.3, -0.728, 2.4],
+    [0.728, -1.3, 2.4],
+    [0.0, -1.3, 2.4],
+    [0.0, 0.0, 0.0],
+    [1.425, -0.798, 0.0],
+    [1.5, 0.0, 0.075],
+    [1.425, 0.0, 0.0],
+    [0.798, -1.425, 0.0],
+    [0.0, -1.5, 0.075],
+    [0.0, -1.425, 0.0],
+    [1.5, -0.84, 0.075],
+    [0.84, -1.5, 0.075],
+];
+
+type Patch = [[Vec3; 4]; 4];
+
+/// The 32 patches, converted to Y-up. Mirrored copies have their columns
+/// reversed so every patch keeps the same orientation.
+pub fn patches() -> Vec<Patch> {
+    let mut patches = Vec::with_capacity(32);
+
+    for (i, indices) in PATCHES.iter().enumerate() {
+        let mirrors: &[(f32, f32)] = if i < FOUR_WAY_PATCHES {
+            &[(1.0, 1.0), (1.0, -1.0), (-1.0, 1.0), (-1.0, -1.0)]
+        } else {
+            &[(1.0, 1.0), (1.0, -1.0)]
+        };
+
+        for &(sx, sy) in mirrors {
+            let reverse = sx * sy < 0.0;
+            let mut patch = [[[0.0; 3]; 4]; 4];
+            for (row, points) in patch.iter_mut().enumerate() {
+                for (col, point) in points.iter_mut().enumerate() {
+                    let source_col = if reverse { 3 - col } else { col };
+                    let [x, y, z] = CONTROL_POINTS[indices[row * 4 + source_col]];
+                    *point = [x * sx, z, -y * sy];
+                }
+            }
+            patches.push(patch);
+        }
+    }
+    patches
+}
+
+fn bernstein(t: f32) -> [f32; 4] {
+    let s = 1.0 - t;
+    [s * s * s, 3.0 * t * s * s, 3.0 * t * t * s, t * t * t]
+}
+
+fn bernstein_derivative(t: f32) -> [f32; 4] {
+    let s = 1.0 - t;
+    [
+        -3.0 * s * s,
+        3.0 * s * s - 6.0 * t * s,
+        6.0 * t * s - 3.0 * t * t,
+        3.0 * t * t,
+    ]
+}
+
+fn evaluate(patch: &Patch, bu: [f32; 4], bv: [f32; 4]) -> Vec3 {
+    let mut out = [0.0; 3];
+    for (row, points) in patch.iter().enumerate() {
+        for (col, point) in points.iter().enumerate() {
+            out = math::add(out, math::scale(*point, bu[row] * bv[col]));
+        }
+    }
+    out
+}
+
+/// Position and unit normal of `patch` at `(u, v)`, with `u` running across
+/// rows and `v` across columns of control points.
+pub fn evaluate_patch(patch: &Patch, u: f32, v: f32) -> (Vec3, Vec3) {
+    let position = evaluate(patch, bernstein(u), bernstein(v));
+    let mut normal = analytic_normal(patch, u, v);
+
+    // Collapsed rows (the lid knob and bottom centre) have a zero derivative;
+    // take the normal from just inside the patch instead.
+    if math::length(normal) < 1e-6 {
+        let nudge = 1e-3;
+        normal = analytic_normal(
+            patch,
+            u.clamp(nudge, 1.0 - nudge),
+            v.clamp(nudge, 1.0 - nudge),
+        );
+    }
+
+    (position, math::normalize(normal))
+}
+
+fn analytic_normal(patch: &Patch, u: f32, v: f32) -> Vec3 {
+    let du = evaluate(patch, bernstein_derivative(u), bernstein(v));
+    let dv = evaluate(patch, bernstein(u), bernstein_derivative(v));
+    math::cross(dv, du)
+}
+
+/// Finer tessellations than this would run past a `u32` index buffer long
+/// before they looked any smoother.
+pub const MAX_SUBDIVISIONS: u32 = 256;
+
+/// Tessellates all 32 patches into a `subdivisions × subdivisions` grid each,
+/// giving `64 * subdivisions²` triangles less the `8 * subdivisions` that
+/// would have no area where the lid knob and bottom centre collapse to a
+/// point. Vertices along patch seams are not shared; UVs are the patch
+/// parameters.
+pub fn tessellate(subdivisions: u32) -> Result<Mesh, String> {
+    if subdivisions > MAX_SUBDIVISIONS {
+        return Err(format!(
+            "{} subdivisions is more than the {} a teapot can take",
+            subdivisions, MAX_SUBDIVISIONS
+        ));
+    }
+    let n = subdivisions.max(1);
+    let stride = n + 1;
+    let mut mesh = Mesh::default();
+
+    for patch in patches() {
+        let base = mesh.positions.len() as u32;
+        let collapsed = |row: usize| patch[row].iter().all(|point| *point == patch[row][0]);
+        let (first_collapsed, last_collapsed) = (collapsed(0), collapsed(3));
+
+        for i in 0..=n {
+            for j in 0..=n {
+                let (u, v) = (i as f32 / n as f32, j as f32 / n as f32);
+                let ([x, y, z], [nx, ny, nz]) = evaluate_patch(&patch, u, v);
+                mesh.positions.push(Vertex {
+                    position: (x, y, z),
+                });
+                mesh.normals.push(Normal {
+                    normal: (nx, ny, nz),
+                });
+                mesh.uvs.push(TexCoord { tex_coords: (v, u) });
+            }
+        }
+
+        for i in 0..n {
+            for j in 0..n {
+                let a = base + i * stride + j;
+                let b = a + 1;
+                let c = a + stride;
+                let d = c + 1;
+                // `a` and `b` lie on row `i`, `c` and `d` on row `i + 1`.
+                if !(i == 0 && first_collapsed) {
+                    mesh.indices.extend_from_slice(&[a, c, b]);
+                }
+                if !(i == n - 1 && last_collapsed) {
+                    mesh.indices.extend_from_slice(&[b, c, d]);
+                }
+            }
+        }
+    }
+
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repair;
+
+    #[test]
+    fn triangle_counts() {
+        for n in [1, 2, 5, 16] {
+            let mesh = tessellate(n).unwrap();
+            let n = n as usize;
+            assert_eq!(mesh.vertex_count(), 32 * (n + 1) * (n + 1));
+            assert_eq!(mesh.triangle_count(), 64 * n * n - 8 * n);
+        }
+        // Zero counts as one, as the scene format has always allowed.
+        assert_eq!(tessellate(0).unwrap().triangle_count(), 56);
+    }
+
+    #[test]
+    fn no_degenerate_triangles() {
+        // Not one subdivision: the corners of the spout's mouth lie on a
+        // line, so its two patches would be flat.
+        for n in [2, 3, 16] {
+            let report = repair::validate(&tessellate(n).unwrap());
+            assert_eq!(report.degenerate, Vec::<usize>::new(), "{} subdivisions", n);
+        }
+    }
+
+    #[test]
+    fn too_many_subdivisions_are_refused() {
+        assert!(tessellate(MAX_SUBDIVISIONS + 1).is_err());
+        assert!(tessellate(u32::MAX).is_err());
+    }
+}
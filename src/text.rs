@@ -0,0 +1,317 @@
+//! Screen-space text drawn from a font atlas. Glyphs are rasterized once,
+//! when the renderer is created, and every string queued during a frame goes
+//! out in a single draw call.
+
+use fontdue::{Font, FontSettings};
+use glium::{
+    index::{NoIndices, PrimitiveType},
+    texture::{ClientFormat, MipmapsOption, RawImage2d, UncompressedFloatFormat},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter},
+    Blend, Display, DrawParameters, Frame, Program, Surface, Texture2d, VertexBuffer,
+};
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+const FONT: &[u8] = include_bytes!("../data/DejaVuSansMono.ttf");
+/// Size the font is rasterized at, in pixels per em.
+const FONT_SIZE: f32 = 14.0;
+/// Width of the atlas texture; its height is whatever the glyphs need.
+const ATLAS_WIDTH: usize = 256;
+/// Empty pixels around each glyph, so neighbours never bleed into it.
+const PADDING: usize = 1;
+/// Side of the solid block at the atlas origin that `rect` samples.
+const SOLID_SIZE: usize = 2;
+/// Drawn in place of characters the atlas lacks.
+const FALLBACK: char = '?';
+
+const VERTEX_SHADER_SRC: &str = r#"
+        #version 140
+
+        in vec2 position;
+        in vec2 tex_coords;
+        in vec4 color;
+
+        out vec2 v_tex_coords;
+        out vec4 v_color;
+
+        uniform vec2 u_screen;
+
+        void main() {
+            v_tex_coords = tex_coords;
+            v_color = color;
+            vec2 ndc = position / u_screen * 2.0 - 1.0;
+            gl_Position = vec4(ndc.x, -ndc.y, 0.0, 1.0);
+        }
+    "#;
+
+const FRAGMENT_SHADER_SRC: &str = r#"
+        #version 140
+
+        in vec2 v_tex_coords;
+        in vec4 v_color;
+        out vec4 color;
+
+        uniform sampler2D u_atlas;
+
+        void main() {
+            color = vec4(v_color.rgb, v_color.a * texture(u_atlas, v_tex_coords).r);
+        }
+    "#;
+
+/// A corner of a glyph quad, positioned in pixels from the top left of the
+/// window.
+#[derive(Copy, Clone)]
+pub struct GlyphVertex {
+    pub position: (f32, f32),
+    pub tex_coords: (f32, f32),
+    pub color: (f32, f32, f32, f32),
+}
+
+implement_vertex!(GlyphVertex, position, tex_coords, color);
+
+/// Where a glyph sits in the atlas and how to place it on a line.
+#[derive(Copy, Clone)]
+struct Glyph {
+    /// Top left of the bitmap relative to the pen on the baseline, y down.
+    offset: [f32; 2],
+    size: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    advance: f32,
+}
+
+/// Printable ASCII plus a few symbols the HUD uses, rasterized into one
+/// single-channel texture.
+pub struct FontAtlas {
+    texture: Texture2d,
+    glyphs: HashMap<char, Glyph>,
+    /// Texture coordinates inside the solid block.
+    solid: [f32; 2],
+    ascent: f32,
+    line_height: f32,
+}
+
+impl FontAtlas {
+    pub fn new(display: &Display) -> FontAtlas {
+        let font = Font::from_bytes(
+            FONT,
+            FontSettings {
+                scale: FONT_SIZE,
+                ..Default::default()
+            },
+        )
+        .expect("failed to parse font!");
+        let metrics = font
+            .horizontal_line_metrics(FONT_SIZE)
+            .expect("font has no horizontal metrics!");
+
+        // Pack the bitmaps onto shelves left to right, starting a new shelf
+        // whenever one fills, behind the solid block.
+        let mut pixels = vec![0u8; ATLAS_WIDTH * SOLID_SIZE];
+        for row in pixels.chunks_exact_mut(ATLAS_WIDTH) {
+            row[..SOLID_SIZE].fill(u8::MAX);
+        }
+        let mut placed = Vec::new();
+        let (mut x, mut y, mut shelf_height) = (SOLID_SIZE + PADDING, 0, SOLID_SIZE);
+        for c in (' '..='~').chain(['°', '×']) {
+            let (glyph, bitmap) = font.rasterize(c, FONT_SIZE);
+            if x + glyph.width + PADDING > ATLAS_WIDTH {
+                x = 0;
+                y += shelf_height + PADDING;
+                shelf_height = 0;
+            }
+            let rows = y + glyph.height;
+            if pixels.len() < rows * ATLAS_WIDTH {
+                pixels.resize(rows * ATLAS_WIDTH, 0);
+            }
+            for (row, line) in bitmap.chunks_exact(glyph.width.max(1)).enumerate() {
+                let start = (y + row) * ATLAS_WIDTH + x;
+                pixels[start..start + glyph.width].copy_from_slice(line);
+            }
+            placed.push((c, glyph, [x, y]));
+            x += glyph.width + PADDING;
+            shelf_height = shelf_height.max(glyph.height);
+        }
+        let height = pixels.len() / ATLAS_WIDTH;
+
+        let size = [ATLAS_WIDTH as f32, height as f32];
+        let glyphs = placed
+            .into_iter()
+            .map(|(c, glyph, [x, y])| {
+                let (width, height) = (glyph.width as f32, glyph.height as f32);
+                let glyph = Glyph {
+                    offset: [glyph.xmin as f32, -(glyph.ymin as f32 + height)],
+                    size: [width, height],
+                    uv_min: [x as f32 / size[0], y as f32 / size[1]],
+                    uv_max: [(x as f32 + width) / size[0], (y as f32 + height) / size[1]],
+                    advance: glyph.advance_width,
+                };
+                (c, glyph)
+            })
+            .collect();
+
+        let image = RawImage2d {
+            data: Cow::Owned(pixels),
+            width: ATLAS_WIDTH as u32,
+            height: height as u32,
+            format: ClientFormat::U8,
+        };
+        let texture = Texture2d::with_format(
+            display,
+            image,
+            UncompressedFloatFormat::U8,
+            MipmapsOption::NoMipmap,
+        )
+        .expect("failed to create font atlas texture!");
+
+        let half_solid = SOLID_SIZE as f32 / 2.0;
+        FontAtlas {
+            texture,
+            glyphs,
+            solid: [half_solid / size[0], half_solid / size[1]],
+            ascent: metrics.ascent.round(),
+            line_height: metrics.new_line_size.round(),
+        }
+    }
+
+    fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&FALLBACK))
+    }
+}
+
+/// Queues text and solid rectangles in window pixels, then draws them all
+/// over the frame with `draw`.
+pub struct TextRenderer {
+    atlas: FontAtlas,
+    program: Program,
+    /// Holds the quads of a frame; replaced by a larger one when they
+    /// outgrow it.
+    buffer: Option<VertexBuffer<GlyphVertex>>,
+    vertices: Vec<GlyphVertex>,
+}
+
+impl TextRenderer {
+    pub fn new(display: &Display) -> TextRenderer {
+        let program = Program::from_source(display, VERTEX_SHADER_SRC, FRAGMENT_SHADER_SRC, None)
+            .expect("failed to create text program!");
+
+        TextRenderer {
+            atlas: FontAtlas::new(display),
+            program,
+            buffer: None,
+            vertices: Vec::new(),
+        }
+    }
+
+    /// Distance between the tops of consecutive lines, in pixels.
+    pub fn line_height(&self) -> f32 {
+        self.atlas.line_height
+    }
+
+    /// Width of `text` on one line, in pixels.
+    pub fn width(&self, text: &str) -> f32 {
+        text.chars()
+            .filter_map(|c| self.atlas.glyph(c))
+            .map(|glyph| glyph.advance)
+            .sum()
+    }
+
+    /// Queues one line of `text` with its top left at `position`, returning
+    /// its width.
+    pub fn text(&mut self, position: [f32; 2], text: &str, color: [f32; 4]) -> f32 {
+        let baseline = (position[1] + self.atlas.ascent).round();
+        let mut pen = position[0].round();
+        for c in text.chars() {
+            let Some(&glyph) = self.atlas.glyph(c) else {
+                continue;
+            };
+            if glyph.size[0] > 0.0 {
+                let min = [pen + glyph.offset[0], baseline + glyph.offset[1]];
+                let max = [min[0] + glyph.size[0], min[1] + glyph.size[1]];
+                self.quad(min, max, glyph.uv_min, glyph.uv_max, color);
+            }
+            pen += glyph.advance;
+        }
+        pen - position[0].round()
+    }
+
+    /// Queues a solid rectangle from `min` to `max`, drawn under any text
+    /// queued after it.
+    pub fn rect(&mut self, min: [f32; 2], max: [f32; 2], color: [f32; 4]) {
+        let solid = self.atlas.solid;
+        self.quad(min, max, solid, solid, color);
+    }
+
+    fn quad(
+        &mut self,
+        min: [f32; 2],
+        max: [f32; 2],
+        uv_min: [f32; 2],
+        uv_max: [f32; 2],
+        color: [f32; 4],
+    ) {
+        let color = (color[0], color[1], color[2], color[3]);
+        let corner = |x: usize, y: usize| GlyphVertex {
+            position: ([min[0], max[0]][x], [min[1], max[1]][y]),
+            tex_coords: ([uv_min[0], uv_max[0]][x], [uv_min[1], uv_max[1]][y]),
+            color,
+        };
+        self.vertices.extend([
+            corner(0, 0),
+            corner(0, 1),
+            corner(1, 1),
+            corner(0, 0),
+            corner(1, 1),
+            corner(1, 0),
+        ]);
+    }
+
+    /// Draws everything queued since the last call over `target` and empties
+    /// the queue.
+    pub fn draw(&mut self, display: &Display, target: &mut Frame) {
+        let vertices = std::mem::take(&mut self.vertices);
+        if vertices.is_empty() {
+            return;
+        }
+        if self
+            .buffer
+            .as_ref()
+            .is_some_and(|buffer| buffer.len() < vertices.len())
+        {
+            self.buffer = None;
+        }
+        let buffer = self.buffer.get_or_insert_with(|| {
+            VertexBuffer::empty_dynamic(display, vertices.len().next_power_of_two())
+                .expect("failed to create text VertexBuffer!")
+        });
+        let used = buffer
+            .slice(0..vertices.len())
+            .expect("text exceeds the text buffer!");
+        used.write(&vertices);
+
+        let (width, height) = target.get_dimensions();
+        let uniforms = uniform! {
+            u_screen: [width as f32, height as f32],
+            u_atlas: self
+                .atlas
+                .texture
+                .sampled()
+                .magnify_filter(MagnifySamplerFilter::Nearest)
+                .minify_filter(MinifySamplerFilter::Nearest),
+        };
+        let params = DrawParameters {
+            blend: Blend::alpha_blending(),
+            ..Default::default()
+        };
+
+        target
+            .draw(
+                used,
+                NoIndices(PrimitiveType::TrianglesList),
+                &self.program,
+                &uniforms,
+                &params,
+            )
+            .expect("failed to draw text!");
+    }
+}
@@ -0,0 +1,235 @@
+type Point = [f32; 2];
+
+/// Triangulates a simple polygon with optional holes by ear clipping, after
+/// joining every hole to the outline with a bridge edge.
+///
+/// Points are numbered outline first, then each hole in order. The triangles
+/// come out counter-clockwise (with x to the right and y up) whatever the
+/// winding of the input rings.
+pub fn triangulate(outline: &[Point], holes: &[Vec<Point>]) -> Result<Vec<[u32; 3]>, String> {
+    if outline.len() < 3 {
+        return Err("polygon outline needs at least 3 points".to_string());
+    }
+
+    let mut points = outline.to_vec();
+    let mut ring: Vec<usize> = (0..outline.len()).collect();
+    let area = signed_area(&points, &ring);
+    if area == 0.0 {
+        return Err("polygon outline has no area".to_string());
+    }
+    if area < 0.0 {
+        ring.reverse();
+    }
+
+    let mut hole_rings = Vec::with_capacity(holes.len());
+    for hole in holes {
+        if hole.len() < 3 {
+            return Err("polygon hole needs at least 3 points".to_string());
+        }
+        let start = points.len();
+        points.extend_from_slice(hole);
+        let mut hole_ring: Vec<usize> = (start..points.len()).collect();
+        if signed_area(&points, &hole_ring) > 0.0 {
+            hole_ring.reverse();
+        }
+        hole_rings.push(hole_ring);
+    }
+
+    // Bridging the rightmost hole first keeps later bridges from crossing
+    // earlier ones.
+    hole_rings.sort_by(|a, b| max_x(&points, b).total_cmp(&max_x(&points, a)));
+    for hole in &hole_rings {
+        ring = bridge(&points, ring, hole)?;
+    }
+
+    Ok(clip_ears(&points, ring))
+}
+
+fn signed_area(points: &[Point], ring: &[usize]) -> f32 {
+    let mut sum = 0.0;
+    for (i, &a) in ring.iter().enumerate() {
+        let b = ring[(i + 1) % ring.len()];
+        sum += points[a][0] * points[b][1] - points[b][0] * points[a][1];
+    }
+    sum / 2.0
+}
+
+fn max_x(points: &[Point], ring: &[usize]) -> f32 {
+    ring.iter().map(|&i| points[i][0]).fold(f32::MIN, f32::max)
+}
+
+/// Twice the signed area of `o, a, b`; positive when they turn left.
+fn cross(o: Point, a: Point, b: Point) -> f32 {
+    (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+}
+
+/// Whether `p` lies inside or on the counter-clockwise triangle `a, b, c`.
+fn in_triangle(p: Point, a: Point, b: Point, c: Point) -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+/// Splices a clockwise `hole` into the counter-clockwise `ring` through a
+/// pair of coincident edges between the hole's rightmost vertex and an
+/// outline vertex it can see (Eberly's method).
+fn bridge(points: &[Point], ring: Vec<usize>, hole: &[usize]) -> Result<Vec<usize>, String> {
+    let start = (0..hole.len())
+        .max_by(|&a, &b| points[hole[a]][0].total_cmp(&points[hole[b]][0]))
+        .unwrap_or(0);
+    let m = points[hole[start]];
+
+    // The closest edge crossed by a ray from `m` towards +x; its endpoint
+    // further along the ray is a first guess at a visible vertex.
+    let mut closest: Option<(f32, usize)> = None;
+    for i in 0..ring.len() {
+        let j = (i + 1) % ring.len();
+        let (a, b) = (points[ring[i]], points[ring[j]]);
+        if (a[1] > m[1]) == (b[1] > m[1]) {
+            continue;
+        }
+        let x = a[0] + (m[1] - a[1]) * (b[0] - a[0]) / (b[1] - a[1]);
+        if x >= m[0] && closest.is_none_or(|(best, _)| x < best) {
+            closest = Some((x, if a[0] > b[0] { i } else { j }));
+        }
+    }
+    let (x, mut candidate) = closest.ok_or("polygon hole lies outside its outline")?;
+
+    // Anything inside the triangle between the ray and the guess could block
+    // the view; the vertex closest in angle to the ray cannot be blocked.
+    let intersection = [x, m[1]];
+    let p = points[ring[candidate]];
+    let (a, b, c) = if p[1] < m[1] {
+        (m, p, intersection)
+    } else {
+        (m, intersection, p)
+    };
+    let mut best_tangent = f32::INFINITY;
+    for (i, &index) in ring.iter().enumerate() {
+        let v = points[index];
+        if i == candidate || v == m || !in_triangle(v, a, b, c) {
+            continue;
+        }
+        let dx = v[0] - m[0];
+        let tangent = (v[1] - m[1]).abs() / dx;
+        if dx > 0.0 && tangent < best_tangent {
+            best_tangent = tangent;
+            candidate = i;
+        }
+    }
+
+    let mut spliced = Vec::with_capacity(ring.len() + hole.len() + 2);
+    spliced.extend_from_slice(&ring[..=candidate]);
+    spliced.extend_from_slice(&hole[start..]);
+    spliced.extend_from_slice(&hole[..=start]);
+    spliced.extend_from_slice(&ring[candidate..]);
+    Ok(spliced)
+}
+
+fn is_ear(points: &[Point], ring: &[usize], a: Point, b: Point, c: Point) -> bool {
+    if cross(a, b, c) <= 0.0 {
+        return false;
+    }
+    ring.iter().all(|&i| {
+        let p = points[i];
+        p == a || p == b || p == c || !in_triangle(p, a, b, c)
+    })
+}
+
+fn clip_ears(points: &[Point], mut ring: Vec<usize>) -> Vec<[u32; 3]> {
+    let mut triangles = Vec::with_capacity(ring.len().saturating_sub(2));
+    let mut i = 0;
+    let mut stalled = 0;
+
+    while ring.len() > 3 {
+        let n = ring.len();
+        let (prev, next) = ((i + n - 1) % n, (i + 1) % n);
+        let [a, b, c] = [ring[prev], ring[i], ring[next]].map(|k| points[k]);
+        let area = cross(a, b, c);
+
+        // Collinear vertices are dropped without a triangle. A full pass
+        // without an ear only happens on self-intersecting input; clip
+        // anyway so the loop ends.
+        if area == 0.0 || stalled >= n || is_ear(points, &ring, a, b, c) {
+            if area > 0.0 {
+                triangles.push([ring[prev], ring[i], ring[next]].map(|k| k as u32));
+            }
+            ring.remove(i);
+            i = (i + ring.len() - 1) % ring.len();
+            stalled = 0;
+        } else {
+            i = next;
+            stalled += 1;
+        }
+    }
+
+    if ring.len() == 3 && cross(points[ring[0]], points[ring[1]], points[ring[2]]) > 0.0 {
+        triangles.push([ring[0], ring[1], ring[2]].map(|k| k as u32));
+    }
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Signed areas of the triangles over all the points, outline first.
+    fn areas(outline: &[Point], holes: &[Vec<Point>]) -> Vec<f32> {
+        let points: Vec<Point> = outline
+            .iter()
+            .chain(holes.iter().flatten())
+            .copied()
+            .collect();
+        triangulate(outline, holes)
+            .unwrap()
+            .iter()
+            .map(|t| signed_area(&points, &t.map(|i| i as usize)))
+            .collect()
+    }
+
+    fn check(outline: &[Point], holes: &[Vec<Point>], triangles: usize, area: f32) {
+        let areas = areas(outline, holes);
+        assert_eq!(areas.len(), triangles);
+        assert!(areas.iter().all(|&a| a > 0.0), "{:?}", areas);
+        assert!(
+            (areas.iter().sum::<f32>() - area).abs() < 1e-5,
+            "{:?}",
+            areas
+        );
+    }
+
+    #[test]
+    fn squares_either_way_round() {
+        let square = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        check(&square, &[], 2, 1.0);
+        let mut clockwise = square;
+        clockwise.reverse();
+        check(&clockwise, &[], 2, 1.0);
+    }
+
+    #[test]
+    fn concave_outline() {
+        // An L of three unit squares: n - 2 triangles for n points.
+        let l = [
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ];
+        check(&l, &[], 4, 3.0);
+    }
+
+    #[test]
+    fn outline_with_a_hole() {
+        // n + 2h - 2 triangles for n points in all and h holes.
+        let outline = [[0.0, 0.0], [4.0, 0.0], [4.0, 4.0], [0.0, 4.0]];
+        let hole = vec![[1.0, 1.0], [3.0, 1.0], [3.0, 3.0], [1.0, 3.0]];
+        check(&outline, &[hole], 8, 12.0);
+    }
+
+    #[test]
+    fn degenerate_outlines_are_errors() {
+        assert!(triangulate(&[[0.0, 0.0], [1.0, 0.0]], &[]).is_err());
+        assert!(triangulate(&[[0.0, 0.0], [1.0, 0.0], [2.0, 0.0]], &[]).is_err());
+    }
+}
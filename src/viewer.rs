@@ -0,0 +1,636 @@
+use crate::bounds::Aabb;
+use crate::clock::FrameClock;
+use crate::debug_draw::{self, Style};
+use crate::gizmo;
+use crate::gltf;
+use crate::gui::Gui;
+use crate::hud::{FrameTimes, Hud};
+use crate::math;
+use crate::mesh::{Mesh, Vertex};
+use crate::overlay::{LineVertex, Overlays};
+use crate::panels::{Changes, Panels};
+use crate::picking::{Picker, Ray};
+use crate::render::{DrawStats, GpuScene, Renderer};
+use crate::repair;
+use crate::scene::{LightKind, Scene};
+use crate::subdivide::{Scheme, Subdivision};
+use crate::text::TextRenderer;
+use crate::winding;
+use glium::{
+    glutin::event::{
+        ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, VirtualKeyCode,
+        WindowEvent,
+    },
+    glutin::window::CursorIcon,
+    Display, VertexBuffer,
+};
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+const ORBIT_SPEED: f32 = 0.01;
+/// How fast the arrow keys turn the first light, in degrees per second.
+const LIGHT_DEGREES_PER_SECOND: f32 = 90.0;
+/// Each level roughly quadruples the triangle count.
+const MAX_SUBDIVISION_LEVELS: u32 = 4;
+const FRAME_REPORT_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the marker at a pick stays up.
+const PICK_MARKER_FRAMES: u32 = 120;
+const PICK_MARKER_COLOR: [f32; 3] = [1.0, 1.0, 0.0];
+
+/// Adds up the time between drawn frames and prints a summary, together with
+/// what the last frame submitted, once per `FRAME_REPORT_INTERVAL`.
+struct FrameTimer {
+    /// The times of the last summary, for the HUD.
+    last_times: Option<FrameTimes>,
+    last_report: Instant,
+    frames: u32,
+    total: Duration,
+    slowest: Duration,
+}
+
+impl FrameTimer {
+    fn new() -> FrameTimer {
+        FrameTimer {
+            last_times: None,
+            last_report: Instant::now(),
+            frames: 0,
+            total: Duration::ZERO,
+            slowest: Duration::ZERO,
+        }
+    }
+
+    /// Counts a frame that came `delta` after the one before it.
+    fn tick(&mut self, delta: Duration, stats: DrawStats) {
+        let now = Instant::now();
+        if !delta.is_zero() {
+            self.frames += 1;
+            self.total += delta;
+            self.slowest = self.slowest.max(delta);
+        }
+
+        if self.frames > 0 && now - self.last_report >= FRAME_REPORT_INTERVAL {
+            let average = self.total.as_secs_f64() * 1000.0 / self.frames as f64;
+            println!(
+                "{:.2} ms/frame ({:.0} fps, worst {:.2} ms): {} draw calls, {} drawn, {} culled, {} triangles",
+                average,
+                1000.0 / average,
+                self.slowest.as_secs_f64() * 1000.0,
+                stats.draw_calls,
+                stats.instances,
+                stats.culled,
+                stats.triangles,
+            );
+            self.last_times = Some(FrameTimes {
+                average: self.total / self.frames,
+                slowest: self.slowest,
+            });
+            self.last_report = now;
+            self.frames = 0;
+            self.total = Duration::ZERO;
+            self.slowest = Duration::ZERO;
+        }
+    }
+}
+
+/// Interactive state around a loaded `Scene`: GPU resources plus whatever the
+/// user has changed since loading, which `save` writes back out.
+pub struct Viewer {
+    display: Display,
+    renderer: Renderer,
+    scene: Scene,
+    scene_path: PathBuf,
+    meshes: Vec<Mesh>,
+    /// World bounds of the whole scene, which the ground grid lies under.
+    bounds: Aabb,
+    gpu: GpuScene,
+    picker: Picker,
+    /// The picked triangle, drawn over the scene.
+    highlight: Option<VertexBuffer<Vertex>>,
+    /// Node of the last pick, used by "frame selected".
+    selected: Option<usize>,
+    overlays: Overlays,
+    /// The lines of `overlays`, generated when first drawn after a change.
+    overlay_lines: Option<VertexBuffer<LineVertex>>,
+    clock: FrameClock,
+    frame_timer: FrameTimer,
+    text: TextRenderer,
+    /// Draw the statistics panel over the scene.
+    show_hud: bool,
+    gui: Gui,
+    /// Show the editing panels.
+    show_gui: bool,
+    modifiers: ModifiersState,
+    dragging: bool,
+    last_cursor: Option<(f64, f64)>,
+    /// Keys down whose effect lasts as long as they are, which `update`
+    /// applies.
+    held_keys: HashSet<VirtualKeyCode>,
+}
+
+impl Viewer {
+    /// Shows `scene`, whose meshes, loaded from beside `scene_path`, are
+    /// `meshes`.
+    pub fn new(
+        display: Display,
+        mut scene: Scene,
+        scene_path: PathBuf,
+        meshes: Vec<Mesh>,
+        clock: FrameClock,
+    ) -> Viewer {
+        let gpu = GpuScene::new(&display, &scene, &meshes);
+        let picker = Picker::new(&meshes);
+        let bounds = scene.bounds(&meshes);
+        let pixels_per_point = display.gl_window().window().scale_factor() as f32;
+
+        if scene.camera.frame_on_load {
+            let (width, height) = display.get_framebuffer_dimensions();
+            scene.camera.frame(&bounds, width, height);
+            scene.camera.frame_on_load = false;
+        }
+
+        Viewer {
+            renderer: Renderer::new(&display),
+            text: TextRenderer::new(&display),
+            gui: Gui::new(&display, pixels_per_point),
+            display,
+            scene,
+            scene_path,
+            meshes,
+            bounds,
+            gpu,
+            picker,
+            highlight: None,
+            selected: None,
+            overlays: Overlays::default(),
+            overlay_lines: None,
+            clock,
+            frame_timer: FrameTimer::new(),
+            show_hud: true,
+            show_gui: true,
+            modifiers: ModifiersState::empty(),
+            dragging: false,
+            last_cursor: None,
+            held_keys: HashSet::new(),
+        }
+    }
+
+    pub fn handle_window_event(&mut self, event: &WindowEvent) {
+        if self.show_gui && self.gui.on_event(event) {
+            return;
+        }
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = *modifiers,
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => self.dragging = !self.snap_to_axis(),
+            WindowEvent::MouseInput {
+                state: ElementState::Released,
+                button: MouseButton::Left,
+                ..
+            } => self.dragging = false,
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Right,
+                ..
+            } => self.pick(),
+            WindowEvent::CursorMoved { position, .. } => {
+                if let Some((x, y)) = self.last_cursor {
+                    if self.dragging {
+                        let dx = (position.x - x) as f32;
+                        let dy = (position.y - y) as f32;
+                        self.scene.camera.orbit(-dx * ORBIT_SPEED, dy * ORBIT_SPEED);
+                    }
+                }
+                self.last_cursor = Some((position.x, position.y));
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(p) => p.y as f32 / 40.0,
+                };
+                self.scene.camera.zoom(0.9f32.powf(lines));
+            }
+            WindowEvent::KeyboardInput {
+                input:
+                    KeyboardInput {
+                        state,
+                        virtual_keycode: Some(key),
+                        ..
+                    },
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    self.held_keys.insert(*key);
+                    self.handle_key(*key);
+                }
+                ElementState::Released => {
+                    self.held_keys.remove(key);
+                }
+            },
+            WindowEvent::Focused(false) => self.held_keys.clear(),
+            _ => (),
+        }
+    }
+
+    fn handle_key(&mut self, key: VirtualKeyCode) {
+        match key {
+            VirtualKeyCode::S if self.modifiers.ctrl() => self.save(),
+            VirtualKeyCode::E if self.modifiers.ctrl() => self.export(),
+            VirtualKeyCode::F => self.frame_selected(),
+            VirtualKeyCode::A => self.frame_all(),
+            VirtualKeyCode::C => {
+                self.renderer.frustum_culling = !self.renderer.frustum_culling;
+                println!(
+                    "frustum culling {}",
+                    if self.renderer.frustum_culling {
+                        "on"
+                    } else {
+                        "off"
+                    }
+                );
+            }
+            VirtualKeyCode::L => {
+                self.renderer.level_of_detail = !self.renderer.level_of_detail;
+                println!(
+                    "level of detail {}",
+                    if self.renderer.level_of_detail {
+                        "on"
+                    } else {
+                        "off"
+                    }
+                );
+            }
+            VirtualKeyCode::G => {
+                self.renderer.grid = !self.renderer.grid;
+                println!("grid {}", if self.renderer.grid { "on" } else { "off" });
+            }
+            VirtualKeyCode::H => {
+                self.show_hud = !self.show_hud;
+                println!("hud {}", if self.show_hud { "on" } else { "off" });
+            }
+            VirtualKeyCode::Tab => {
+                self.show_gui = !self.show_gui;
+                println!("panels {}", if self.show_gui { "on" } else { "off" });
+                if !self.show_gui {
+                    let window = self.display.gl_window();
+                    window.window().set_cursor_visible(true);
+                    window.window().set_cursor_icon(CursorIcon::Default);
+                }
+            }
+            VirtualKeyCode::W => {
+                self.renderer.render_mode = self.renderer.render_mode.next();
+                println!("render mode {}", self.renderer.render_mode.name());
+            }
+            VirtualKeyCode::F1 => self.toggle_overlay("vertex normals", |o| &mut o.normals),
+            VirtualKeyCode::F2 => self.toggle_overlay("tangents", |o| &mut o.tangents),
+            VirtualKeyCode::F3 => self.toggle_overlay("face normals", |o| &mut o.face_normals),
+            VirtualKeyCode::F4 => self.toggle_overlay("bounds", |o| &mut o.bounds),
+            VirtualKeyCode::F5 => self.toggle_overlay("lights", |o| &mut o.lights),
+            VirtualKeyCode::F6 => self.toggle_overlay("axes", |o| &mut o.axes),
+            VirtualKeyCode::V => self.validate(),
+            VirtualKeyCode::RBracket => self.change_subdivision(1),
+            VirtualKeyCode::LBracket => self.change_subdivision(-1),
+            _ => (),
+        }
+    }
+
+    /// Advances everything that moves on its own by one fixed `step`; for now
+    /// the first light, which turns while arrow keys are held.
+    fn update(&mut self, step: Duration) {
+        let held = |key| f32::from(u8::from(self.held_keys.contains(&key)));
+        let x = held(VirtualKeyCode::Up) - held(VirtualKeyCode::Down);
+        let y = held(VirtualKeyCode::Left) - held(VirtualKeyCode::Right);
+        if x != 0.0 || y != 0.0 {
+            let degrees = LIGHT_DEGREES_PER_SECOND * step.as_secs_f32();
+            self.rotate_light(x * degrees, y * degrees);
+        }
+    }
+
+    fn toggle_overlay(&mut self, name: &str, flag: impl Fn(&mut Overlays) -> &mut bool) {
+        let flag = flag(&mut self.overlays);
+        *flag = !*flag;
+        println!("{} overlay {}", name, if *flag { "on" } else { "off" });
+        self.overlay_lines = None;
+    }
+
+    /// Turns the first light of the scene around the X and Y axes.
+    fn rotate_light(&mut self, x_degrees: f32, y_degrees: f32) {
+        self.overlay_lines = None;
+        let rotation = math::rotation([x_degrees, y_degrees, 0.0]);
+        if let Some(light) = self.scene.lights.first_mut() {
+            match &mut light.kind {
+                LightKind::Directional { direction } => {
+                    *direction = math::transform_vector(&rotation, *direction)
+                }
+                LightKind::Point { position } => {
+                    *position = math::transform_point(&rotation, *position)
+                }
+            }
+        }
+    }
+
+    /// Prints what `repair::validate` finds in the mesh of the selected
+    /// node, or in every mesh when nothing is selected.
+    fn validate(&self) {
+        let selected = self
+            .selected
+            .and_then(|node| self.scene.mesh_index(&self.scene.nodes[node].mesh));
+        for (index, (desc, mesh)) in self.scene.meshes.iter().zip(&self.meshes).enumerate() {
+            if selected.is_none_or(|selected| selected == index) {
+                let winding = match winding::detect(mesh) {
+                    Some(winding) => winding.name(),
+                    None => "unknown",
+                };
+                println!(
+                    "mesh `{}`: {} winding, {}",
+                    desc.name,
+                    winding,
+                    repair::validate(mesh)
+                );
+            }
+        }
+    }
+
+    /// Adds `delta` subdivision levels to the mesh of the selected node,
+    /// starting Catmull-Clark subdivision on meshes that had none, and
+    /// rebuilds everything derived from it.
+    fn change_subdivision(&mut self, delta: i32) {
+        let node = match self.selected {
+            Some(node) => &self.scene.nodes[node],
+            None => return,
+        };
+        let index = match self.scene.mesh_index(&node.mesh) {
+            Some(index) => index,
+            None => return,
+        };
+        let desc = &mut self.scene.meshes[index];
+        let levels = desc.subdivision.as_ref().map_or(0, |s| s.levels);
+        let levels = levels
+            .saturating_add_signed(delta)
+            .min(MAX_SUBDIVISION_LEVELS);
+        match &mut desc.subdivision {
+            Some(subdivision) => subdivision.levels = levels,
+            None if levels > 0 => {
+                desc.subdivision = Some(Subdivision::new(Scheme::CatmullClark, levels))
+            }
+            None => return,
+        }
+        println!("mesh `{}` subdivision level {}", desc.name, levels);
+
+        let base_dir = self
+            .scene_path
+            .parent()
+            .map(PathBuf::from)
+            .unwrap_or_default();
+        let mesh = match self.scene.load_mesh(index, &base_dir) {
+            Ok((mesh, log)) => {
+                log.print(&self.scene.meshes[index].name);
+                mesh
+            }
+            Err(err) => {
+                eprintln!("failed to reload mesh: {}", err);
+                return;
+            }
+        };
+        self.gpu
+            .update_mesh(&self.display, &self.scene, index, &mesh);
+        self.picker.update_mesh(index, &mesh);
+        self.meshes[index] = mesh;
+        self.bounds = self.scene.bounds(&self.meshes);
+        self.highlight = None;
+        self.overlay_lines = None;
+    }
+
+    /// Fits the camera to the last picked node, or to the whole scene when
+    /// nothing is selected.
+    fn frame_selected(&mut self) {
+        let bounds = match self.selected {
+            Some(node) => self.scene.node_bounds(node, &self.meshes),
+            None => self.scene.bounds(&self.meshes),
+        };
+        let (width, height) = self.display.get_framebuffer_dimensions();
+        self.scene.camera.frame(&bounds, width, height);
+    }
+
+    fn frame_all(&mut self) {
+        let (width, height) = self.display.get_framebuffer_dimensions();
+        self.scene.camera.frame(&self.bounds, width, height);
+    }
+
+    /// Snaps the camera to look along the gizmo axis under the cursor, if
+    /// there is one. Returns whether there was.
+    fn snap_to_axis(&mut self) -> bool {
+        let Some(cursor) = self.last_cursor else {
+            return false;
+        };
+        let window = self.display.get_framebuffer_dimensions();
+        let Some(side) = gizmo::pick(&self.scene.camera, window, cursor) else {
+            return false;
+        };
+        self.scene.camera.snap(side);
+        let axis = side.iter().position(|&c| c != 0.0).unwrap_or(0);
+        let sign = if side[axis] > 0.0 { '+' } else { '-' };
+        println!("view from {}{}", sign, ["X", "Y", "Z"][axis]);
+        true
+    }
+
+    /// Casts a ray under the cursor, reports what it hits and highlights the
+    /// hit triangle.
+    fn pick(&mut self) {
+        let cursor = match self.last_cursor {
+            Some(cursor) => cursor,
+            None => return,
+        };
+        let (width, height) = self.display.get_framebuffer_dimensions();
+        let camera = &self.scene.camera;
+        let ray = match Ray::from_cursor(
+            cursor,
+            (width, height),
+            &camera.perspective(width, height),
+            &camera.view_matrix(),
+        ) {
+            Some(ray) => ray,
+            None => return,
+        };
+
+        let hit = match self.picker.pick(&self.scene, &self.meshes, &ray) {
+            Some(hit) => hit,
+            None => {
+                println!("picked nothing");
+                self.highlight = None;
+                self.selected = None;
+                return;
+            }
+        };
+
+        self.selected = Some(hit.node);
+        let node = &self.scene.nodes[hit.node];
+        match hit.instance {
+            Some(instance) => print!("picked node `{}` instance {}", node.name, instance),
+            None => print!("picked node `{}`", node.name),
+        }
+        println!(
+            ", triangle {} at {:?}, barycentric {:?}",
+            hit.triangle, hit.position, hit.barycentric
+        );
+
+        // Marks the hit with an arrow along the triangle's normal, labelled
+        // with the node, sized by the hit triangle.
+        let [a, b, c] = hit.corners;
+        let normal = math::cross(math::sub(c, a), math::sub(b, a));
+        let size = math::length(math::sub(b, a)).max(math::length(math::sub(c, a)));
+        let tip = math::add(
+            hit.position,
+            math::scale(math::normalize(normal), 2.0 * size),
+        );
+        let style = Style::color(PICK_MARKER_COLOR)
+            .on_top()
+            .frames(PICK_MARKER_FRAMES);
+        debug_draw::arrow(hit.position, tip, size / 2.0, style);
+        debug_draw::text3d(
+            math::add(tip, math::scale(math::normalize(normal), size / 2.0)),
+            &node.name,
+            size / 2.0,
+            style,
+        );
+
+        let corners = hit.corners.map(|[x, y, z]| Vertex {
+            position: (x, y, z),
+        });
+        self.highlight = Some(
+            VertexBuffer::new(&self.display, &corners)
+                .expect("failed to create highlight VertexBuffer!"),
+        );
+    }
+
+    pub fn save(&self) {
+        match self.scene.save(&self.scene_path) {
+            Ok(()) => println!("saved scene to {}", self.scene_path.display()),
+            Err(err) => eprintln!("failed to save scene: {}", err),
+        }
+    }
+
+    /// Writes the scene as it is drawn, meshes included, to a `.glb` beside
+    /// the scene file.
+    pub fn export(&self) {
+        let path = self.scene_path.with_extension("glb");
+        match gltf::save(&self.scene, &self.meshes, &path) {
+            Ok(()) => println!("exported scene to {}", path.display()),
+            Err(err) => eprintln!("failed to export scene: {}", err),
+        }
+    }
+
+    /// Runs a frame of the editing panels and rebuilds whatever their edits
+    /// made stale.
+    fn update_gui(&mut self) {
+        let mut changes = Changes::default();
+        let mut panels = Panels {
+            scene: &mut self.scene,
+            renderer: &mut self.renderer,
+            overlays: &mut self.overlays,
+            show_hud: &mut self.show_hud,
+            selected: &mut self.selected,
+            clock: &mut self.clock,
+        };
+        let cursor = self
+            .gui
+            .run(self.display.get_framebuffer_dimensions(), |context| {
+                panels.show(context, &mut changes)
+            });
+        {
+            let window = self.display.gl_window();
+            window.window().set_cursor_visible(cursor.is_some());
+            if let Some(cursor) = cursor {
+                window.window().set_cursor_icon(cursor);
+            }
+        }
+
+        if changes.lights || changes.overlays {
+            self.overlay_lines = None;
+        }
+        if changes.nodes {
+            self.bounds = self.scene.bounds(&self.meshes);
+            self.overlay_lines = None;
+            self.highlight = None;
+        }
+        if changes.selection {
+            self.highlight = None;
+        }
+        if changes.frame_all {
+            self.frame_all();
+        }
+        if changes.frame_selected {
+            self.frame_selected();
+        }
+    }
+
+    /// When the clock next wants a frame, or `None` if one is due now.
+    pub fn next_frame(&self) -> Option<Instant> {
+        self.clock.next_frame()
+    }
+
+    pub fn request_redraw(&self) {
+        self.display.gl_window().window().request_redraw();
+    }
+
+    /// Draws a frame, first simulating the fixed steps that fit into the time
+    /// since the last one.
+    pub fn frame(&mut self) {
+        let time = self.clock.tick();
+        for _ in 0..time.steps {
+            self.update(self.clock.step());
+        }
+        self.draw(time.delta);
+    }
+
+    fn draw(&mut self, delta: Duration) {
+        if self.show_gui {
+            self.update_gui();
+        }
+        let mut target_frame = self.display.draw();
+        let stats = self
+            .renderer
+            .draw_scene(&mut target_frame, &self.scene, &self.gpu);
+        self.renderer
+            .draw_grid(&mut target_frame, &self.scene, &self.bounds);
+        if let Some(highlight) = &self.highlight {
+            self.renderer
+                .draw_highlight(&mut target_frame, &self.scene, highlight);
+        }
+        if self.overlays.any() {
+            let lines = self.overlay_lines.get_or_insert_with(|| {
+                VertexBuffer::new(
+                    &self.display,
+                    &self.overlays.lines(&self.scene, &self.meshes),
+                )
+                .expect("failed to create overlay VertexBuffer!")
+            });
+            self.renderer
+                .draw_lines(&mut target_frame, &self.scene, lines);
+        }
+        self.renderer
+            .draw_debug(&self.display, &mut target_frame, &self.scene);
+        self.renderer
+            .draw_gizmo(&self.display, &mut target_frame, &self.scene.camera);
+        if self.show_hud {
+            Hud {
+                frame_times: self.frame_timer.last_times,
+                stats,
+                camera: &self.scene.camera,
+                render_mode: self.renderer.render_mode,
+            }
+            .queue(&mut self.text);
+        }
+        self.text.draw(&self.display, &mut target_frame);
+        if self.show_gui {
+            self.gui.draw(&self.display, &mut target_frame);
+        }
+        target_frame.finish().expect("failed to draw on screen");
+        self.frame_timer.tick(delta, stats);
+    }
+}
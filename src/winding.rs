@@ -0,0 +1,192 @@
+use crate::math::{self, Vec3};
+use crate::mesh::{Mesh, Normal};
+use serde::{Deserialize, Serialize};
+
+/// The way a mesh's triangles turn when seen from their front in world
+/// space. `Mesh` uses `Clockwise`, as the teapot does.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Winding {
+    Clockwise,
+    CounterClockwise,
+}
+
+impl Winding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Winding::Clockwise => "clockwise",
+            Winding::CounterClockwise => "counter-clockwise",
+        }
+    }
+}
+
+/// How a mesh's winding is found and used, as set per mesh in scene files.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum WindingMode {
+    /// Detect the winding and cull whichever side that makes the back.
+    #[default]
+    Detect,
+    /// Detect the winding and flip meshes wound the other way, so they draw
+    /// like every other mesh.
+    Flip,
+    /// Trust the given winding without looking.
+    Assume(Winding),
+    /// Draw both sides of every triangle.
+    TwoSided,
+}
+
+impl WindingMode {
+    /// The winding to cull `mesh` by, or `None` to draw both sides.
+    pub fn resolve(self, mesh: &Mesh) -> Option<Winding> {
+        match self {
+            WindingMode::Detect | WindingMode::Flip => detect(mesh),
+            WindingMode::Assume(winding) => Some(winding),
+            WindingMode::TwoSided => None,
+        }
+    }
+}
+
+/// Signed volumes smaller than this fraction of the bounding box volume are
+/// taken as rounding noise, as for flat or open meshes.
+const MIN_RELATIVE_VOLUME: f32 = 1e-3;
+
+/// Normal agreement weaker than this fraction of the total area is no
+/// evidence either way.
+const MIN_AGREEMENT: f32 = 0.25;
+
+/// Finds the dominant winding of `mesh`: from the sign of its volume when it
+/// encloses one, otherwise from which side its normals point to. `None` when
+/// neither tells, as for a flat mesh without normals.
+pub fn detect(mesh: &Mesh) -> Option<Winding> {
+    if mesh.indices.is_empty() {
+        return None;
+    }
+
+    // Measured about the centroid so that open meshes far from the origin
+    // are not swamped by the cone to it.
+    let bounds = mesh.bounds();
+    let center = math::scale(math::add(bounds.min, bounds.max), 0.5);
+    let volume: f32 = mesh
+        .triangles()
+        .map(|t| {
+            let [a, b, c] = t.map(|i| math::sub(mesh.position(i), center));
+            math::dot(a, math::cross(b, c)) / 6.0
+        })
+        .sum();
+    let size = math::sub(bounds.max, bounds.min);
+    if volume.abs() > MIN_RELATIVE_VOLUME * size[0] * size[1] * size[2] {
+        return Some(if volume < 0.0 {
+            Winding::Clockwise
+        } else {
+            Winding::CounterClockwise
+        });
+    }
+
+    if mesh.normals.len() != mesh.positions.len() {
+        return None;
+    }
+    let (agreement, area) = normal_agreement(mesh);
+    if agreement.abs() <= MIN_AGREEMENT * area {
+        return None;
+    }
+    Some(if agreement > 0.0 {
+        Winding::Clockwise
+    } else {
+        Winding::CounterClockwise
+    })
+}
+
+/// Area of the triangles whose corner normals agree with their clockwise
+/// front, minus the area of those that disagree, and the total area.
+fn normal_agreement(mesh: &Mesh) -> (f32, f32) {
+    mesh.triangles().fold((0.0, 0.0), |(agreement, area), t| {
+        let face = mesh.face_normal(t);
+        let normals: Vec3 = t
+            .iter()
+            .fold([0.0; 3], |sum, &i| math::add(sum, mesh.normal(i)));
+        let size = math::length(face);
+        (
+            agreement + size * math::dot(face, normals).signum(),
+            area + size,
+        )
+    })
+}
+
+/// Flips `mesh` to the clockwise winding if it is wound the other way, and
+/// turns its normals around if they then face inwards. Returns whether the
+/// triangles flipped.
+pub fn orient(mesh: &mut Mesh) -> bool {
+    if detect(mesh) != Some(Winding::CounterClockwise) {
+        return false;
+    }
+    for triangle in mesh.indices.chunks_exact_mut(3) {
+        triangle.swap(1, 2);
+    }
+    if mesh.normals.len() == mesh.positions.len() && normal_agreement(mesh).0 < 0.0 {
+        for normal in &mut mesh.normals {
+            let (x, y, z) = normal.normal;
+            *normal = Normal {
+                normal: (-x, -y, -z),
+            };
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives;
+
+    fn flipped(mesh: &Mesh) -> Mesh {
+        let mut mesh = mesh.clone();
+        for triangle in mesh.indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+        mesh
+    }
+
+    #[test]
+    fn closed_meshes_by_volume() {
+        let cube = primitives::cube(2.0, 1);
+        assert_eq!(detect(&cube), Some(Winding::Clockwise));
+        assert_eq!(detect(&flipped(&cube)), Some(Winding::CounterClockwise));
+        // The volume alone decides, even against normals turned inwards.
+        let mut inside_out = flipped(&cube);
+        for normal in &mut inside_out.normals {
+            let (x, y, z) = normal.normal;
+            normal.normal = (-x, -y, -z);
+        }
+        assert_eq!(detect(&inside_out), Some(Winding::CounterClockwise));
+    }
+
+    #[test]
+    fn flat_meshes_by_normals() {
+        let plane = primitives::plane(1.0, 1.0, 2, 2);
+        assert_eq!(detect(&plane), Some(Winding::Clockwise));
+        assert_eq!(detect(&flipped(&plane)), Some(Winding::CounterClockwise));
+        let mut bare = plane.clone();
+        bare.normals.clear();
+        assert_eq!(detect(&bare), None);
+    }
+
+    #[test]
+    fn orient_flips_only_counter_clockwise_meshes() {
+        let cube = primitives::cube(1.0, 1);
+        let mut mesh = flipped(&cube);
+        assert!(orient(&mut mesh));
+        assert_eq!(mesh.indices, cube.indices);
+        assert!(!orient(&mut mesh));
+        assert_eq!(mesh.indices, cube.indices);
+    }
+
+    #[test]
+    fn modes() {
+        let cube = primitives::cube(1.0, 1);
+        assert_eq!(WindingMode::Detect.resolve(&cube), Some(Winding::Clockwise));
+        assert_eq!(
+            WindingMode::Assume(Winding::CounterClockwise).resolve(&cube),
+            Some(Winding::CounterClockwise)
+        );
+        assert_eq!(WindingMode::TwoSided.resolve(&cube), None);
+    }
+}